@@ -1,2 +1,5 @@
+for /f %%i in ('git rev-parse HEAD') do set NEP330_BUILD_INFO_SOURCE_CODE_SNAPSHOT=git+https://github.com/arquam-ejaz/raffle-dapp?rev=%%i
+set NEP330_BUILD_INFO_BUILD_COMMAND=build.bat
+set NEP330_BUILD_INFO_OUTPUT_WASM_PATH=res/raffle_dapp.wasm
 cargo build --target wasm32-unknown-unknown --release
 copy target\wasm32-unknown-unknown\release\*.wasm res
\ No newline at end of file
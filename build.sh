@@ -1,6 +1,10 @@
 #!/bin/bash
 set -e
 
-RUSTFLAGS='-C link-arg=-s' cargo build --target wasm32-unknown-unknown --release
-cp target/wasm32-unknown-unknown/release/*.wasm ./res/
+# Records the commit the wasm is built from in its NEP-330 source metadata
+export NEP330_BUILD_INFO_SOURCE_CODE_SNAPSHOT="git+https://github.com/arquam-ejaz/raffle-dapp?rev=$(git rev-parse HEAD)"
+export NEP330_BUILD_INFO_BUILD_COMMAND="./build.sh $*"
+export NEP330_BUILD_INFO_OUTPUT_WASM_PATH="res/raffle_dapp.wasm"
 
+RUSTFLAGS='-C link-arg=-s' cargo build --target wasm32-unknown-unknown --release "$@"
+cp target/wasm32-unknown-unknown/release/*.wasm ./res/
@@ -0,0 +1,1122 @@
+use crate::events::log_event;
+use crate::finalize::FINALIZE_WINDOW;
+use crate::participation::EntryRateLimit;
+use crate::raffle::{RaffleStore, ENTRY_STORAGE_RESERVE, FLAG_WITHDRAWALS, MAX_PARTICIPANTS};
+use crate::*;
+use near_sdk::json_types::U128;
+use serde_json::json;
+
+// upper bound for the share (in percent) of a ticket's resale price going to the organizer
+pub(crate) const MAX_RESALE_ROYALTY: u8 = 50;
+
+// upper bound for the share (in percent) of the locked tokens kept when a participant leaves
+pub(crate) const MAX_WITHDRAWAL_PENALTY: u8 = 10;
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Limits every account to `max_entries` raffle entries per `window` (in ms) across the
+    // contract, to slow down bots entering every raffle. A `max_entries` of 0 removes the limit.
+    pub fn set_entry_rate_limit(&mut self, max_entries: u32, window: Timestamp) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can set the entry rate limit"
+        );
+
+        if max_entries == 0 {
+            self.entry_rate_limit = None;
+            env::log_str("Entry rate limit removed");
+            return;
+        }
+
+        assert!(window > 0, "The rate limiting window cannot be empty");
+
+        self.entry_rate_limit = Some(EntryRateLimit {
+            max_entries,
+            window: window * TO_FROM_NANOSECONDS,
+        });
+
+        env::log_str(&format!(
+            "Accounts can now enter {} raffle(s) every {} ms",
+            max_entries, window
+        ));
+    }
+
+    // Limits every account to `max_entries` entries locking no tokens per `window` (in ms), the
+    // sponsored entries and the entries of free-entry raffles. A `max_entries` of 0 removes the
+    // limit.
+    pub fn set_free_entry_limit(&mut self, max_entries: u32, window: Timestamp) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can set the free entry limit"
+        );
+
+        if max_entries == 0 {
+            self.free_entry_limit = None;
+            env::log_str("Free entry limit removed");
+            return;
+        }
+
+        assert!(window > 0, "The rate limiting window cannot be empty");
+
+        self.free_entry_limit = Some(EntryRateLimit {
+            max_entries,
+            window: window * TO_FROM_NANOSECONDS,
+        });
+
+        env::log_str(&format!(
+            "Accounts can now enter {} free raffle(s) every {} ms",
+            max_entries, window
+        ));
+    }
+
+    // Lets the raffle owner designate an operator account (e.g. a hot wallet) that can
+    // finalize the raffle on their behalf. Passing `None` removes the current operator.
+    pub fn set_operator(&mut self, raffle_id: String, operator: Option<String>) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can set its operator"
+        );
+
+        let operator = operator.map(|id| AccountId::try_from(id).unwrap());
+        assert!(
+            operator.is_none() || operator != raffle_details.approver,
+            "The approver of the raffle cannot be its operator"
+        );
+
+        raffle_details.operator = operator;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        match raffle_details.operator {
+            Some(operator) => env::log_str(&format!(
+                "{:?} can now finalize the raffle of {:?}",
+                operator.to_string(),
+                raffle_account_id.to_string()
+            )),
+            None => env::log_str(&format!(
+                "Operator removed from the raffle of {:?}",
+                raffle_account_id.to_string()
+            )),
+        }
+    }
+
+    // Sets the account receiving the funds swept from expired raffles
+    pub fn set_treasury(&mut self, treasury: String) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can set the treasury"
+        );
+
+        self.treasury = AccountId::try_from(treasury).unwrap();
+
+        env::log_str(&format!(
+            "Expired raffles will now be swept to {:?}",
+            self.treasury.to_string()
+        ));
+    }
+
+    // Proposes `new_owner` as the raffle's new owner, e.g. when the organizer rotates keys or
+    // hands the raffle over to a team multisig. The transfer only takes effect once the new
+    // owner accepts it through `accept_raffle`.
+    pub fn transfer_raffle(&mut self, raffle_id: String, new_owner: String) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+        let new_owner_id: AccountId = AccountId::try_from(new_owner).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can transfer the raffle"
+        );
+
+        assert_ne!(
+            new_owner_id, raffle_details.owner,
+            "You already own this raffle"
+        );
+
+        assert!(
+            raffle_details.participants.get(&new_owner_id).is_none()
+                && raffle_details
+                    .waitlist_positions
+                    .get(&new_owner_id)
+                    .is_none(),
+            "A participant of the raffle cannot become its owner"
+        );
+
+        raffle_details.pending_owner = Some(new_owner_id.clone());
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        env::log_str(&format!(
+            "{:?} has been proposed as the new owner of the raffle {:?}",
+            new_owner_id.to_string(),
+            raffle_account_id.to_string()
+        ));
+    }
+
+    pub fn accept_raffle(&mut self, raffle_id: String) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            Some(env::predecessor_account_id()),
+            raffle_details.pending_owner,
+            "You have not been proposed as the owner of this raffle"
+        );
+
+        assert!(
+            raffle_details
+                .participants
+                .get(&env::predecessor_account_id())
+                .is_none()
+                && raffle_details
+                    .waitlist_positions
+                    .get(&env::predecessor_account_id())
+                    .is_none(),
+            "A participant of the raffle cannot become its owner"
+        );
+        assert_ne!(
+            Some(env::predecessor_account_id()),
+            raffle_details.approver,
+            "The approver of the raffle cannot become its owner"
+        );
+
+        let previous_owner = raffle_details.owner;
+        raffle_details.owner = env::predecessor_account_id();
+        raffle_details.pending_owner = None;
+        // The operator was chosen by the previous owner, the new owner has to designate its own
+        raffle_details.operator = None;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        env::log_str(&format!(
+            "The raffle {:?} has been transferred from {:?} to {:?}",
+            raffle_account_id.to_string(),
+            previous_owner.to_string(),
+            env::predecessor_account_id().to_string()
+        ));
+    }
+
+    // Lets the raffle owner remove a participant (e.g. for violating the raffle's rules) before
+    // the raffle ends. The participant's locked tokens are refunded and the slot is freed. The
+    // storage of an entry locking no tokens was paid from the allowance of sponsored entries,
+    // which gets back the storage released.
+    pub fn remove_participant(&mut self, raffle_id: String, account: String) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+        let participant_id: AccountId = AccountId::try_from(account).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can remove participants"
+        );
+
+        assert!(
+            env::block_timestamp() < raffle_details.end,
+            "Participants can only be removed before the raffle ends"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        let locked_tokens = raffle_details
+            .remove_entry(&participant_id)
+            .unwrap_or_else(|| env::panic_str("The account is not participating in this raffle"));
+        raffle_details.record_exit();
+        raffle_details.ticket_listings.remove(&participant_id);
+        raffle_details.return_pooled_nft(&participant_id, self.gas_config.nft_transfer);
+        if locked_tokens == 0 {
+            raffle_details.sponsor_pool +=
+                (initial_storage_usage.saturating_sub(env::storage_usage())) as Balance
+                    * env::storage_byte_cost();
+        }
+
+        let promoted = raffle_details.promote_from_waitlist(&raffle_account_id);
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+        if let Some(promoted) = promoted {
+            self.internal_resolve_instant_win(&raffle_account_id, &promoted);
+        }
+
+        if locked_tokens > 0 {
+            Promise::new(participant_id.clone()).transfer(locked_tokens);
+        }
+
+        log_event(
+            "participant_removed",
+            json!({
+                "raffle_id": raffle_account_id.to_string(),
+                "account_id": participant_id.to_string(),
+                "refund": locked_tokens.to_string(),
+            }),
+        );
+    }
+
+    // Adds the attached deposit to the allowance paying for sponsored entries, letting accounts
+    // without funds (e.g. Keypom drops or trial accounts) enter through `participate_sponsored`.
+    // What is left of the allowance goes back to the owner once the raffle is settled.
+    #[payable]
+    pub fn fund_sponsored_entries(&mut self, raffle_id: String) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can fund sponsored entries"
+        );
+
+        assert!(
+            env::block_timestamp() < raffle_details.end,
+            "Sponsored entries can only be funded before the raffle ends"
+        );
+
+        raffle_details.sponsor_pool += env::attached_deposit();
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        env::log_str(&format!(
+            "The sponsorship allowance of the raffle {:?} is now {} yoctoNEAR",
+            raffle_account_id.to_string(),
+            raffle_details.sponsor_pool
+        ));
+    }
+
+    // Raises the participants limit of a running raffle. Like at registration, the prize has to
+    // back the storage of the entries up to the new limit, so the attached deposit should cover
+    // `ENTRY_STORAGE_RESERVE` bytes for each additional participant and is added to the prize.
+    // The accounts of the waitlist are promoted into the new slots.
+    #[payable]
+    pub fn raise_max_participants(&mut self, raffle_id: String, max_participants: u64) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can raise its participants limit"
+        );
+
+        assert!(
+            env::block_timestamp() < raffle_details.entries_close(),
+            "The participants limit can only be raised while the raffle accepts entries"
+        );
+
+        let previous_max_participants = raffle_details.max_participants;
+        assert!(
+            max_participants > previous_max_participants && max_participants <= MAX_PARTICIPANTS,
+            "The new participants limit should be greater than {} and at most {}",
+            previous_max_participants,
+            MAX_PARTICIPANTS
+        );
+
+        let reserve = (max_participants - previous_max_participants) as Balance
+            * ENTRY_STORAGE_RESERVE as Balance
+            * env::storage_byte_cost();
+        assert!(
+            env::attached_deposit() >= reserve,
+            "The attached deposit should cover the storage of the additional participants, {} yoctoNEAR",
+            reserve
+        );
+
+        raffle_details.max_participants = max_participants;
+        raffle_details.prize += reserve;
+        let mut promoted = Vec::new();
+        while !raffle_details.waitlist.is_empty()
+            && raffle_details.participants.len() < max_participants
+        {
+            promoted.extend(raffle_details.promote_from_waitlist(&raffle_account_id));
+        }
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+        for account_id in promoted {
+            self.internal_resolve_instant_win(&raffle_account_id, &account_id);
+        }
+
+        let refund = env::attached_deposit() - reserve;
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+
+        log_event(
+            "max_participants_raised",
+            json!({
+                "raffle_id": raffle_account_id.to_string(),
+                "previous_max_participants": previous_max_participants,
+                "max_participants": max_participants,
+            }),
+        );
+    }
+
+    // Sets the percentage of every ticket resale paid to the raffle's owner
+    pub fn set_resale_royalty(&mut self, raffle_id: String, royalty: u8) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can set the resale royalty"
+        );
+
+        assert!(
+            royalty <= MAX_RESALE_ROYALTY,
+            "The resale royalty cannot exceed {}%",
+            MAX_RESALE_ROYALTY
+        );
+
+        raffle_details.resale_royalty = royalty;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        env::log_str(&format!(
+            "The resale royalty of the raffle {:?} is now {}%",
+            raffle_account_id.to_string(),
+            royalty
+        ));
+    }
+
+    // Keeps `penalty` percent of the locked tokens of the participants leaving the raffle, adding
+    // it to the prize. The penalty can only be raised before anyone entered the raffle, so that
+    // it never exceeds the one the participants agreed to when they entered.
+    pub fn set_withdrawal_penalty(&mut self, raffle_id: String, penalty: u8) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can set the withdrawal penalty"
+        );
+
+        assert!(
+            raffle_details.has_flag(FLAG_WITHDRAWALS),
+            "Withdrawals are not enabled for this raffle"
+        );
+
+        assert!(
+            penalty <= MAX_WITHDRAWAL_PENALTY,
+            "The withdrawal penalty cannot exceed {}%",
+            MAX_WITHDRAWAL_PENALTY
+        );
+
+        assert!(
+            penalty <= raffle_details.withdrawal_penalty || raffle_details.participants.is_empty(),
+            "The withdrawal penalty can only be raised before anyone entered the raffle"
+        );
+
+        raffle_details.withdrawal_penalty = penalty;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        env::log_str(&format!(
+            "The withdrawal penalty of the raffle {:?} is now {}%",
+            raffle_account_id.to_string(),
+            penalty
+        ));
+    }
+
+    // Requires `approver` (e.g. a compliance signer) to approve the draw before the prize is paid
+    // out when it is at least `threshold`. Passing `None` removes the approver. The approver has
+    // to be another account than the owner and the operator, and both the approver and the
+    // threshold are frozen once anyone entered the raffle.
+    pub fn set_finalize_approver(
+        &mut self,
+        raffle_id: String,
+        approver: Option<String>,
+        threshold: U128,
+    ) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can set its approver"
+        );
+
+        assert!(
+            raffle_details.draw_block.is_none(),
+            "The approver cannot be changed once the raffle is being finalized"
+        );
+        assert!(
+            raffle_details.participants.is_empty(),
+            "The approver can only be changed before anyone entered the raffle"
+        );
+        assert!(
+            approver.is_none() || raffle_details.pick_range.is_none(),
+            "The draw of a number pick raffle cannot need an approval"
+        );
+
+        let approver = approver.map(|id| AccountId::try_from(id).unwrap());
+        if let Some(approver) = &approver {
+            assert!(
+                approver != &raffle_details.owner
+                    && Some(approver) != raffle_details.operator.as_ref(),
+                "The approver has to be another account than the raffle's owner and operator"
+            );
+        }
+
+        raffle_details.approver = approver;
+        raffle_details.approval_threshold = threshold.0;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        match raffle_details.approver {
+            Some(approver) => env::log_str(&format!(
+                "{:?} has to approve the draw of the raffle of {:?} when its prize is at least {} yoctoNEAR",
+                approver.to_string(),
+                raffle_account_id.to_string(),
+                threshold.0
+            )),
+            None => env::log_str(&format!(
+                "Approver removed from the raffle of {:?}",
+                raffle_account_id.to_string()
+            )),
+        }
+    }
+
+    // Closes the entries `minutes` before the end of the raffle, so that nobody can join at the
+    // last second knowing the conditions of the draw
+    pub fn set_entry_cutoff(&mut self, raffle_id: String, minutes: u64) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can set its entry cutoff"
+        );
+
+        let entry_cutoff = minutes * 60 * 1_000 * TO_FROM_NANOSECONDS;
+        assert!(
+            entry_cutoff < raffle_details.end - raffle_details.start,
+            "The entry cutoff should be shorter than the raffle"
+        );
+
+        raffle_details.entry_cutoff = entry_cutoff;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        env::log_str(&format!(
+            "Entries to the raffle {:?} now close {} minute(s) before its end",
+            raffle_account_id.to_string(),
+            minutes
+        ));
+    }
+
+    // Restricts the entries to the accounts on the allowlist of the raffle for the first `minutes`
+    // after its start, the raffle opening to everyone afterwards. A `minutes` of 0 keeps the
+    // raffle restricted to the allowlist until its end.
+    pub fn set_priority_window(&mut self, raffle_id: String, minutes: u64) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can set its priority window"
+        );
+
+        assert!(
+            raffle_details.allowlist_root.is_some(),
+            "Only the raffles with an allowlist can have a priority window"
+        );
+
+        assert!(
+            env::block_timestamp() < raffle_details.start,
+            "The priority window can only be changed before the raffle starts"
+        );
+
+        let priority_window = minutes * 60 * 1_000 * TO_FROM_NANOSECONDS;
+        assert!(
+            priority_window < raffle_details.end - raffle_details.start,
+            "The priority window should be shorter than the raffle"
+        );
+
+        raffle_details.priority_window = priority_window;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        match raffle_details.public_from() {
+            Some(public_from) => env::log_str(&format!(
+                "The raffle {:?} opens to everyone at {} ms, {} minute(s) after its start",
+                raffle_account_id.to_string(),
+                public_from / TO_FROM_NANOSECONDS,
+                minutes
+            )),
+            None => env::log_str(&format!(
+                "The raffle {:?} is restricted to its allowlist until its end",
+                raffle_account_id.to_string()
+            )),
+        }
+    }
+
+    // Draws the winner `minutes` after the end of the raffle, leaving time to settle disputes
+    // about the last entries and keeping the draw away from the block the raffle ends in
+    pub fn set_draw_delay(&mut self, raffle_id: String, minutes: u64) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can set its draw delay"
+        );
+
+        assert!(
+            env::block_timestamp() <= raffle_details.end,
+            "The draw delay can only be changed before the raffle ends"
+        );
+
+        let draw_delay = minutes * 60 * 1_000 * TO_FROM_NANOSECONDS;
+        assert!(
+            draw_delay < FINALIZE_WINDOW,
+            "The draw delay should be shorter than the finalization window"
+        );
+
+        raffle_details.draw_delay = draw_delay;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        env::log_str(&format!(
+            "The winner of the raffle {:?} will be drawn {} minute(s) after its end",
+            raffle_account_id.to_string(),
+            minutes
+        ));
+    }
+
+    // Shows or hides the raffle from the listing views, a hidden raffle can still be joined by id
+    pub fn set_raffle_visibility(&mut self, raffle_id: String, visible: bool) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can change its visibility"
+        );
+
+        raffle_details.visible = visible;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        env::log_str(&format!(
+            "The raffle {:?} is now {}",
+            raffle_account_id.to_string(),
+            if visible { "listed" } else { "unlisted" }
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::test_utils::{get_created_receipts, get_logs};
+    use near_sdk::testing_env;
+
+    #[test]
+    #[should_panic(expected = "Only the raffle's owner can set its operator")]
+    fn check_set_operator_not_owner() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.predecessor_account_id(bob_account_id());
+        testing_env!(context.build());
+
+        contract.set_operator(
+            alice_account_id().to_string(),
+            Some(bob_account_id().to_string()),
+        );
+    }
+
+    #[test]
+    fn check_raise_max_participants() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            Some(1),
+            None,
+            None,
+            None,
+        );
+        let prize = contract.raffles.get(&alice_account_id()).unwrap().prize;
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.predecessor_account_id(jacob_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.join_waitlist(alice_account_id().to_string(), None, None);
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.raise_max_participants(alice_account_id().to_string(), 3);
+
+        // jacob takes one of the new slots
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(raffle_details.max_participants, 3);
+        assert_eq!(
+            raffle_details.participants.get(&jacob_account_id()),
+            Some(2 * ONE_NEAR)
+        );
+        assert!(raffle_details.waitlist.is_empty());
+        assert_eq!(
+            raffle_details.prize,
+            prize + 2 * ENTRY_STORAGE_RESERVE as Balance * env::storage_byte_cost()
+        );
+    }
+
+    #[test]
+    fn check_transfer_raffle() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.transfer_raffle(alice_account_id().to_string(), bob_account_id().to_string());
+
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.accept_raffle(alice_account_id().to_string());
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(raffle_details.owner, bob_account_id());
+        assert!(raffle_details.pending_owner.is_none());
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        assert!(contract.raffles.get(&alice_account_id()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "You have not been proposed as the owner of this raffle")]
+    fn check_accept_raffle_not_proposed() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.transfer_raffle(alice_account_id().to_string(), bob_account_id().to_string());
+
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.accept_raffle(alice_account_id().to_string());
+    }
+
+    #[test]
+    fn check_remove_participant() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.remove_participant(alice_account_id().to_string(), bob_account_id().to_string());
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert!(raffle_details.participants.get(&bob_account_id()).is_none());
+        assert_eq!(
+            get_logs(),
+            vec![
+                "EVENT_JSON:{\"data\":[{\"account_id\":\"bob.testnet\",\"raffle_id\":\"alice.testnet\",\"refund\":\"2000000000000000000000000\"}],\"event\":\"participant_removed\",\"standard\":\"raffle_dapp\",\"version\":\"1.0.0\"}"
+            ]
+        );
+    }
+
+    #[test]
+    fn check_remove_sponsored_participant() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.fund_sponsored_entries(alice_account_id().to_string());
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate_sponsored(alice_account_id().to_string(), None);
+        let sponsor_pool = contract
+            .raffles
+            .get(&alice_account_id())
+            .unwrap()
+            .sponsor_pool;
+
+        context.predecessor_account_id(alice_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.remove_participant(alice_account_id().to_string(), bob_account_id().to_string());
+
+        // nothing is refunded to bob, the storage of his entry goes back to the allowance
+        assert!(get_created_receipts().is_empty());
+        assert!(
+            contract
+                .raffles
+                .get(&alice_account_id())
+                .unwrap()
+                .sponsor_pool
+                > sponsor_pool
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Participants can only be removed before the raffle ends")]
+    fn check_remove_participant_after_end() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.remove_participant(alice_account_id().to_string(), bob_account_id().to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "The proposed owner of the raffle cannot participate in it")]
+    fn check_participate_pending_owner() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.transfer_raffle(alice_account_id().to_string(), bob_account_id().to_string());
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "A participant of the raffle cannot become its owner")]
+    fn check_accept_raffle_participant() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.transfer_raffle(alice_account_id().to_string(), bob_account_id().to_string());
+
+        // an entry made before this version rejected the proposed owner
+        let mut raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        raffle_details
+            .participants
+            .insert(&bob_account_id(), &(2 * ONE_NEAR));
+        contract
+            .raffles
+            .insert(&alice_account_id(), &raffle_details);
+
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.accept_raffle(alice_account_id().to_string());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The approver has to be another account than the raffle's owner and operator"
+    )]
+    fn check_set_finalize_approver_operator() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_operator(
+            alice_account_id().to_string(),
+            Some(bob_account_id().to_string()),
+        );
+
+        contract.set_finalize_approver(
+            alice_account_id().to_string(),
+            Some(bob_account_id().to_string()),
+            U128(10 * ONE_NEAR),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The approver can only be changed before anyone entered the raffle")]
+    fn check_set_finalize_approver_after_entries() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_finalize_approver(
+            alice_account_id().to_string(),
+            Some(jacob_account_id().to_string()),
+            U128(10 * ONE_NEAR),
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        // the organizer can't drop the compliance check the entrants joined on
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.set_finalize_approver(alice_account_id().to_string(), None, U128(0));
+    }
+}
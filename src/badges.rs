@@ -0,0 +1,138 @@
+use crate::raffle::{RaffleDetails, FLAG_PARTICIPATION_BADGES};
+use crate::*;
+use serde_json::json;
+
+// attached to the registry's `sbt_mint` for the storage of each badge, paid from the raffle's
+// allowance of sponsored entries
+pub(crate) const BADGE_STORAGE_DEPOSIT: Balance = ONE_NEAR / 100;
+
+// class of the badges issued by the contract, one per raffle participated in
+const PARTICIPATION_BADGE_CLASS: u64 = 1;
+
+impl RaffleDapp {
+    // Issues a soulbound badge to every participant of a settled raffle through the NEP-393
+    // registry, if the raffle enabled them and its allowance can pay for their storage
+    pub(crate) fn internal_issue_badges(
+        &self,
+        raffle_account_id: &AccountId,
+        raffle_detail: &mut RaffleDetails,
+    ) {
+        if !raffle_detail.has_flag(FLAG_PARTICIPATION_BADGES)
+            || raffle_detail.participants.is_empty()
+        {
+            return;
+        }
+        let registry = match &self.badge_registry {
+            Some(registry) => registry.clone(),
+            None => return,
+        };
+
+        let deposit = BADGE_STORAGE_DEPOSIT * raffle_detail.participants.len() as Balance;
+        if raffle_detail.sponsor_pool < deposit {
+            env::log_str(&format!(
+                "The allowance of the raffle {:?} can't pay for the participation badges",
+                raffle_account_id.to_string()
+            ));
+            return;
+        }
+        raffle_detail.sponsor_pool -= deposit;
+
+        let issued_at = env::block_timestamp() / TO_FROM_NANOSECONDS;
+        let token_spec: Vec<serde_json::Value> = raffle_detail
+            .participants
+            .keys()
+            .map(|account_id| {
+                json!([
+                    account_id.to_string(),
+                    [{
+                        "class": PARTICIPATION_BADGE_CLASS,
+                        "issued_at": issued_at,
+                        "reference": raffle_account_id.to_string(),
+                    }]
+                ])
+            })
+            .collect();
+
+        Promise::new(registry).function_call(
+            "sbt_mint".to_string(),
+            json!({ "token_spec": token_spec }).to_string().into_bytes(),
+            deposit,
+            self.gas_config.badge_mint,
+        );
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Sets the NEP-393 registry issuing the participation badges of the raffles enabling them,
+    // passing `None` stops issuing badges
+    pub fn set_badge_registry(&mut self, registry: Option<String>) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can set the badge registry"
+        );
+
+        self.badge_registry = registry.map(|registry| AccountId::try_from(registry).unwrap());
+        match &self.badge_registry {
+            Some(registry) => env::log_str(&format!(
+                "Participation badges are issued through {:?}",
+                registry.to_string()
+            )),
+            None => env::log_str("Participation badges are no longer issued"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raffle::DEFAULT_RAFFLE_FLAGS;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_participation_badges() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_badge_registry(Some(mike_account_id().to_string()));
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            Some(DEFAULT_RAFFLE_FLAGS | FLAG_PARTICIPATION_BADGES),
+            None,
+            None,
+        );
+
+        context.attached_deposit(ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.fund_sponsored_entries(alice_account_id().to_string());
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        let mut raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        contract.internal_issue_badges(&alice_account_id(), &mut raffle_details);
+        assert_eq!(
+            raffle_details.sponsor_pool,
+            ONE_NEAR - BADGE_STORAGE_DEPOSIT
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use crate::*;
+use near_sdk::serde::Serialize;
+
+// number of changes kept in the feed, older ones are dropped as new ones are recorded
+const MAX_RETAINED_CHANGES: u64 = 1_000;
+
+// largest number of changes returned by a single call of `get_changes_since`
+const MAX_CHANGES_LIMIT: u64 = 100;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum RaffleChangeKind {
+    Created,
+    Updated,
+    Removed,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct RaffleChange {
+    pub(crate) raffle_id: AccountId,
+    pub(crate) kind: RaffleChangeKind,
+}
+
+// JSON representation of an entry of the change feed
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RaffleChangeView {
+    pub(crate) seq: u64,
+    pub(crate) raffle_id: AccountId,
+    pub(crate) kind: RaffleChangeKind,
+}
+
+impl RaffleDapp {
+    // Appends a change of the raffle to the feed read by indexers
+    pub(crate) fn record_change(&mut self, raffle_account_id: &AccountId, kind: RaffleChangeKind) {
+        self.change_seq += 1;
+        self.changes.insert(
+            &self.change_seq,
+            &RaffleChange {
+                raffle_id: raffle_account_id.clone(),
+                kind,
+            },
+        );
+        if self.change_seq > MAX_RETAINED_CHANGES {
+            self.changes
+                .remove(&(self.change_seq - MAX_RETAINED_CHANGES));
+        }
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Returns the changes recorded after the sequence number `seq`, in order, so indexers can
+    // sync incrementally. Only the latest `MAX_RETAINED_CHANGES` changes are kept, an indexer
+    // lagging further behind has to re-scan the raffles.
+    pub fn get_changes_since(&self, seq: u64, limit: Option<u64>) -> Vec<RaffleChangeView> {
+        let limit = limit.unwrap_or(MAX_CHANGES_LIMIT).min(MAX_CHANGES_LIMIT);
+        let first_retained = self.change_seq.saturating_sub(MAX_RETAINED_CHANGES) + 1;
+
+        (seq.saturating_add(1).max(first_retained)..=self.change_seq)
+            .take(limit as usize)
+            .map(|seq| {
+                let change = self.changes.get(&seq).unwrap();
+                RaffleChangeView {
+                    seq,
+                    raffle_id: change.raffle_id,
+                    kind: change.kind,
+                }
+            })
+            .collect()
+    }
+
+    // Sequence number of the latest change
+    pub fn get_change_seq(&self) -> u64 {
+        self.change_seq
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_get_changes_since() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        let seq = contract.get_change_seq();
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        context.random_seed(vec![0; 32]);
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        complete_draw(&mut context, &mut contract, alice_account_id());
+
+        let changes = contract.get_changes_since(0, None);
+        assert_eq!(changes.first().unwrap().kind, RaffleChangeKind::Created);
+
+        let changes = contract.get_changes_since(seq, None);
+        // the entry, the block of the draw and the removal
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].seq, seq + 1);
+        assert_eq!(changes[0].raffle_id, alice_account_id());
+        assert_eq!(changes[0].kind, RaffleChangeKind::Updated);
+        assert_eq!(changes[1].kind, RaffleChangeKind::Updated);
+        assert_eq!(changes[2].kind, RaffleChangeKind::Removed);
+
+        assert!(contract
+            .get_changes_since(contract.get_change_seq(), None)
+            .is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+use crate::events::log_event;
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::PublicKey;
+use serde_json::json;
+
+// gas allowance of the access keys handed out for prize claims, taken out of the prize
+pub(crate) const CLAIM_KEY_ALLOWANCE: Balance = ONE_NEAR / 10;
+
+// time the winner has to claim an escrowed prize before its organizer can take it back
+// (30 days, in ns)
+#[cfg(not(feature = "testnet"))]
+pub(crate) const PRIZE_CLAIM_EXPIRY: Timestamp = 30 * 24 * NANOSECONDS_PER_HOUR;
+#[cfg(feature = "testnet")]
+pub(crate) const PRIZE_CLAIM_EXPIRY: Timestamp = 24 * NANOSECONDS_PER_HOUR;
+
+// Prize escrowed for the holder of a claim key instead of being sent to the winner's account
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PrizeClaim {
+    pub(crate) raffle_id: AccountId,
+    pub(crate) owner: AccountId,
+    pub(crate) amount: Balance,
+    pub(crate) expires_at: Timestamp,
+}
+
+impl RaffleDapp {
+    // Escrows `amount` for the holder of `public_key`, which is added to the contract account
+    // as an access key only allowed to claim it
+    pub(crate) fn internal_escrow_prize(
+        &mut self,
+        raffle_account_id: &AccountId,
+        owner: &AccountId,
+        public_key: PublicKey,
+        amount: Balance,
+    ) {
+        self.prize_claims.insert(
+            &public_key,
+            &PrizeClaim {
+                raffle_id: raffle_account_id.clone(),
+                owner: owner.clone(),
+                amount: amount - CLAIM_KEY_ALLOWANCE,
+                expires_at: env::block_timestamp() + PRIZE_CLAIM_EXPIRY,
+            },
+        );
+
+        Promise::new(env::current_account_id()).add_access_key(
+            public_key.clone(),
+            CLAIM_KEY_ALLOWANCE,
+            env::current_account_id(),
+            "claim_prize".to_string(),
+        );
+
+        log_event(
+            "prize_escrowed",
+            json!({
+                "raffle_id": raffle_account_id.to_string(),
+                "public_key": String::from(&public_key),
+                "amount": (amount - CLAIM_KEY_ALLOWANCE).to_string(),
+            }),
+        );
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Sends the prize escrowed for the signing claim key to `account_id`, which can be an
+    // implicit account that doesn't exist yet. The claim key is removed afterwards.
+    pub fn claim_prize(&mut self, account_id: String) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Prizes can only be claimed with their claim key"
+        );
+
+        let receiver_id: AccountId = AccountId::try_from(account_id).unwrap();
+        let public_key = env::signer_account_pk();
+
+        let prize_claim = self
+            .prize_claims
+            .remove(&public_key)
+            .unwrap_or_else(|| env::panic_str("No prize is escrowed for this key"));
+
+        assert!(
+            env::block_timestamp() < prize_claim.expires_at,
+            "The prize claim has expired"
+        );
+
+        Promise::new(receiver_id.clone()).transfer(prize_claim.amount);
+        Promise::new(env::current_account_id()).delete_key(public_key);
+
+        log_event(
+            "prize_claimed",
+            json!({
+                "raffle_id": prize_claim.raffle_id.to_string(),
+                "account_id": receiver_id.to_string(),
+                "amount": prize_claim.amount.to_string(),
+            }),
+        );
+    }
+
+    // Returns an escrowed prize that wasn't claimed in time to the raffle's owner
+    pub fn reclaim_expired_prize(&mut self, public_key: PublicKey) {
+        let prize_claim = self
+            .prize_claims
+            .get(&public_key)
+            .unwrap_or_else(|| env::panic_str("No prize is escrowed for this key"));
+
+        assert_eq!(
+            env::predecessor_account_id(),
+            prize_claim.owner,
+            "Only the raffle's owner can reclaim its prize"
+        );
+
+        assert!(
+            env::block_timestamp() >= prize_claim.expires_at,
+            "The prize can still be claimed by the winner"
+        );
+
+        self.prize_claims.remove(&public_key);
+        self.internal_pay_owner(prize_claim.owner.clone(), prize_claim.amount);
+        Promise::new(env::current_account_id()).delete_key(public_key);
+
+        env::log_str(&format!(
+            "The unclaimed prize of the raffle {:?} was returned to {:?}",
+            prize_claim.raffle_id.to_string(),
+            prize_claim.owner.to_string()
+        ));
+    }
+
+    // Returns the amount escrowed for `public_key`, if any
+    pub fn get_prize_claim(&self, public_key: PublicKey) -> Option<U128> {
+        self.prize_claims
+            .get(&public_key)
+            .map(|prize_claim| U128(prize_claim.amount))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raffle::{DEFAULT_RAFFLE_FLAGS, FLAG_PRIZE_CLAIM_KEYS};
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_claim_prize() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            Some(DEFAULT_RAFFLE_FLAGS | FLAG_PRIZE_CLAIM_KEYS),
+            None,
+            None,
+        );
+        let prize = contract.raffles.get(&alice_account_id()).unwrap().prize;
+
+        let public_key: PublicKey = "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp"
+            .parse()
+            .unwrap();
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.set_claim_key(alice_account_id().to_string(), public_key.clone());
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.random_seed(vec![0; 32]);
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        complete_draw(&mut context, &mut contract, alice_account_id());
+        assert_eq!(
+            contract.get_prize_claim(public_key.clone()),
+            Some(U128(prize - CLAIM_KEY_ALLOWANCE))
+        );
+
+        // the winner signs with the claim key to send the prize to a new implicit account
+        context.predecessor_account_id(raffle_dapp_account_id());
+        context.signer_account_id(raffle_dapp_account_id());
+        context.signer_account_pk(public_key.clone());
+        testing_env!(context.build());
+
+        contract.claim_prize(
+            "98793cd91a3f870fb126f66285808c7e094afcfc4eda8a970f6648cdf0dbd6de".to_string(),
+        );
+        assert!(contract.get_prize_claim(public_key).is_none());
+    }
+
+    #[test]
+    fn check_claim_key_leave_raffle() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            Some(DEFAULT_RAFFLE_FLAGS | FLAG_PRIZE_CLAIM_KEYS),
+            None,
+            None,
+        );
+
+        let public_key: PublicKey = "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp"
+            .parse()
+            .unwrap();
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        for account_id in [bob_account_id(), jacob_account_id()] {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(2 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+
+            context.attached_deposit(0);
+            testing_env!(context.build());
+
+            contract.set_claim_key(alice_account_id().to_string(), public_key.clone());
+        }
+
+        // the claim keys go along with the entries whose deposits paid for them
+        context.predecessor_account_id(bob_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.leave_raffle(alice_account_id().to_string());
+
+        context.predecessor_account_id(jacob_account_id());
+        testing_env!(context.build());
+
+        contract.transfer_ticket(
+            alice_account_id().to_string(),
+            mike_account_id().to_string(),
+            None,
+        );
+
+        assert!(contract
+            .raffles
+            .get(&alice_account_id())
+            .unwrap()
+            .claim_keys
+            .is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+use crate::events::log_event;
+use crate::views::{decode_cursor, Page, RaffleView, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT};
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+use serde_json::json;
+
+impl RaffleDapp {
+    fn assert_curator(&self) {
+        let account_id = env::predecessor_account_id();
+        assert!(
+            account_id == env::current_account_id() || self.moderators.contains(&account_id),
+            "Only the contract account or a moderator can curate the featured raffles"
+        );
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Adds or removes a moderator allowed to feature raffles
+    pub fn set_moderator(&mut self, account_id: String, moderator: bool) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can set the moderators"
+        );
+        self.assert_not_decommissioned();
+
+        let account_id: AccountId = AccountId::try_from(account_id).unwrap();
+        if moderator {
+            self.moderators.insert(&account_id);
+        } else {
+            self.moderators.remove(&account_id);
+        }
+
+        env::log_str(&format!(
+            "{:?} is {} a moderator",
+            account_id.to_string(),
+            if moderator { "now" } else { "no longer" }
+        ));
+    }
+
+    // Features or unfeatures a vetted raffle, highlighted by the official frontend. A raffle is
+    // unfeatured once it is settled.
+    pub fn set_raffle_featured(&mut self, raffle_id: String, featured: bool) {
+        self.assert_curator();
+
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let changed = if featured {
+            self.featured_raffles
+                .insert(&raffle_account_id, &())
+                .is_none()
+        } else {
+            self.featured_raffles.remove(&raffle_account_id).is_some()
+        };
+        if changed {
+            log_event(
+                if featured {
+                    "raffle_featured"
+                } else {
+                    "raffle_unfeatured"
+                },
+                json!({
+                    "raffle_id": raffle_account_id.to_string(),
+                    "curator": env::predecessor_account_id().to_string(),
+                }),
+            );
+        }
+    }
+
+    // Lists the featured raffles that are still visible, in order of their id
+    pub fn get_featured_raffles(
+        &self,
+        cursor: Option<Base64VecU8>,
+        limit: Option<u64>,
+    ) -> Page<RaffleView> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+        let featured: Box<dyn Iterator<Item = (AccountId, ())>> = match decode_cursor(cursor) {
+            Ok(Some(after)) => Box::new(self.featured_raffles.iter_from(after)),
+            Ok(None) => Box::new(self.featured_raffles.iter()),
+            Err(_) => return Page::empty(),
+        };
+
+        Page::collect(
+            featured
+                .map(|(raffle_account_id, _)| {
+                    let raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+                    (raffle_account_id, raffle_details)
+                })
+                .filter(|(_, raffle_details)| raffle_details.visible)
+                .map(|(raffle_account_id, raffle_details)| {
+                    (
+                        raffle_account_id.clone(),
+                        Self::raffle_view(raffle_account_id, &raffle_details),
+                    )
+                }),
+            limit,
+        )
+    }
+
+    pub fn get_moderators(&self) -> Vec<AccountId> {
+        self.moderators.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_featured_raffles() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_moderator(mike_account_id().to_string(), true);
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.predecessor_account_id(mike_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.set_raffle_featured(alice_account_id().to_string(), true);
+        assert_eq!(contract.get_featured_raffles(None, None).items.len(), 1);
+
+        contract.set_raffle_featured(alice_account_id().to_string(), false);
+        assert!(contract.get_featured_raffles(None, None).items.is_empty());
+    }
+
+    #[test]
+    fn check_featured_raffles_pages() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        for account_id in [bob_account_id(), alice_account_id()] {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(17 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.register_raffle(
+                Some(U64(1644353705121)),
+                U64(1644353705521),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+
+        context.predecessor_account_id(raffle_dapp_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.set_raffle_featured(bob_account_id().to_string(), true);
+        contract.set_raffle_featured(alice_account_id().to_string(), true);
+
+        // the featured raffles come in order of their id, whatever the order they were featured
+        let page = contract.get_featured_raffles(None, Some(1));
+        assert_eq!(page.items[0].raffle_id, alice_account_id());
+
+        // a raffle unfeatured in between doesn't shift the next page
+        contract.set_raffle_featured(alice_account_id().to_string(), false);
+        let page = contract.get_featured_raffles(page.next_cursor, Some(1));
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].raffle_id, bob_account_id());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Only the contract account or a moderator can curate the featured raffles"
+    )]
+    fn check_featured_raffles_not_moderator() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_raffle_featured(alice_account_id().to_string(), true);
+    }
+}
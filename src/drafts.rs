@@ -0,0 +1,100 @@
+use crate::raffle::{owned_raffle_id, RaffleConfig};
+use crate::*;
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Saves the settings of a raffle of the caller without funding it, to be reviewed before
+    // `fund_and_activate` registers it. Saving a draft again replaces it.
+    pub fn create_draft(&mut self, config: RaffleConfig) {
+        self.assert_accepting_raffles();
+        let raffle_account_id = owned_raffle_id(config.raffle_id.clone());
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_none(),
+            "You have already registered a raffle"
+        );
+        config.validate();
+
+        self.drafts.insert(&raffle_account_id, &config);
+        env::log_str(&format!(
+            "Draft saved for the raffle of {:?}",
+            raffle_account_id.to_string()
+        ));
+    }
+
+    // Registers the drafted raffle, the attached deposit being the one set in the draft
+    #[payable]
+    pub fn fund_and_activate(&mut self, raffle_id: String) {
+        let raffle_account_id = owned_raffle_id(raffle_id);
+
+        let config = self.drafts.remove(&raffle_account_id);
+        assert!(config.is_some(), "No draft saved for this raffle");
+
+        let config = config.unwrap();
+        assert_eq!(
+            env::attached_deposit(),
+            config.deposit.0,
+            "The attached deposit should be the deposit set in the draft"
+        );
+
+        self.internal_register_raffle(
+            &raffle_account_id,
+            &env::predecessor_account_id(),
+            config,
+            env::attached_deposit(),
+            None,
+        );
+    }
+
+    pub fn get_draft(&self, raffle_id: String) -> Option<RaffleConfig> {
+        self.drafts.get(&AccountId::try_from(raffle_id).ok()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_draft() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        testing_env!(context.build());
+
+        contract.create_draft(RaffleConfig {
+            raffle_id: alice_account_id().to_string(),
+            start: U64(1644353705121),
+            end: U64(1644353705521),
+            max_participants: Some(10),
+            flags: None,
+            allowlist_root: None,
+            prize_commitment: None,
+            deposit: U128(17 * ONE_NEAR),
+        });
+        assert!(contract.get_draft(alice_account_id().to_string()).is_some());
+        assert!(contract.raffles.get(&alice_account_id()).is_none());
+
+        context.attached_deposit(17 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.fund_and_activate(alice_account_id().to_string());
+        assert!(contract.get_draft(alice_account_id().to_string()).is_none());
+        assert_eq!(
+            contract
+                .raffles
+                .get(&alice_account_id())
+                .unwrap()
+                .max_participants,
+            10
+        );
+    }
+}
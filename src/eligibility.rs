@@ -0,0 +1,243 @@
+use crate::participation::{has_windowed_entry_left, is_allowlisted, MIN_LOCKED_AMOUNT};
+use crate::quiz::is_correct_answer;
+use crate::raffle::{ENTRY_STORAGE_RESERVE, FLAG_FREE_ENTRY, FLAG_PRE_REGISTRATION};
+use crate::*;
+use near_sdk::json_types::{Base58CryptoHash, U128};
+use near_sdk::serde::Serialize;
+use near_sdk::CryptoHash;
+
+// Entry requirement of a raffle an account fails to meet
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum IneligibilityReason {
+    NoRaffle,
+    InvalidAccount,
+    ContractAccount,
+    OwnRaffle,
+    AlreadyParticipating,
+    NotAllowlisted,
+    RaffleFull,
+    NotStarted,
+    Ended,
+    EntriesClosed,
+    DepositTooLow,
+    // entries to free raffles don't lock any tokens
+    DepositNotAllowed,
+    SponsorshipExhausted,
+    AnswerRequired,
+    IncorrectAnswer,
+    RateLimited,
+    FreeEntryLimitReached,
+    // entries to NFT pool raffles are made by transferring an NFT of the pool
+    NftPoolEntry,
+}
+
+// Outcome of a dry run of `participate`. The entry gate of the raffle, if any, can only be
+// checked on entry since it queries another contract, so it is returned for the frontends to
+// check themselves.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EligibilityView {
+    pub(crate) eligible: bool,
+    pub(crate) reasons: Vec<IneligibilityReason>,
+    pub(crate) entry_gate: Option<EntryGate>,
+}
+
+impl EligibilityView {
+    fn ineligible(reason: IneligibilityReason) -> Self {
+        Self {
+            eligible: false,
+            reasons: vec![reason],
+            entry_gate: None,
+        }
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Evaluates every requirement `participate` checks for `account_id` entering the raffle with
+    // `deposit` attached, returning all the requirements it fails to meet rather than the first
+    pub fn can_participate(
+        &self,
+        raffle_id: String,
+        account_id: String,
+        deposit: U128,
+        proof: Option<Vec<Base58CryptoHash>>,
+        answer: Option<String>,
+    ) -> EligibilityView {
+        let raffle_details = match self.find_raffle(raffle_id) {
+            Some((_, raffle_details)) => raffle_details,
+            None => return EligibilityView::ineligible(IneligibilityReason::NoRaffle),
+        };
+        let account_id = match AccountId::try_from(account_id) {
+            Ok(account_id) => account_id,
+            Err(_) => return EligibilityView::ineligible(IneligibilityReason::InvalidAccount),
+        };
+
+        let mut reasons = Vec::new();
+        if account_id == env::current_account_id() {
+            reasons.push(IneligibilityReason::ContractAccount);
+        }
+        if account_id == raffle_details.owner
+            || raffle_details.pending_owner.as_ref() == Some(&account_id)
+        {
+            reasons.push(IneligibilityReason::OwnRaffle);
+        }
+        if raffle_details.participants.get(&account_id).is_some() {
+            reasons.push(IneligibilityReason::AlreadyParticipating);
+        }
+        if raffle_details.nft_pool.is_some() {
+            reasons.push(IneligibilityReason::NftPoolEntry);
+        }
+
+        let proof: Vec<CryptoHash> = proof
+            .unwrap_or_default()
+            .into_iter()
+            .map(CryptoHash::from)
+            .collect();
+        if !is_allowlisted(&raffle_details, &account_id, &proof) {
+            reasons.push(IneligibilityReason::NotAllowlisted);
+        }
+        if raffle_details.participants.len() >= raffle_details.max_participants {
+            reasons.push(IneligibilityReason::RaffleFull);
+        }
+
+        let current_timestamp = env::block_timestamp();
+        if current_timestamp <= raffle_details.start
+            && !raffle_details.has_flag(FLAG_PRE_REGISTRATION)
+        {
+            reasons.push(IneligibilityReason::NotStarted);
+        }
+        if current_timestamp >= raffle_details.end {
+            reasons.push(IneligibilityReason::Ended);
+        } else if current_timestamp >= raffle_details.entries_close() {
+            reasons.push(IneligibilityReason::EntriesClosed);
+        }
+
+        if raffle_details.has_flag(FLAG_FREE_ENTRY) {
+            if deposit.0 > 0 {
+                reasons.push(IneligibilityReason::DepositNotAllowed);
+            }
+            if raffle_details.sponsor_pool
+                < ENTRY_STORAGE_RESERVE as Balance * env::storage_byte_cost()
+            {
+                reasons.push(IneligibilityReason::SponsorshipExhausted);
+            }
+            if let Some(rate_limit) = self.free_entry_limit.as_ref() {
+                if !has_windowed_entry_left(&self.free_entry_windows, rate_limit, &account_id) {
+                    reasons.push(IneligibilityReason::FreeEntryLimitReached);
+                }
+            }
+        } else if deposit.0 < MIN_LOCKED_AMOUNT {
+            reasons.push(IneligibilityReason::DepositTooLow);
+        }
+
+        if let Some(quiz) = raffle_details.quiz.as_ref() {
+            match answer {
+                Some(answer) if !is_correct_answer(quiz, &answer) => {
+                    reasons.push(IneligibilityReason::IncorrectAnswer)
+                }
+                Some(_) => {}
+                None => reasons.push(IneligibilityReason::AnswerRequired),
+            }
+        }
+
+        if let Some(rate_limit) = self.entry_rate_limit.as_ref() {
+            if !has_windowed_entry_left(&self.entry_windows, rate_limit, &account_id) {
+                reasons.push(IneligibilityReason::RateLimited);
+            }
+        }
+
+        EligibilityView {
+            eligible: reasons.is_empty(),
+            reasons,
+            entry_gate: raffle_details.entry_gate,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_can_participate() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let eligibility = contract.can_participate(
+            alice_account_id().to_string(),
+            alice_account_id().to_string(),
+            U128(0),
+            None,
+            None,
+        );
+        assert!(!eligibility.eligible);
+        assert_eq!(
+            eligibility.reasons,
+            vec![
+                IneligibilityReason::OwnRaffle,
+                IneligibilityReason::NotStarted,
+                IneligibilityReason::DepositTooLow,
+            ]
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        let eligibility = contract.can_participate(
+            alice_account_id().to_string(),
+            bob_account_id().to_string(),
+            U128(2 * ONE_NEAR),
+            None,
+            None,
+        );
+        assert!(eligibility.eligible);
+        assert!(eligibility.reasons.is_empty());
+
+        assert_eq!(
+            contract
+                .can_participate(
+                    bob_account_id().to_string(),
+                    bob_account_id().to_string(),
+                    U128(2 * ONE_NEAR),
+                    None,
+                    None,
+                )
+                .reasons,
+            vec![IneligibilityReason::NoRaffle]
+        );
+        assert_eq!(
+            contract
+                .can_participate(
+                    alice_account_id().to_string(),
+                    "Not an id!".to_string(),
+                    U128(2 * ONE_NEAR),
+                    None,
+                    None,
+                )
+                .reasons,
+            vec![IneligibilityReason::InvalidAccount]
+        );
+    }
+}
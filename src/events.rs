@@ -0,0 +1,19 @@
+use near_sdk::env;
+use serde_json::json;
+
+// NEP-297 standard name and version used for the events emitted by the contract
+const EVENT_STANDARD: &str = "raffle_dapp";
+const EVENT_STANDARD_VERSION: &str = "1.0.0";
+
+// Emits a NEP-297 event so that indexers can pick up raffle changes from the logs
+pub(crate) fn log_event(event: &str, data: serde_json::Value) {
+    env::log_str(&format!(
+        "EVENT_JSON:{}",
+        json!({
+            "standard": EVENT_STANDARD,
+            "version": EVENT_STANDARD_VERSION,
+            "event": event,
+            "data": [data],
+        })
+    ));
+}
@@ -0,0 +1,562 @@
+use crate::events::log_event;
+use crate::gates::EntryGate;
+use crate::raffle::{RaffleDetails, RaffleStore};
+use crate::*;
+use near_sdk::json_types::{Base58CryptoHash, U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{CryptoHash, PromiseResult};
+use serde_json::json;
+
+// number of raffles exported by a single call unless a limit is given
+const DEFAULT_EXPORT_LIMIT: u64 = 5;
+
+// State of a raffle carried over to the deployment it is exported to, along with the funds it
+// holds. Dates are in ns, as they are stored.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RaffleExport {
+    pub(crate) raffle_id: AccountId,
+    pub(crate) owner: AccountId,
+    pub(crate) prize: U128,
+    pub(crate) storage_cost: U128,
+    pub(crate) start: U64,
+    pub(crate) end: U64,
+    pub(crate) max_participants: u64,
+    pub(crate) flags: u32,
+    pub(crate) visible: bool,
+    pub(crate) resale_royalty: u8,
+    pub(crate) withdrawal_penalty: u8,
+    pub(crate) operator: Option<AccountId>,
+    pub(crate) entry_cutoff: U64,
+    pub(crate) draw_delay: U64,
+    pub(crate) approver: Option<AccountId>,
+    pub(crate) approval_threshold: U128,
+    pub(crate) bond: U128,
+    pub(crate) sponsor_pool: U128,
+    pub(crate) instant_wins: Vec<InstantWinTier>,
+    pub(crate) instant_pool: U128,
+    pub(crate) draw_schedule: Option<DrawSchedule>,
+    pub(crate) promo_match: U128,
+    pub(crate) series: Option<String>,
+    pub(crate) allowlist_root: Option<Base58CryptoHash>,
+    pub(crate) priority_window: U64,
+    pub(crate) entry_gate: Option<EntryGate>,
+    pub(crate) participants: Vec<(AccountId, U128)>,
+    // participants in join order with the time of their entry
+    pub(crate) entries: Vec<(AccountId, U64)>,
+    pub(crate) entries_storage: U64,
+    pub(crate) pre_registered: u64,
+    pub(crate) quiz: Option<Quiz>,
+    pub(crate) physical_prize: Option<String>,
+    pub(crate) claim_keys: Vec<(AccountId, PublicKey)>,
+    pub(crate) promo_codes: Vec<Base58CryptoHash>,
+    pub(crate) instant_rolls: Vec<AccountId>,
+    pub(crate) foreign_payouts: Vec<(AccountId, ForeignAddress)>,
+    pub(crate) metadata: Option<RaffleMetadata>,
+}
+
+impl RaffleExport {
+    // Funds held for the raffle, attached to its import
+    pub(crate) fn held_balance(&self) -> Balance {
+        self.prize.0
+            + self.storage_cost.0
+            + self.sponsor_pool.0
+            + self.instant_pool.0
+            + self
+                .draw_schedule
+                .as_ref()
+                .map_or(0, DrawSchedule::escrowed_prizes)
+            + self.bond.0
+            + self
+                .participants
+                .iter()
+                .map(|(_, locked_tokens)| locked_tokens.0)
+                .sum::<Balance>()
+    }
+}
+
+// Whether the raffle only holds state an export carries. Raffles with a waitlist, ticket
+// listings, a draw in progress, a hidden or sealed prize, an NFT pool or number picks have to
+// be settled on the deployment they were registered on.
+fn exportable(raffle_details: &RaffleDetails) -> bool {
+    raffle_details.waitlist.is_empty()
+        && raffle_details.ticket_listings.is_empty()
+        && raffle_details.draw_block.is_none()
+        && raffle_details.pending_winner.is_none()
+        && raffle_details.sealed_prize.is_none()
+        && raffle_details.mystery_prize.is_none()
+        && raffle_details.nft_pool.is_none()
+        && raffle_details.pick_range.is_none()
+}
+
+impl RaffleDapp {
+    pub(crate) fn assert_not_exporting(&self) {
+        assert!(
+            self.export_target.is_none(),
+            "The contract is being migrated to a new account"
+        );
+    }
+
+    fn raffle_export(
+        raffle_account_id: &AccountId,
+        raffle_details: &RaffleDetails,
+    ) -> RaffleExport {
+        RaffleExport {
+            raffle_id: raffle_account_id.clone(),
+            owner: raffle_details.owner.clone(),
+            prize: U128(raffle_details.prize),
+            storage_cost: U128(raffle_details.storage_cost),
+            start: U64(raffle_details.start),
+            end: U64(raffle_details.end),
+            max_participants: raffle_details.max_participants,
+            flags: raffle_details.flags,
+            visible: raffle_details.visible,
+            resale_royalty: raffle_details.resale_royalty,
+            withdrawal_penalty: raffle_details.withdrawal_penalty,
+            operator: raffle_details.operator.clone(),
+            entry_cutoff: U64(raffle_details.entry_cutoff),
+            draw_delay: U64(raffle_details.draw_delay),
+            approver: raffle_details.approver.clone(),
+            approval_threshold: U128(raffle_details.approval_threshold),
+            bond: U128(raffle_details.bond),
+            sponsor_pool: U128(raffle_details.sponsor_pool),
+            instant_wins: raffle_details.instant_wins.clone(),
+            instant_pool: U128(raffle_details.instant_pool),
+            draw_schedule: raffle_details.draw_schedule.clone(),
+            promo_match: U128(raffle_details.promo_match),
+            series: raffle_details.series.clone(),
+            allowlist_root: raffle_details.allowlist_root.map(Base58CryptoHash::from),
+            priority_window: U64(raffle_details.priority_window),
+            entry_gate: raffle_details.entry_gate.clone(),
+            participants: raffle_details
+                .participants
+                .iter()
+                .map(|(account_id, locked_tokens)| (account_id, U128(locked_tokens)))
+                .collect(),
+            entries: raffle_details
+                .entries
+                .iter()
+                .map(|(_, (account_id, entered_at))| (account_id, U64(entered_at)))
+                .collect(),
+            entries_storage: U64(raffle_details.entries_storage),
+            pre_registered: raffle_details.pre_registered,
+            quiz: raffle_details.quiz.clone(),
+            physical_prize: raffle_details.physical_prize.clone(),
+            claim_keys: raffle_details.claim_keys.to_vec(),
+            promo_codes: raffle_details
+                .promo_codes
+                .iter()
+                .map(Base58CryptoHash::from)
+                .collect(),
+            instant_rolls: raffle_details.instant_rolls.to_vec(),
+            foreign_payouts: raffle_details.foreign_payouts.to_vec(),
+            metadata: None,
+        }
+    }
+
+    // Saves the raffle carried by `export`, either imported from the previous deployment or
+    // restored after its export failed
+    fn internal_restore_raffle(&mut self, export: &RaffleExport) {
+        assert!(
+            self.raffles.get(&export.raffle_id).is_none(),
+            "A raffle is already registered from {:?}",
+            export.raffle_id.to_string()
+        );
+
+        let mut raffle_details = RaffleDetails::new(
+            &export.raffle_id,
+            &export.owner,
+            export.start.0,
+            export.end.0,
+            export.max_participants,
+            export.flags,
+        );
+        raffle_details.prize = export.prize.0;
+        raffle_details.storage_cost = export.storage_cost.0;
+        raffle_details.visible = export.visible;
+        raffle_details.resale_royalty = export.resale_royalty;
+        raffle_details.withdrawal_penalty = export.withdrawal_penalty;
+        raffle_details.operator = export.operator.clone();
+        raffle_details.entry_cutoff = export.entry_cutoff.0;
+        raffle_details.draw_delay = export.draw_delay.0;
+        raffle_details.approver = export.approver.clone();
+        raffle_details.approval_threshold = export.approval_threshold.0;
+        raffle_details.bond = export.bond.0;
+        raffle_details.sponsor_pool = export.sponsor_pool.0;
+        raffle_details.instant_wins = export.instant_wins.clone();
+        raffle_details.instant_pool = export.instant_pool.0;
+        raffle_details.draw_schedule = export.draw_schedule.clone();
+        raffle_details.promo_match = export.promo_match.0;
+        raffle_details.series = export.series.clone();
+        raffle_details.allowlist_root = export.allowlist_root.map(CryptoHash::from);
+        raffle_details.priority_window = export.priority_window.0;
+        raffle_details.entry_gate = export.entry_gate.clone();
+        raffle_details.pre_registered = export.pre_registered;
+        raffle_details.quiz = export.quiz.clone();
+        raffle_details.physical_prize = export.physical_prize.clone();
+        raffle_details.entries_storage = export.entries_storage.0;
+        for (account_id, locked_tokens) in &export.participants {
+            raffle_details
+                .participants
+                .insert(account_id, &locked_tokens.0);
+        }
+        for (account_id, entered_at) in &export.entries {
+            raffle_details.log_entry(account_id, entered_at.0);
+        }
+        for (account_id, public_key) in &export.claim_keys {
+            raffle_details.claim_keys.insert(account_id, public_key);
+        }
+        for promo_code in &export.promo_codes {
+            raffle_details
+                .promo_codes
+                .insert(&CryptoHash::from(*promo_code));
+        }
+        for account_id in &export.instant_rolls {
+            raffle_details.instant_rolls.insert(account_id);
+        }
+        for (account_id, destination) in &export.foreign_payouts {
+            raffle_details
+                .foreign_payouts
+                .insert(account_id, destination);
+        }
+        self.internal_save_raffle(&export.raffle_id, &raffle_details);
+        if let Some(metadata) = &export.metadata {
+            self.raffle_metadata.insert(&export.raffle_id, metadata);
+        }
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Migrates the deployment to `new_contract`, which must have set this account as its import
+    // source. The first call stops new registrations, the promotion, the house raffles and the
+    // jackpot. Every call pushes up to `limit` raffles along with their funds, and once no raffle
+    // is left the pools of the house raffles and of the jackpot follow and the contract is
+    // decommissioned. Prizes escrowed for claim keys or foreign payouts and rolled over prizes
+    // stay withdrawable here.
+    pub fn export_to(&mut self, new_contract: String, limit: Option<u64>) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can migrate the contract"
+        );
+        assert!(
+            self.sunset.is_none(),
+            "The contract is already being retired"
+        );
+
+        let new_contract: AccountId = AccountId::try_from(new_contract).unwrap();
+        match &self.export_target {
+            Some(export_target) => assert_eq!(
+                export_target, &new_contract,
+                "The contract is already being migrated to another account"
+            ),
+            None => {
+                self.export_target = Some(new_contract.clone());
+                if let Some(promotion) = self.promotion.as_mut() {
+                    promotion.expires_at = env::block_timestamp();
+                    self.internal_expire_promotion();
+                }
+                self.house_config = None;
+                self.jackpot_config = None;
+                log_event(
+                    "export_started",
+                    json!({
+                        "new_contract": new_contract.to_string(),
+                        "raffles": self.raffles.len(),
+                    }),
+                );
+            }
+        }
+
+        let raffle_account_ids: Vec<AccountId> = self
+            .raffles
+            .iter()
+            .filter(|(_, raffle_details)| exportable(raffle_details))
+            .map(|(raffle_account_id, _)| raffle_account_id)
+            .take(limit.unwrap_or(DEFAULT_EXPORT_LIMIT) as usize)
+            .collect();
+
+        for raffle_account_id in &raffle_account_ids {
+            let mut raffle_details = self.raffles.get(raffle_account_id).unwrap();
+            let mut export = Self::raffle_export(raffle_account_id, &raffle_details);
+            export.metadata = self.raffle_metadata.get(raffle_account_id);
+            let deposit = export.held_balance();
+
+            raffle_details.clear_storage();
+            self.internal_remove_raffle(raffle_account_id);
+            self.pending_exports.insert(raffle_account_id, &export);
+            self.pending_export_count += 1;
+
+            Promise::new(new_contract.clone())
+                .function_call(
+                    "import_raffle".to_string(),
+                    json!({ "raffle": export }).to_string().into_bytes(),
+                    deposit,
+                    self.gas_config.state_export,
+                )
+                .then(
+                    Promise::new(env::current_account_id()).function_call(
+                        "on_raffle_exported".to_string(),
+                        json!({ "raffle_id": raffle_account_id.to_string() })
+                            .to_string()
+                            .into_bytes(),
+                        0,
+                        self.gas_config.state_export_callback,
+                    ),
+                );
+        }
+
+        if self.raffles.is_empty() && self.pending_export_count == 0 {
+            let balances = self.house_pool + self.jackpot;
+            Promise::new(new_contract.clone()).function_call(
+                "import_balances".to_string(),
+                json!({
+                    "house_pool": U128(self.house_pool),
+                    "jackpot": U128(self.jackpot),
+                })
+                .to_string()
+                .into_bytes(),
+                balances,
+                self.gas_config.state_export,
+            );
+            self.house_pool = 0;
+            self.jackpot = 0;
+            self.sunset = Some(SunsetPhase::Decommissioned);
+
+            log_event(
+                "export_completed",
+                json!({
+                    "new_contract": new_contract.to_string(),
+                    "balances": U128(balances),
+                }),
+            );
+        } else {
+            env::log_str(&format!(
+                "{} raffle(s) exported to {:?}, {} left and {} in flight",
+                raffle_account_ids.len(),
+                new_contract.to_string(),
+                self.raffles.len(),
+                self.pending_export_count
+            ));
+        }
+    }
+
+    // Drops the state of an exported raffle once the new deployment imported it, or restores
+    // the raffle if the import failed, its funds being returned with the failed call
+    #[private]
+    pub fn on_raffle_exported(&mut self, raffle_id: AccountId) {
+        let export = match self.pending_exports.remove(&raffle_id) {
+            Some(export) => export,
+            None => return,
+        };
+        self.pending_export_count -= 1;
+
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => log_event(
+                "raffle_exported",
+                json!({
+                    "raffle_id": raffle_id.to_string(),
+                    "new_contract": self.export_target.as_ref().map(|target| target.to_string()),
+                }),
+            ),
+            _ => {
+                self.metrics.record_failure(FailureCode::ExportFailed);
+                self.internal_restore_raffle(&export);
+                env::log_str(&format!(
+                    "The export of the raffle {:?} failed, it was restored",
+                    raffle_id.to_string()
+                ));
+            }
+        }
+    }
+
+    // Sets the previous deployment allowed to import its raffles and balances into this one,
+    // passing `None` stops the imports
+    pub fn set_import_source(&mut self, source: Option<String>) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can set the import source"
+        );
+
+        self.import_source = source.map(|source| AccountId::try_from(source).unwrap());
+        match &self.import_source {
+            Some(source) => env::log_str(&format!(
+                "Raffles can be imported from {:?}",
+                source.to_string()
+            )),
+            None => env::log_str("Raffles can no longer be imported"),
+        }
+    }
+
+    // Registers a raffle exported by the import source, the attached deposit being the funds
+    // it holds
+    #[payable]
+    pub fn import_raffle(&mut self, raffle: RaffleExport) {
+        assert_eq!(
+            Some(env::predecessor_account_id()),
+            self.import_source,
+            "Only the import source can import raffles"
+        );
+        assert_eq!(
+            env::attached_deposit(),
+            raffle.held_balance(),
+            "The attached deposit should be the balance held for the raffle"
+        );
+
+        self.internal_restore_raffle(&raffle);
+        self.assert_solvent();
+
+        log_event(
+            "raffle_imported",
+            json!({
+                "raffle_id": raffle.raffle_id.to_string(),
+                "participants": raffle.participants.len(),
+            }),
+        );
+    }
+
+    // Adds the pools of the house raffles and of the jackpot exported by the import source
+    #[payable]
+    pub fn import_balances(&mut self, house_pool: U128, jackpot: U128) {
+        assert_eq!(
+            Some(env::predecessor_account_id()),
+            self.import_source,
+            "Only the import source can import balances"
+        );
+        assert_eq!(
+            env::attached_deposit(),
+            house_pool.0 + jackpot.0,
+            "The attached deposit should be the imported balances"
+        );
+
+        self.house_pool += house_pool.0;
+        self.jackpot += jackpot.0;
+
+        env::log_str(&format!(
+            "{} yoctoNEAR imported into the house pool and {} yoctoNEAR into the jackpot",
+            house_pool.0, jackpot.0
+        ));
+    }
+
+    pub fn get_export_target(&self) -> Option<AccountId> {
+        self.export_target.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_export_and_import() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.predecessor_account_id(raffle_dapp_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.export_to(mike_account_id().to_string(), None);
+        assert!(contract.raffles.is_empty());
+        let export = contract.pending_exports.get(&alice_account_id()).unwrap();
+        assert_eq!(export.participants.len(), 1);
+
+        // the import failed, the raffle is restored
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_raffle_exported(alice_account_id());
+        let held_balance = contract
+            .raffles
+            .get(&alice_account_id())
+            .unwrap()
+            .held_balance();
+        assert_eq!(held_balance, export.held_balance());
+
+        // the second export goes through
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+        contract.export_to(mike_account_id().to_string(), None);
+
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_raffle_exported(alice_account_id());
+        assert!(contract.pending_exports.get(&alice_account_id()).is_none());
+
+        // the new deployment imports it
+        let mut new_contract = RaffleDapp::new();
+        new_contract.set_import_source(Some(raffle_dapp_account_id().to_string()));
+        context.attached_deposit(export.held_balance());
+        testing_env!(context.build());
+
+        new_contract.import_raffle(export);
+        let raffle_details = new_contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(raffle_details.held_balance(), held_balance);
+        assert_eq!(
+            raffle_details.participants.get(&bob_account_id()),
+            Some(2 * ONE_NEAR)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The contract is being retired, no new raffles can be registered")]
+    fn check_export_stops_registrations() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.export_to(mike_account_id().to_string(), None);
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use crate::events::log_event;
+use crate::*;
+use near_sdk::json_types::U128;
+use serde_json::json;
+
+// delay between the proposal of a new fee schedule and the moment it can be applied (2 days, in ns)
+#[cfg(not(feature = "testnet"))]
+pub(crate) const FEE_CONFIG_TIMELOCK: Timestamp = 48 * NANOSECONDS_PER_HOUR;
+#[cfg(feature = "testnet")]
+pub(crate) const FEE_CONFIG_TIMELOCK: Timestamp = NANOSECONDS_PER_HOUR;
+
+// flat fee taken from the deposit of every registration until the contract account changes it
+#[cfg(not(feature = "testnet"))]
+pub(crate) const DEFAULT_CREATION_FEE: Balance = 2 * ONE_NEAR;
+#[cfg(feature = "testnet")]
+pub(crate) const DEFAULT_CREATION_FEE: Balance = ONE_NEAR / 10;
+
+// one basis point is a hundredth of a percent
+const BASIS_POINTS: u16 = 10_000;
+
+// Fees charged by the contract, all of them paid to the treasury except the finalize bounty
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct FeeConfig {
+    // flat fee taken from the deposit of every registration
+    pub(crate) creation_flat: Balance,
+    // share of the prize taken when a winner is drawn, in basis points
+    pub(crate) prize_bps: u16,
+    // taken from the prize and paid to whoever finalizes the raffle
+    pub(crate) finalize_bounty: Balance,
+}
+
+impl Default for FeeConfig {
+    fn default() -> Self {
+        Self {
+            creation_flat: DEFAULT_CREATION_FEE,
+            prize_bps: 0,
+            finalize_bounty: 0,
+        }
+    }
+}
+
+impl FeeConfig {
+    // Splits a prize into the fee of the treasury, the finalize bounty and the winner's share
+    pub(crate) fn split_prize(&self, prize: Balance) -> (Balance, Balance, Balance) {
+        let fee = prize * self.prize_bps as Balance / BASIS_POINTS as Balance;
+        let bounty = self.finalize_bounty.min(prize - fee);
+        (fee, bounty, prize - fee - bounty)
+    }
+}
+
+// Share of the collected fees donated to a public-goods or community fund
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Donation {
+    pub(crate) account: AccountId,
+    // in basis points
+    pub(crate) bps: u16,
+}
+
+// Fee schedule waiting for its timelock to expire
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PendingFeeConfig {
+    pub(crate) fee_config: FeeConfig,
+    pub(crate) effective_at: Timestamp,
+}
+
+impl RaffleDapp {
+    // Pays a fee to the treasury, minus the shares set aside for the house raffles and the
+    // jackpot and the share donated to the public-goods account if they are set, and records it
+    // in the treasury's accounting
+    pub(crate) fn internal_collect_fee(&mut self, fee: Balance) {
+        if fee == 0 {
+            return;
+        }
+
+        let amount = self.internal_fund_house_pool(fee);
+        let amount = self.internal_fund_jackpot(amount);
+        let donated = match &self.donation {
+            Some(donation) => {
+                let donated = amount * donation.bps as Balance / BASIS_POINTS as Balance;
+                if donated > 0 {
+                    Promise::new(donation.account.clone()).transfer(donated);
+                    log_event(
+                        "fee_donated",
+                        json!({
+                            "account": donation.account.to_string(),
+                            "amount": donated.to_string(),
+                        }),
+                    );
+                }
+                donated
+            }
+            None => 0,
+        };
+        if amount > donated {
+            Promise::new(self.treasury.clone()).transfer(amount - donated);
+        }
+
+        self.fees_collected += fee;
+        self.fees_donated += donated;
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Donates `bps` basis points of every fee collected from now on to `account`. Passing
+    // `None` stops the donations.
+    pub fn set_donation(&mut self, account: Option<String>, bps: u16) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can change the fees"
+        );
+
+        assert!(
+            bps <= BASIS_POINTS,
+            "The donation cannot exceed {} basis points",
+            BASIS_POINTS
+        );
+
+        self.donation = account.map(|account| Donation {
+            account: AccountId::try_from(account).unwrap(),
+            bps,
+        });
+
+        match &self.donation {
+            Some(donation) => env::log_str(&format!(
+                "{} basis points of the fees are donated to {:?}",
+                bps,
+                donation.account.to_string()
+            )),
+            None => env::log_str("The fees are no longer donated"),
+        }
+    }
+
+    // Proposes a new fee schedule, which can only be applied with `apply_fee_config` once
+    // `FEE_CONFIG_TIMELOCK` has passed so that organizers and participants can react to it
+    pub fn propose_fee_config(
+        &mut self,
+        creation_flat: U128,
+        prize_bps: u16,
+        finalize_bounty: U128,
+    ) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can change the fees"
+        );
+
+        assert!(
+            prize_bps <= BASIS_POINTS,
+            "The prize fee cannot exceed {} basis points",
+            BASIS_POINTS
+        );
+
+        let effective_at = env::block_timestamp() + FEE_CONFIG_TIMELOCK;
+        self.pending_fee_config = Some(PendingFeeConfig {
+            fee_config: FeeConfig {
+                creation_flat: creation_flat.0,
+                prize_bps,
+                finalize_bounty: finalize_bounty.0,
+            },
+            effective_at,
+        });
+
+        log_event(
+            "fee_config_proposed",
+            json!({
+                "creation_flat": creation_flat,
+                "prize_bps": prize_bps,
+                "finalize_bounty": finalize_bounty,
+                "effective_at": effective_at / TO_FROM_NANOSECONDS,
+            }),
+        );
+    }
+
+    // Replaces the fee schedule with the proposed one once its timelock has expired
+    pub fn apply_fee_config(&mut self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can change the fees"
+        );
+
+        let pending_fee_config = self
+            .pending_fee_config
+            .take()
+            .unwrap_or_else(|| env::panic_str("No fee schedule was proposed"));
+
+        assert!(
+            env::block_timestamp() >= pending_fee_config.effective_at,
+            "The proposed fee schedule is still timelocked"
+        );
+
+        self.fee_config = pending_fee_config.fee_config;
+        env::log_str("The proposed fee schedule is now in effect");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raffle::ORGANIZER_BOND;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+
+    #[test]
+    #[should_panic(expected = "The proposed fee schedule is still timelocked")]
+    fn check_apply_fee_config_timelocked() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.propose_fee_config(U128(ONE_NEAR), 500, U128(0));
+
+        context.block_timestamp(FEE_CONFIG_TIMELOCK - 1);
+        testing_env!(context.build());
+
+        contract.apply_fee_config();
+    }
+
+    #[test]
+    fn check_fee_config() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.propose_fee_config(U128(ONE_NEAR), 500, U128(ONE_NEAR / 2));
+
+        context.block_timestamp(FEE_CONFIG_TIMELOCK);
+        testing_env!(context.build());
+
+        contract.apply_fee_config();
+
+        context.block_timestamp(1644353705000 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(11 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(
+            raffle_details.prize,
+            10 * ONE_NEAR - ORGANIZER_BOND - raffle_details.storage_cost
+        );
+
+        let (fee, bounty, winner_prize) = contract.fee_config.split_prize(10 * ONE_NEAR);
+        assert_eq!(fee, ONE_NEAR / 2);
+        assert_eq!(bounty, ONE_NEAR / 2);
+        assert_eq!(winner_prize, 9 * ONE_NEAR);
+    }
+
+    #[test]
+    fn check_donation() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_donation(Some(jack_account_id().to_string()), 1_000);
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        // 10% of the creation fee
+        let treasury = contract.get_treasury();
+        assert_eq!(treasury.fees_collected, U128(DEFAULT_CREATION_FEE));
+        assert_eq!(treasury.fees_donated, U128(DEFAULT_CREATION_FEE / 10));
+        assert!(get_logs().contains(&format!(
+            "EVENT_JSON:{{\"data\":[{{\"account\":\"jack.testnet\",\"amount\":\"{}\"}}],\"event\":\"fee_donated\",\"standard\":\"raffle_dapp\",\"version\":\"1.0.0\"}}",
+            DEFAULT_CREATION_FEE / 10
+        )));
+    }
+}
@@ -0,0 +1,1581 @@
+use crate::events::log_event;
+use crate::payouts::{PayoutKind, RedrawRule};
+use crate::raffle::{RaffleDetails, RaffleStore, FLAG_WEIGHTED_ODDS};
+use crate::*;
+use near_sdk::collections::UnorderedMap;
+use near_sdk::Gas;
+use serde_json::json;
+
+// time after the end of a raffle from which the funds it still holds can be swept to the
+// treasury if it was never finalized (one year, in ns)
+#[cfg(not(feature = "testnet"))]
+pub(crate) const SWEEP_EXPIRY: u64 = 365 * 24 * NANOSECONDS_PER_HOUR;
+#[cfg(feature = "testnet")]
+pub(crate) const SWEEP_EXPIRY: u64 = 24 * NANOSECONDS_PER_HOUR;
+
+// time after the end of a raffle left to its owner and operator to finalize it, after which any
+// account can force the finalization as a keeper (7 days, in ns)
+#[cfg(not(feature = "testnet"))]
+pub(crate) const FINALIZE_WINDOW: u64 = 7 * 24 * NANOSECONDS_PER_HOUR;
+#[cfg(feature = "testnet")]
+pub(crate) const FINALIZE_WINDOW: u64 = NANOSECONDS_PER_HOUR;
+
+// number of blocks between the finalization of a raffle and the block whose seed draws its winner
+pub(crate) const DRAW_DELAY_BLOCKS: BlockHeight = 3;
+
+// number of raffles swept or finalized by a single call unless a limit is given
+const DEFAULT_SWEEP_LIMIT: u64 = 10;
+
+// Picks the winner's index among `participants` entries: the first byte of the seed lower than
+// the number of participants. Every byte being uniform, so is the first one in range. Returns
+// `None` when no byte is in range and the draw has to be retried with another seed.
+pub(crate) fn draw_index(random_seed: &[u8], participants: usize) -> Option<usize> {
+    random_seed
+        .iter()
+        .map(|byte| *byte as usize)
+        .find(|index| *index < participants)
+}
+
+// Picks the winner's index with odds proportional to `weights`: the first 16 bytes of the seed
+// are a number whose remainder by the total weight falls in the cumulative weight of the winner.
+// Numbers past the largest multiple of the total are rejected so every unit of weight is equally
+// likely, returning `None` when the draw has to be retried with another seed.
+pub(crate) fn draw_weighted(random_seed: &[u8], weights: &[Balance]) -> Option<usize> {
+    let total: Balance = weights.iter().sum();
+    if total == 0 {
+        return draw_index(random_seed, weights.len());
+    }
+    let number = u128::from_le_bytes(random_seed.get(..16)?.try_into().ok()?);
+    if number >= u128::MAX - u128::MAX % total {
+        return None;
+    }
+    let mut target = number % total;
+    weights.iter().position(|weight| {
+        if target < *weight {
+            return true;
+        }
+        target -= weight;
+        false
+    })
+}
+
+impl RaffleDapp {
+    // Pays the prize to the winner, refunds the other participants and releases the raffle
+    pub(crate) fn internal_settle_raffle(
+        &mut self,
+        raffle_account_id: &AccountId,
+        mut raffle_detail: RaffleDetails,
+        winner_account_id: &AccountId,
+    ) {
+        let winner_locked_tokens = raffle_detail.participants.get(winner_account_id).unwrap();
+        let settlement_id = self.internal_open_settlement();
+        let mut payouts = Vec::new();
+
+        // The prize fee goes to the treasury and the bounty to the signer of the finalization,
+        // since the retries of the draw are called by the contract itself
+        let (prize_fee, bounty, winner_prize) = self.fee_config.split_prize(raffle_detail.prize);
+        self.internal_collect_fee(prize_fee);
+        if bounty > 0 {
+            Promise::new(env::signer_account_id()).transfer(bounty);
+        }
+
+        // A winner who registered a claim key gets the prize escrowed for that key instead, and
+        // one who elected a foreign chain gets it escrowed until its transaction is signed. When
+        // prizes can be swapped, the prize is held until the winner elects its currency. The prize
+        // money of a physical prize is kept as a bond until the winner confirms its delivery.
+        let foreign_payout = raffle_detail.foreign_payouts.get(winner_account_id);
+        let claim_window = self
+            .prize_swaps
+            .as_ref()
+            .map(|config| config.claim_window.0 * TO_FROM_NANOSECONDS);
+        match raffle_detail.claim_keys.get(winner_account_id) {
+            _ if raffle_detail.physical_prize.is_some() => {
+                self.internal_open_fulfillment(
+                    raffle_account_id,
+                    &raffle_detail,
+                    winner_account_id,
+                    winner_prize,
+                );
+                self.internal_payout(
+                    settlement_id,
+                    &mut payouts,
+                    winner_account_id,
+                    winner_locked_tokens,
+                    PayoutKind::Refund,
+                );
+            }
+            Some(public_key) => {
+                self.internal_escrow_prize(
+                    raffle_account_id,
+                    &raffle_detail.owner,
+                    public_key,
+                    winner_prize,
+                );
+                self.internal_payout(
+                    settlement_id,
+                    &mut payouts,
+                    winner_account_id,
+                    winner_locked_tokens,
+                    PayoutKind::Refund,
+                );
+            }
+            None if foreign_payout.is_some() => {
+                self.internal_escrow_foreign_payout(
+                    raffle_account_id,
+                    winner_account_id,
+                    foreign_payout.unwrap(),
+                    winner_prize,
+                );
+                self.internal_payout(
+                    settlement_id,
+                    &mut payouts,
+                    winner_account_id,
+                    winner_locked_tokens,
+                    PayoutKind::Refund,
+                );
+            }
+            None if claim_window.is_some() => {
+                self.internal_hold_prize(
+                    raffle_account_id,
+                    winner_account_id,
+                    winner_prize,
+                    claim_window.unwrap(),
+                );
+                self.internal_payout(
+                    settlement_id,
+                    &mut payouts,
+                    winner_account_id,
+                    winner_locked_tokens,
+                    PayoutKind::Refund,
+                );
+            }
+            None => {
+                self.internal_payout(
+                    settlement_id,
+                    &mut payouts,
+                    winner_account_id,
+                    winner_prize + winner_locked_tokens,
+                    PayoutKind::Prize,
+                );
+            }
+        }
+
+        raffle_detail.pay_out_nft_pool(Some(winner_account_id), self.gas_config.nft_transfer);
+
+        // The prizes of the scheduled draws nobody held by the end go to the final winner
+        let unheld_prizes = raffle_detail
+            .draw_schedule
+            .as_ref()
+            .map_or(0, DrawSchedule::escrowed_prizes);
+        if unheld_prizes > 0 {
+            Promise::new(winner_account_id.clone()).transfer(unheld_prizes);
+        }
+
+        if let Some(sealed_prize) = raffle_detail.sealed_prize.take() {
+            self.internal_unseal_prize(raffle_account_id, sealed_prize, winner_account_id);
+        }
+
+        env::log_str(&format!(
+            "The winner for this raffle is {:?} and his locked tokens was {:?} NEAR",
+            winner_account_id.to_string(),
+            winner_locked_tokens / ONE_NEAR
+        ));
+
+        for (participants_account_id, locked_tokens) in raffle_detail.participants.to_vec() {
+            if &participants_account_id == winner_account_id {
+                continue;
+            }
+            self.internal_payout(
+                settlement_id,
+                &mut payouts,
+                &participants_account_id,
+                locked_tokens,
+                PayoutKind::Refund,
+            );
+        }
+        // A failed prize goes to the other winners of a number pick raffle, the ones who picked
+        // the same number, and is drawn again among the participants otherwise
+        let redraw_rule = match raffle_detail.picks.get(winner_account_id) {
+            Some(winning_number) if raffle_detail.pick_range.is_some() => RedrawRule::Split {
+                winners: raffle_detail
+                    .picks
+                    .iter()
+                    .filter(|(account_id, number)| {
+                        *number == winning_number
+                            && account_id != winner_account_id
+                            && raffle_detail.participants.get(account_id).is_some()
+                    })
+                    .map(|(account_id, _)| account_id)
+                    .collect(),
+                owner: raffle_detail.owner.clone(),
+            },
+            _ => RedrawRule::Draw {
+                weighted: raffle_detail.has_flag(FLAG_WEIGHTED_ODDS),
+            },
+        };
+        self.internal_record_settlement(
+            settlement_id,
+            raffle_account_id,
+            payouts,
+            Some(redraw_rule),
+        );
+
+        self.record_completed_raffle(
+            &raffle_detail.owner,
+            winner_prize,
+            raffle_detail.participants.len(),
+        );
+        self.record_audience(
+            &raffle_detail.owner,
+            raffle_detail.participants.keys().collect(),
+        );
+        self.record_series_edition(&raffle_detail, Some((winner_account_id, winner_prize)));
+        if raffle_account_id == &env::current_account_id() {
+            self.record_house_streaks(
+                winner_account_id,
+                raffle_detail.participants.keys().collect(),
+            );
+        }
+        self.open_ratings(
+            raffle_account_id,
+            &raffle_detail.owner,
+            raffle_detail.participants.keys().collect(),
+        );
+
+        self.internal_issue_badges(raffle_account_id, &mut raffle_detail);
+
+        raffle_detail.clear_storage();
+        self.internal_remove_raffle(raffle_account_id);
+
+        // The raffle's storage is released, so its cost goes back to the owner along with
+        // what is left of the sponsorship allowance, of the instant-win pool and of the bond
+        let bond = Self::internal_release_bond(&raffle_detail);
+        self.internal_pay_owner(
+            raffle_detail.owner.clone(),
+            raffle_detail.storage_cost
+                + raffle_detail.sponsor_pool
+                + raffle_detail.instant_pool
+                + bond,
+        );
+    }
+
+    // Pays half of the organizer's bond to the keeper who forced the finalization, if any, and
+    // returns what is left for the owner
+    pub(crate) fn internal_release_bond(raffle_detail: &RaffleDetails) -> Balance {
+        match &raffle_detail.keeper {
+            Some(keeper) => {
+                let slashed = raffle_detail.bond / 2;
+                Promise::new(keeper.clone()).transfer(slashed);
+                env::log_str(&format!(
+                    "{:?} yoctoNEAR of the organizer's bond were slashed to the keeper {:?}",
+                    slashed,
+                    keeper.to_string()
+                ));
+                raffle_detail.bond - slashed
+            }
+            None => raffle_detail.bond,
+        }
+    }
+
+    // Refunds the participants of a raffle closed without a winner, because nobody entered it or
+    // it was abandoned, and releases the raffle to its owner
+    pub(crate) fn internal_close_raffle(&mut self, raffle_account_id: &AccountId) {
+        let mut raffle_detail: RaffleDetails = self.raffles.get(raffle_account_id).unwrap();
+        let abandoned = raffle_detail.abandoned;
+        let settlement_id = self.internal_open_settlement();
+        let mut payouts = Vec::new();
+        for (participants_account_id, locked_tokens) in raffle_detail.participants.to_vec() {
+            self.internal_payout(
+                settlement_id,
+                &mut payouts,
+                &participants_account_id,
+                locked_tokens,
+                PayoutKind::Refund,
+            );
+        }
+        self.internal_record_settlement(settlement_id, raffle_account_id, payouts, None);
+        raffle_detail.pay_out_nft_pool(None, self.gas_config.nft_transfer);
+        if abandoned {
+            self.record_cancelled_raffle(&raffle_detail.owner);
+        } else {
+            self.record_completed_raffle(&raffle_detail.owner, 0, 0);
+            self.record_series_edition(&raffle_detail, None);
+        }
+        raffle_detail.clear_storage();
+        self.internal_remove_raffle(raffle_account_id);
+        let bond = Self::internal_release_bond(&raffle_detail);
+        let sealed_prize_deposit = raffle_detail.sealed_prize_deposit();
+        // The promotion's match isn't the owner's, it goes back to the promotion
+        self.internal_return_prize_match(raffle_detail.promo_match);
+        self.internal_pay_owner(
+            raffle_detail.owner.clone(),
+            raffle_detail.prize - raffle_detail.promo_match
+                + raffle_detail.storage_cost
+                + raffle_detail.sponsor_pool
+                + raffle_detail.instant_pool
+                + raffle_detail
+                    .draw_schedule
+                    .as_ref()
+                    .map_or(0, DrawSchedule::escrowed_prizes)
+                + sealed_prize_deposit
+                + bond,
+        );
+        if abandoned {
+            env::log_str("Your raffle was abandoned, no winner was drawn");
+        } else {
+            env::log_str("Nobody participated in your raffle");
+        }
+    }
+
+    // Finalizes the raffle, calling `finalize_raffle` again in a later block until its winner can
+    // be drawn when `reschedule` is set. Otherwise a later call has to complete the draw.
+    pub(crate) fn internal_finalize_raffle(
+        &mut self,
+        raffle_account_id: &AccountId,
+        reschedule: bool,
+    ) {
+        let mut raffle_detail: RaffleDetails = self.raffles.get(raffle_account_id).unwrap();
+
+        let current_time = env::block_timestamp();
+        let overdue = current_time > raffle_detail.end + FINALIZE_WINDOW;
+
+        assert!(
+            current_time > raffle_detail.end,
+            "You can only finalize raffle after it ends"
+        );
+        assert!(
+            current_time > raffle_detail.draws_after(),
+            "The winner of this raffle can only be drawn once its draw delay has passed"
+        );
+
+        // Past the finalization window, the account forcing the finalization is a keeper, the
+        // signer being kept across the calls the contract makes to itself
+        let signer = env::signer_account_id();
+        if overdue
+            && raffle_detail.keeper.is_none()
+            && signer != raffle_detail.owner
+            && raffle_detail.operator.as_ref() != Some(&signer)
+        {
+            raffle_detail.keeper = Some(signer);
+            self.internal_save_raffle(raffle_account_id, &raffle_detail);
+        }
+
+        // A hidden prize has to be revealed by the organizer first, unless a keeper forces the
+        // finalization after the organizer let the finalization window pass
+        if raffle_detail.prize_hidden() {
+            assert!(
+                overdue,
+                "The organizer has to reveal the mystery prize before the raffle is finalized"
+            );
+            env::log_str("The mystery prize was never revealed by the organizer");
+        }
+
+        let abandoned = raffle_detail.abandoned;
+        let participants: UnorderedMap<AccountId, Balance> = raffle_detail.participants;
+
+        // No winner is drawn once participants started reclaiming their deposits, the ones
+        // still entered are refunded along with the owner
+        if participants.is_empty() || abandoned {
+            self.internal_close_raffle(raffle_account_id);
+            return;
+        }
+
+        assert!(
+            self.raffles
+                .get(raffle_account_id)
+                .unwrap()
+                .pending_winner
+                .is_none(),
+            "The draw of the raffle is waiting for the approval of its approver"
+        );
+
+        // The winner is drawn from the seed of a block at least `DRAW_DELAY_BLOCKS` after the one
+        // finalizing the raffle, the contract calling itself until then, so that neither the
+        // caller nor the producer of that block can choose the seed
+        let mut raffle_detail: RaffleDetails = self.raffles.get(raffle_account_id).unwrap();
+        if raffle_detail.draw_block.is_none() {
+            raffle_detail.draw_block = Some(env::block_height() + DRAW_DELAY_BLOCKS);
+            self.internal_save_raffle(raffle_account_id, &raffle_detail);
+            env::log_str(&format!(
+                "The winner will be drawn from the random seed of the block {:?} or a later one",
+                raffle_detail.draw_block.unwrap()
+            ));
+        }
+        if env::block_height() < raffle_detail.draw_block.unwrap() {
+            if reschedule {
+                self.schedule_finalize(raffle_account_id);
+            }
+            return;
+        }
+
+        // The winners of the scheduled draws can be excluded from the final one, unless nobody
+        // would be left to draw from
+        let mut participants_vec = participants.to_vec();
+        if let Some(schedule) = &raffle_detail.draw_schedule {
+            if participants_vec
+                .iter()
+                .any(|(account_id, _)| !schedule.excludes(account_id))
+            {
+                participants_vec.retain(|(account_id, _)| !schedule.excludes(account_id));
+            }
+        }
+
+        env::log_str(&format!(
+            "Total number of participants: {:?}",
+            participants.len()
+        ));
+
+        let random_seed = env::random_seed();
+        env::log_str(&format!("env::random_seed = {:?}", random_seed));
+
+        // A number pick raffle draws its winning number instead of a participant, and a raffle
+        // with weighted odds draws its participants in proportion to their locked tokens
+        let weighted_odds = raffle_detail.has_flag(FLAG_WEIGHTED_ODDS);
+        let draw = match raffle_detail.pick_range {
+            Some(max_number) => draw_index(&random_seed, max_number as usize),
+            None if weighted_odds => {
+                let weights: Vec<Balance> = participants_vec
+                    .iter()
+                    .map(|(_, locked_tokens)| *locked_tokens)
+                    .collect();
+                draw_weighted(&random_seed, &weights)
+            }
+            None => draw_index(&random_seed, participants_vec.len()),
+        };
+
+        let mut raffle_detail: RaffleDetails = self.raffles.get(raffle_account_id).unwrap();
+        raffle_detail.attempts += 1;
+
+        if draw.is_none() {
+            self.metrics.record_failure(FailureCode::DrawRetried);
+            self.internal_save_raffle(raffle_account_id, &raffle_detail);
+            env::log_str(
+                "Failed to discover Random index in this block, searching it in the future blocks...",
+            );
+            if reschedule {
+                self.schedule_finalize(raffle_account_id);
+            }
+            return;
+        }
+
+        let random_index = draw.unwrap();
+        if raffle_detail.pick_range.is_some() {
+            env::log_str(&format!(
+                "The winning number {:?} was drawn in {:?} attempt(s)",
+                random_index + 1,
+                raffle_detail.attempts
+            ));
+            self.internal_settle_number_pick(
+                raffle_account_id,
+                raffle_detail,
+                random_index as u8 + 1,
+            );
+            return;
+        }
+
+        let winner_account_id = participants_vec[random_index].0.clone();
+
+        env::log_str(&format!(
+            "The Random index {:?} was discovered in {:?} attempt(s)",
+            random_index, raffle_detail.attempts
+        ));
+        log_event(
+            "winner_drawn",
+            json!({
+                "raffle_id": raffle_account_id.to_string(),
+                "winner": winner_account_id.to_string(),
+                "odds": if weighted_odds { "weighted" } else { "uniform" },
+            }),
+        );
+
+        // Large prizes are only paid out once the raffle's approver co-signs the draw
+        if raffle_detail.requires_approval() {
+            raffle_detail.pending_winner = Some(winner_account_id);
+            self.internal_save_raffle(raffle_account_id, &raffle_detail);
+            env::log_str(&format!(
+                "The draw of the raffle {:?} is waiting for the approval of {:?}",
+                raffle_account_id.to_string(),
+                raffle_detail.approver.unwrap().to_string()
+            ));
+            return;
+        }
+
+        self.internal_settle_raffle(raffle_account_id, raffle_detail, &winner_account_id);
+    }
+
+    // Calls `finalize_raffle` again in a later block with the gas left, minus the reserve of
+    // the gas budgets
+    fn schedule_finalize(&self, raffle_account_id: &AccountId) {
+        let gas = Gas((env::prepaid_gas().0)
+            .saturating_sub(env::used_gas().0)
+            .saturating_sub(self.gas_config.finalize_reserve.0));
+        assert!(
+            gas >= self.gas_config.min_finalize_call,
+            "Not enough gas attached to finalize the raffle, at least {} is needed for the next call",
+            self.gas_config.min_finalize_call.0
+        );
+
+        Promise::new(env::current_account_id()).function_call(
+            "finalize_raffle".to_string(),
+            json!({ "raffle_id": raffle_account_id.to_string() })
+                .to_string()
+                .into_bytes(),
+            0,
+            gas,
+        );
+    }
+
+    // Keys of the index of the raffles by end, from the one following `after`
+    pub(crate) fn raffles_by_end_after(
+        &self,
+        after: Option<(Timestamp, AccountId)>,
+    ) -> Box<dyn Iterator<Item = (Timestamp, AccountId)> + '_> {
+        match after {
+            Some(key) => Box::new(self.raffles_by_end.iter_from(key).map(|(key, _)| key)),
+            None => Box::new(self.raffles_by_end.iter().map(|(key, _)| key)),
+        }
+    }
+
+    // Raffles that ended more than `SWEEP_EXPIRY` ago in order of their end, from the one
+    // following `after`
+    pub(crate) fn expired_raffles(
+        &self,
+        after: Option<(Timestamp, AccountId)>,
+    ) -> impl Iterator<Item = (Timestamp, AccountId)> + '_ {
+        let expired_before = env::block_timestamp().saturating_sub(SWEEP_EXPIRY);
+        self.raffles_by_end_after(after)
+            .take_while(move |(end, _)| *end < expired_before)
+    }
+
+    // Schedules `finalize_raffle` for a raffle that ended, when lazy finalization is on and the
+    // gas left can pay for the call, refunding the deposit attached by the caller. Returns
+    // whether it was scheduled, the caller going on as usual otherwise.
+    pub(crate) fn internal_trigger_finalization(&mut self, raffle_account_id: &AccountId) -> bool {
+        if !self.lazy_finalization {
+            return false;
+        }
+        let ended = self
+            .raffles
+            .get(raffle_account_id)
+            .is_some_and(|raffle_detail| {
+                env::block_timestamp() > raffle_detail.draws_after()
+                    && raffle_detail.pending_winner.is_none()
+            });
+        let gas_left = (env::prepaid_gas().0)
+            .saturating_sub(env::used_gas().0)
+            .saturating_sub(self.gas_config.finalize_reserve.0);
+        if !ended || gas_left < self.gas_config.min_finalize_call.0 {
+            return false;
+        }
+
+        self.schedule_finalize(raffle_account_id);
+        if env::attached_deposit() > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(env::attached_deposit());
+        }
+        env::log_str(&format!(
+            "The raffle {:?} has ended, its finalization was scheduled",
+            raffle_account_id.to_string()
+        ));
+        true
+    }
+
+    // Raffles that ended and can be finalized in order of their end, from the one following
+    // `after`. Raffles waiting for the approval of their draw are left out.
+    pub(crate) fn finalizable_raffles(
+        &self,
+        after: Option<(Timestamp, AccountId)>,
+    ) -> impl Iterator<Item = (Timestamp, AccountId)> + '_ {
+        let now = env::block_timestamp();
+        self.raffles_by_end_after(after)
+            .take_while(move |(end, _)| *end < now)
+            .filter(move |(_, raffle_account_id)| {
+                let raffle_detail = self.raffles.get(raffle_account_id).unwrap();
+                raffle_detail.pending_winner.is_none() && raffle_detail.draws_after() < now
+            })
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Lets `participate` and `participate_sponsored` schedule the finalization of the raffles
+    // that ended instead of rejecting the entry, so they settle without waiting for a keeper
+    pub fn set_lazy_finalization(&mut self, enabled: bool) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can set the lazy finalization"
+        );
+
+        self.lazy_finalization = enabled;
+        env::log_str(&format!(
+            "Lazy finalization is {}",
+            if enabled { "enabled" } else { "disabled" }
+        ));
+    }
+
+    pub fn get_lazy_finalization(&self) -> bool {
+        self.lazy_finalization
+    }
+
+    pub fn finalize_raffle(&mut self, raffle_id: String) {
+        self.metrics.finalize_raffle += 1;
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let raffle_detail: RaffleDetails = self.raffles.get(&raffle_account_id).unwrap();
+
+        assert!(
+            env::block_timestamp() > raffle_detail.end + FINALIZE_WINDOW
+                || raffle_detail.owner == env::current_account_id()
+                || env::predecessor_account_id() == raffle_detail.owner
+                || env::predecessor_account_id() == env::current_account_id()
+                || raffle_detail.operator.as_ref() == Some(&env::predecessor_account_id()),
+            "Only the raffle's owner, its operator or the contract account can finalize the raffle"
+        );
+
+        self.internal_finalize_raffle(&raffle_account_id, true);
+    }
+
+    // Finalizes up to `limit` ended raffles, oldest first. Each finalization is dispatched to
+    // `finalize_expired_raffle` with a gas budget of its own, so a large raffle can't exhaust the
+    // gas of the batch, and the batch stops early when the gas left can't fund another call. The
+    // contract account can finalize any ended raffle, other keepers only the ones past their
+    // `FINALIZE_WINDOW`. Raffles whose winner can't be drawn yet only commit to their draw block
+    // and are completed by a later call.
+    pub fn finalize_expired(&mut self, limit: Option<u64>) {
+        let by_contract = env::predecessor_account_id() == env::current_account_id();
+        let overdue_before = env::block_timestamp().saturating_sub(FINALIZE_WINDOW);
+
+        let raffle_account_ids: Vec<AccountId> = self
+            .finalizable_raffles(None)
+            .map(|(_, raffle_account_id)| raffle_account_id)
+            .filter(|raffle_account_id| {
+                let raffle_detail = self.raffles.get(raffle_account_id).unwrap();
+                by_contract
+                    || raffle_detail.owner == env::current_account_id()
+                    || raffle_detail.end < overdue_before
+            })
+            .take(limit.unwrap_or(DEFAULT_SWEEP_LIMIT) as usize)
+            .collect();
+
+        let mut dispatched = 0;
+        for raffle_account_id in raffle_account_ids {
+            let gas_left = (env::prepaid_gas().0)
+                .saturating_sub(env::used_gas().0)
+                .saturating_sub(self.gas_config.finalize_reserve.0);
+            if gas_left < self.gas_config.min_finalize_call.0 {
+                break;
+            }
+            Promise::new(env::current_account_id()).function_call(
+                "finalize_expired_raffle".to_string(),
+                json!({ "raffle_id": raffle_account_id.to_string() })
+                    .to_string()
+                    .into_bytes(),
+                0,
+                self.gas_config.min_finalize_call,
+            );
+            dispatched += 1;
+        }
+
+        env::log_str(&format!("{} raffle(s) dispatched", dispatched));
+    }
+
+    // Finalizes a raffle dispatched by `finalize_expired`. The keeper forcing the finalization is
+    // still the signer of the batch. A raffle finalized in the meantime is skipped.
+    #[private]
+    pub fn finalize_expired_raffle(&mut self, raffle_id: AccountId) {
+        let finalizable = self.raffles.get(&raffle_id).is_some_and(|raffle_detail| {
+            raffle_detail.pending_winner.is_none()
+                && env::block_timestamp() > raffle_detail.draws_after()
+        });
+        if finalizable {
+            self.internal_finalize_raffle(&raffle_id, false);
+        }
+    }
+
+    // Pays out the pending draw of a raffle whose prize requires the approval of its approver
+    pub fn approve_finalize(&mut self, raffle_id: String) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let raffle_detail: RaffleDetails = self.raffles.get(&raffle_account_id).unwrap();
+
+        assert_eq!(
+            Some(env::predecessor_account_id()),
+            raffle_detail.approver,
+            "Only the raffle's approver can approve its draw"
+        );
+
+        let winner_account_id = raffle_detail
+            .pending_winner
+            .clone()
+            .unwrap_or_else(|| env::panic_str("The raffle has no draw waiting for approval"));
+
+        self.internal_settle_raffle(&raffle_account_id, raffle_detail, &winner_account_id);
+    }
+
+    // Moves everything still held by up to `limit` expired raffles (prize, storage cost and
+    // unclaimed deposits) to the treasury, emitting a `raffle_swept` event for each of them
+    pub fn sweep_expired_raffles(&mut self, limit: Option<u64>) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can sweep expired raffles"
+        );
+
+        let raffle_account_ids: Vec<AccountId> = self
+            .expired_raffles(None)
+            .map(|(_, raffle_account_id)| raffle_account_id)
+            .take(limit.unwrap_or(DEFAULT_SWEEP_LIMIT) as usize)
+            .collect();
+
+        for raffle_account_id in raffle_account_ids {
+            let mut raffle_detail: RaffleDetails = self.raffles.get(&raffle_account_id).unwrap();
+            let amount = raffle_detail.held_balance();
+
+            self.record_cancelled_raffle(&raffle_detail.owner);
+            raffle_detail.take_waitlist();
+            // The pooled NFTs go back to the accounts that staked them
+            raffle_detail.pay_out_nft_pool(None, self.gas_config.nft_transfer);
+            raffle_detail.clear_storage();
+            self.internal_remove_raffle(&raffle_account_id);
+
+            Promise::new(self.treasury.clone()).transfer(amount);
+
+            log_event(
+                "raffle_swept",
+                json!({
+                    "raffle_id": raffle_account_id.to_string(),
+                    "treasury": self.treasury.to_string(),
+                    "amount": amount.to_string(),
+                }),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fees::DEFAULT_CREATION_FEE;
+    use crate::raffle::{MAX_PARTICIPANTS, ORGANIZER_BOND};
+    use crate::test_utils::*;
+    use near_sdk::collections::UnorderedMap;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_randomness() {
+        let mut map: UnorderedMap<String, u8> = UnorderedMap::new(b"c");
+
+        map.insert(&"alice 1".to_string(), &1);
+        map.insert(&"alice 2".to_string(), &2);
+        map.insert(&"alice 3".to_string(), &3);
+        map.insert(&"alice 4".to_string(), &4);
+        map.insert(&"alice 5".to_string(), &5);
+        map.insert(&"alice 6".to_string(), &6);
+        map.insert(&"alice 7".to_string(), &7);
+        map.insert(&"alice 8".to_string(), &8);
+        map.insert(&"alice 9".to_string(), &9);
+        map.insert(&"alice 10".to_string(), &10);
+
+        let mut context = get_context();
+        let v = vec![
+            150, 255, 1, 8, 45, 32, 101, 50, 123, 221, 58, 3, 127, 202, 56, 16, 32, 9, 111, 255,
+            49, 45, 77, 17, 25, 26, 37, 79, 210, 159, 31, 56,
+        ];
+        context.random_seed(v);
+        testing_env!(context.build());
+
+        let map_vec = map.to_vec();
+        let length = map_vec.len() as u8;
+        let random_seed = env::random_seed();
+        let mut random_index = random_seed[0];
+        let mut found = false;
+
+        for x in random_seed.iter() {
+            if *x < length {
+                random_index = *x;
+                found = true;
+                break;
+            }
+        }
+
+        // Should print RANDOM: ["alice 2" , 2] | INDEX: 1
+        if !found {
+            println!("Failed to discover Random index in this block, searching it in the future blocks...");
+        } else {
+            println!(
+                "RANDOM: [{:?} , {:?}] | INDEX: {:?}",
+                map_vec[random_index as usize].0,
+                map_vec[random_index as usize].1,
+                random_index as usize
+            );
+        }
+    }
+
+    #[test]
+    fn check_finalize_raffle() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.block_timestamp(1644353705135 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(10 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.block_timestamp(1644353705145 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(mike_account_id());
+        context.attached_deposit(15 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.block_timestamp(1644353705150 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(jack_account_id());
+        context.attached_deposit(27 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        let v = vec![
+            150, 255, 1, 8, 45, 32, 101, 50, 123, 221, 58, 3, 127, 202, 56, 16, 32, 9, 111, 255,
+            49, 45, 77, 17, 25, 26, 37, 79, 210, 159, 31, 56,
+        ];
+        context.random_seed(v);
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        complete_draw(&mut context, &mut contract, alice_account_id());
+        assert!(contract.raffles.get(&alice_account_id()).is_none());
+    }
+
+    #[test]
+    fn check_finalize_raffle_by_operator() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_operator(
+            alice_account_id().to_string(),
+            Some(bob_account_id().to_string()),
+        );
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        assert!(contract.raffles.get(&alice_account_id()).is_none());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Only the raffle's owner, its operator or the contract account can finalize the raffle"
+    )]
+    fn check_finalize_raffle_by_removed_operator() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_operator(
+            alice_account_id().to_string(),
+            Some(bob_account_id().to_string()),
+        );
+        contract.set_operator(alice_account_id().to_string(), None);
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Only the raffle's owner, its operator or the contract account can finalize the raffle"
+    )]
+    fn check_finalize_raffle_by_previous_owner() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.transfer_raffle(alice_account_id().to_string(), bob_account_id().to_string());
+
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.accept_raffle(alice_account_id().to_string());
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The winner of this raffle can only be drawn once its draw delay has passed"
+    )]
+    fn check_finalize_raffle_before_draw_delay() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_draw_delay(alice_account_id().to_string(), 30);
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        // the raffle has ended but the half hour of its draw delay hasn't passed
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        assert!(contract
+            .get_finalizable_raffles(None, None)
+            .items
+            .is_empty());
+        contract.finalize_raffle(alice_account_id().to_string());
+    }
+
+    // `testing_env!` resets the storage usage to the context's one, so it is carried over
+    #[test]
+    fn check_finalize_raffle_releases_storage() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        let initial_storage_usage = env::storage_usage();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        complete_draw(&mut context, &mut contract, alice_account_id());
+
+        // only the entries of the change feed, the payout receipts, the organizer's profile, the
+        // audience record and the pending rating are kept
+        for seq in 1..=contract.get_change_seq() {
+            contract.changes.remove(&seq);
+        }
+        contract.settlements.remove(&contract.settlement_seq);
+        contract.latest_settlements.remove(&alice_account_id());
+        contract.organizers.remove(&alice_account_id());
+        contract
+            .audiences
+            .remove(&(alice_account_id(), bob_account_id()));
+        contract
+            .pending_ratings
+            .remove(&(alice_account_id(), bob_account_id()));
+        assert_eq!(env::storage_usage(), initial_storage_usage);
+    }
+
+    #[test]
+    fn check_sweep_expired_raffles() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_treasury(jack_account_id().to_string());
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.predecessor_account_id(bob_account_id());
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1675889705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        // a day after alice's raffle expired, bob's one, ending a year later, hasn't expired yet
+        context.block_timestamp(
+            1644353705521 * TO_FROM_NANOSECONDS + SWEEP_EXPIRY + 24 * NANOSECONDS_PER_HOUR,
+        );
+        context.predecessor_account_id(raffle_dapp_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        let pending_sweeps = contract.get_pending_sweeps(None, None).items;
+        assert_eq!(pending_sweeps.len(), 1);
+        assert_eq!(pending_sweeps[0].raffle_id, alice_account_id());
+        // alice's deposit and jacob's entry, less the creation fee
+        let swept = 19 * ONE_NEAR - DEFAULT_CREATION_FEE;
+        assert_eq!(pending_sweeps[0].amount, U128(swept));
+
+        contract.sweep_expired_raffles(None);
+
+        assert!(contract.raffles.get(&alice_account_id()).is_none());
+        assert!(contract.raffles.get(&bob_account_id()).is_some());
+        assert!(contract.get_pending_sweeps(None, None).items.is_empty());
+        assert_eq!(
+            get_logs().last().unwrap(),
+            &format!(
+                r#"EVENT_JSON:{{"data":[{{"amount":"{}","raffle_id":"alice.testnet","treasury":"jack.testnet"}}],"event":"raffle_swept","standard":"raffle_dapp","version":"1.0.0"}}"#,
+                swept
+            )
+        );
+    }
+
+    // Seeds of 32 bytes generated with xorshift64*, so the tests stay deterministic
+    fn random_seeds(count: usize) -> impl Iterator<Item = Vec<u8>> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        (0..count).map(move |_| {
+            (0..4)
+                .flat_map(|_| {
+                    state ^= state >> 12;
+                    state ^= state << 25;
+                    state ^= state >> 27;
+                    state.wrapping_mul(0x2545_f491_4f6c_dd1d).to_le_bytes()
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn check_draw_index_in_range() {
+        for participants in 1..=MAX_PARTICIPANTS as usize {
+            for random_seed in random_seeds(50) {
+                match draw_index(&random_seed, participants) {
+                    Some(index) => {
+                        assert!(index < participants);
+                        // no earlier byte of the seed was in range
+                        let position = random_seed
+                            .iter()
+                            .position(|byte| *byte as usize == index)
+                            .unwrap();
+                        assert!(random_seed[..position]
+                            .iter()
+                            .all(|byte| *byte as usize >= participants));
+                    }
+                    None => assert!(random_seed
+                        .iter()
+                        .all(|byte| *byte as usize >= participants)),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn check_draw_weighted() {
+        let weights = [ONE_NEAR, 3 * ONE_NEAR, 0, 4 * ONE_NEAR];
+        let mut wins = [0u64; 4];
+        for random_seed in random_seeds(20_000) {
+            if let Some(index) = draw_weighted(&random_seed, &weights) {
+                wins[index] += 1;
+            }
+        }
+
+        // nothing locked never wins, and the others win in proportion to their locked tokens
+        assert_eq!(wins[2], 0);
+        let draws: u64 = wins.iter().sum();
+        for (wins, weight) in wins.iter().zip(weights) {
+            let expected = draws as f64 * (weight / ONE_NEAR) as f64 / 8.0;
+            assert!((*wins as f64 - expected).abs() < 0.05 * draws as f64);
+        }
+
+        // a draw nobody locked tokens for is uniform
+        assert!(draw_weighted(&[1; 32], &[0, 0]).is_some());
+    }
+
+    #[test]
+    fn check_draw_index_uniform() {
+        for participants in [2, 3, 7, 10, 64, 100, 255, 256] {
+            let mut wins = vec![0u64; participants];
+            for random_seed in random_seeds(20_000) {
+                if let Some(index) = draw_index(&random_seed, participants) {
+                    wins[index] += 1;
+                }
+            }
+
+            // Pearson's chi-squared statistic against the uniform distribution, compared to a
+            // bound well above its expected value (the degrees of freedom)
+            let draws: u64 = wins.iter().sum();
+            let expected = draws as f64 / participants as f64;
+            let chi_squared: f64 = wins
+                .iter()
+                .map(|wins| (*wins as f64 - expected).powi(2) / expected)
+                .sum();
+            let degrees_of_freedom = (participants - 1) as f64;
+            assert!(
+                chi_squared < degrees_of_freedom + 6.0 * (2.0 * degrees_of_freedom).sqrt(),
+                "The draw isn't uniform for {} participants: chi-squared = {}",
+                participants,
+                chi_squared
+            );
+        }
+    }
+
+    #[test]
+    fn check_finalize_raffle_draws_from_future_block() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.block_index(100);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        context.random_seed(vec![0; 32]);
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(raffle_details.draw_block, Some(100 + DRAW_DELAY_BLOCKS));
+        assert_eq!(raffle_details.attempts, 0);
+
+        // the winner isn't drawn before the committed block
+        context.block_index(100 + DRAW_DELAY_BLOCKS - 1);
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        assert!(contract.raffles.get(&alice_account_id()).is_some());
+
+        complete_draw(&mut context, &mut contract, alice_account_id());
+        assert!(contract.raffles.get(&alice_account_id()).is_none());
+    }
+
+    fn register_approved_raffle(context: &mut VMContextBuilder, contract: &mut RaffleDapp) {
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.set_finalize_approver(
+            alice_account_id().to_string(),
+            Some(jacob_account_id().to_string()),
+            U128(10 * ONE_NEAR),
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        complete_draw(context, contract, alice_account_id());
+    }
+
+    #[test]
+    fn check_finalize_raffle_awaits_approval() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_approved_raffle(&mut context, &mut contract);
+
+        let pending_draw = contract
+            .get_pending_draw(alice_account_id().to_string())
+            .unwrap();
+        assert_eq!(pending_draw.winner, bob_account_id());
+        assert_eq!(pending_draw.approver, jacob_account_id());
+
+        context.predecessor_account_id(jacob_account_id());
+        testing_env!(context.build());
+
+        contract.approve_finalize(alice_account_id().to_string());
+        assert!(contract.raffles.get(&alice_account_id()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the raffle's approver can approve its draw")]
+    fn check_approve_finalize_not_approver() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_approved_raffle(&mut context, &mut contract);
+
+        context.predecessor_account_id(alice_account_id());
+        testing_env!(context.build());
+
+        contract.approve_finalize(alice_account_id().to_string());
+    }
+
+    #[test]
+    fn check_finalize_raffle_by_keeper_slashes_bond() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        // the owner's finalization window is over, so anyone can force it
+        context.block_timestamp(1644353705521 * TO_FROM_NANOSECONDS + FINALIZE_WINDOW + 1);
+        context.predecessor_account_id(mike_account_id());
+        context.signer_account_id(mike_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        assert_eq!(
+            contract.raffles.get(&alice_account_id()).unwrap().keeper,
+            Some(mike_account_id())
+        );
+
+        complete_draw(&mut context, &mut contract, alice_account_id());
+        assert!(contract.raffles.get(&alice_account_id()).is_none());
+        assert!(get_logs().contains(&format!(
+            "{:?} yoctoNEAR of the organizer's bond were slashed to the keeper {:?}",
+            ORGANIZER_BOND / 2,
+            mike_account_id().to_string()
+        )));
+    }
+
+    #[test]
+    fn check_lazy_finalization() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_lazy_finalization(true);
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        // a late entry schedules the finalization instead of being rejected
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(jacob_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(raffle_details.participants.len(), 1);
+        assert_eq!(
+            get_logs().last().unwrap(),
+            "The raffle \"alice.testnet\" has ended, its finalization was scheduled"
+        );
+    }
+
+    // Runs the finalizations dispatched by the last call of `finalize_expired`
+    fn run_dispatched_finalizations(context: &mut VMContextBuilder, contract: &mut RaffleDapp) {
+        let raffle_ids: Vec<AccountId> = get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                VmAction::FunctionCall {
+                    method_name, args, ..
+                } if method_name == "finalize_expired_raffle" => {
+                    let args: serde_json::Value = serde_json::from_slice(&args).unwrap();
+                    Some(args["raffle_id"].as_str().unwrap().parse().unwrap())
+                }
+                _ => None,
+            })
+            .collect();
+
+        let keeper = context.context.predecessor_account_id.clone();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        for raffle_id in raffle_ids {
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.finalize_expired_raffle(raffle_id);
+        }
+        context.predecessor_account_id(keeper.parse().unwrap());
+    }
+
+    #[test]
+    fn check_finalize_expired() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        for account_id in [alice_account_id(), jacob_account_id()] {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(17 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.register_raffle(
+                Some(U64(1644353705121)),
+                U64(1644353705521),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        // raffles still in their finalization window are left to their owners
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(mike_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.finalize_expired(None);
+        run_dispatched_finalizations(&mut context, &mut contract);
+        assert_eq!(contract.raffles.len(), 2);
+
+        context.block_timestamp(1644353705521 * TO_FROM_NANOSECONDS + FINALIZE_WINDOW + 1);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        // the raffle without participants is settled, the other one commits to its draw block
+        contract.finalize_expired(None);
+        assert_eq!(get_logs().last().unwrap(), "2 raffle(s) dispatched");
+        run_dispatched_finalizations(&mut context, &mut contract);
+        assert!(contract.raffles.get(&jacob_account_id()).is_none());
+        assert!(contract
+            .raffles
+            .get(&alice_account_id())
+            .unwrap()
+            .draw_block
+            .is_some());
+
+        context.block_index(env::block_height() + DRAW_DELAY_BLOCKS);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.finalize_expired(None);
+        run_dispatched_finalizations(&mut context, &mut contract);
+        assert!(contract.raffles.is_empty());
+    }
+}
@@ -0,0 +1,643 @@
+use crate::events::log_event;
+use crate::participation::assert_storage_covered;
+use crate::raffle::RaffleStore;
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::PromiseResult;
+use serde_json::json;
+
+// version of the MPC key the payouts are signed with
+const MPC_KEY_VERSION: u32 = 0;
+
+// precision of the conversion of the fractions of a NEAR to wei (a millionth of a NEAR)
+const WEI_CONVERSION_UNIT: Balance = ONE_NEAR / 1_000_000;
+
+// Address on an EVM chain a participant elected to receive the prize on if they win
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ForeignAddress {
+    pub(crate) chain_id: u64,
+    // 0x-prefixed hex address
+    pub(crate) address: String,
+}
+
+// EVM chain the winners can be paid on, along with the amount in wei paid for each NEAR of prize
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ForeignChain {
+    pub(crate) chain_id: u64,
+    pub(crate) wei_per_near: U128,
+}
+
+// MPC signer of the chain signatures service and derivation path of the key paying the winners
+// on the foreign chains
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ChainSignaturesConfig {
+    pub(crate) signer: AccountId,
+    pub(crate) path: String,
+    pub(crate) chains: Vec<ForeignChain>,
+}
+
+impl ChainSignaturesConfig {
+    fn chain(&self, chain_id: u64) -> Option<&ForeignChain> {
+        self.chains.iter().find(|chain| chain.chain_id == chain_id)
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum ForeignPayoutStatus {
+    // escrowed until the contract account requests its signature
+    Pending,
+    // waiting for the signature of the MPC signer
+    Signing,
+    // signed, the transaction can be broadcast on the foreign chain
+    Signed,
+}
+
+// Prize of a settled raffle paid on a foreign chain
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ForeignPayout {
+    pub(crate) raffle_id: AccountId,
+    pub(crate) winner: AccountId,
+    pub(crate) destination: ForeignAddress,
+    pub(crate) amount: Balance,
+    pub(crate) status: ForeignPayoutStatus,
+    // unsigned EIP-1559 transaction, hex encoded, and the signature returned by the signer
+    pub(crate) transaction: Option<String>,
+    pub(crate) signature: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ForeignPayoutView {
+    pub(crate) raffle_id: AccountId,
+    pub(crate) winner: AccountId,
+    pub(crate) destination: ForeignAddress,
+    pub(crate) amount: U128,
+    pub(crate) status: ForeignPayoutStatus,
+    pub(crate) transaction: Option<String>,
+    pub(crate) signature: Option<String>,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_evm_address(address: &str) -> [u8; 20] {
+    let digits = address
+        .strip_prefix("0x")
+        .filter(|digits| digits.len() == 40)
+        .unwrap_or_else(|| env::panic_str("The address should be 0x followed by 40 hex digits"));
+
+    let mut bytes = [0; 20];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&digits[2 * i..2 * i + 2], 16).unwrap_or_else(|_| {
+            env::panic_str("The address should be 0x followed by 40 hex digits")
+        });
+    }
+    bytes
+}
+
+// Recursive length prefix encoding of the fields of an EVM transaction
+fn rlp_length(len: usize, offset: u8) -> Vec<u8> {
+    if len < 56 {
+        return vec![offset + len as u8];
+    }
+    let bytes = (len as u64).to_be_bytes();
+    let first = bytes.iter().position(|byte| *byte != 0).unwrap();
+    [
+        vec![offset + 55 + (8 - first) as u8],
+        bytes[first..].to_vec(),
+    ]
+    .concat()
+}
+
+fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    [rlp_length(bytes.len(), 0x80), bytes.to_vec()].concat()
+}
+
+fn rlp_uint(value: u128) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let first = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len());
+    rlp_bytes(&bytes[first..])
+}
+
+fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload = items.concat();
+    [rlp_length(payload.len(), 0xc0), payload].concat()
+}
+
+// Unsigned EIP-1559 transaction sending `value` wei to `to`
+pub(crate) fn evm_transfer(
+    chain_id: u64,
+    nonce: u64,
+    max_priority_fee_per_gas: u128,
+    max_fee_per_gas: u128,
+    gas_limit: u64,
+    to: [u8; 20],
+    value: u128,
+) -> Vec<u8> {
+    let fields = rlp_list(&[
+        rlp_uint(chain_id as u128),
+        rlp_uint(nonce as u128),
+        rlp_uint(max_priority_fee_per_gas),
+        rlp_uint(max_fee_per_gas),
+        rlp_uint(gas_limit as u128),
+        rlp_bytes(&to),
+        rlp_uint(value),
+        rlp_bytes(&[]),
+        rlp_list(&[]),
+    ]);
+    [vec![0x02], fields].concat()
+}
+
+// Amount in wei paid for `amount` yoctoNEAR at the rate of `wei_per_near`. The fraction of a
+// NEAR is converted in units of `WEI_CONVERSION_UNIT` so that it can't overflow.
+fn wei_amount(amount: Balance, wei_per_near: u128) -> u128 {
+    let fraction = (amount % ONE_NEAR / WEI_CONVERSION_UNIT)
+        .checked_mul(wei_per_near)
+        .map(|wei| wei / (ONE_NEAR / WEI_CONVERSION_UNIT));
+    (amount / ONE_NEAR)
+        .checked_mul(wei_per_near)
+        .zip(fraction)
+        .and_then(|(wei, fraction)| wei.checked_add(fraction))
+        .unwrap_or_else(|| env::panic_str("The payout doesn't fit in a transaction"))
+}
+
+impl RaffleDapp {
+    // Escrows the prize of a winner who elected to be paid on a foreign chain, until the
+    // contract account requests the signature of its transaction
+    pub(crate) fn internal_escrow_foreign_payout(
+        &mut self,
+        raffle_account_id: &AccountId,
+        winner_account_id: &AccountId,
+        destination: ForeignAddress,
+        amount: Balance,
+    ) {
+        self.foreign_payout_seq += 1;
+        self.foreign_payouts.insert(
+            &self.foreign_payout_seq,
+            &ForeignPayout {
+                raffle_id: raffle_account_id.clone(),
+                winner: winner_account_id.clone(),
+                destination: destination.clone(),
+                amount,
+                status: ForeignPayoutStatus::Pending,
+                transaction: None,
+                signature: None,
+            },
+        );
+
+        log_event(
+            "foreign_payout_escrowed",
+            json!({
+                "payout_id": self.foreign_payout_seq,
+                "raffle_id": raffle_account_id.to_string(),
+                "winner": winner_account_id.to_string(),
+                "chain_id": destination.chain_id,
+                "address": destination.address,
+                "amount": U128(amount),
+            }),
+        );
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Sets the MPC signer and the foreign chains the winners can be paid on, passing `None`
+    // stops the foreign payouts. Payouts already escrowed can still be cancelled by their winners.
+    pub fn set_chain_signatures(&mut self, config: Option<ChainSignaturesConfig>) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can set the chain signatures"
+        );
+        self.assert_not_decommissioned();
+
+        self.chain_signatures = config;
+        match &self.chain_signatures {
+            Some(config) => env::log_str(&format!(
+                "Prizes can be paid on {} foreign chains through {:?}",
+                config.chains.len(),
+                config.signer.to_string()
+            )),
+            None => env::log_str("Prizes are no longer paid on foreign chains"),
+        }
+    }
+
+    // Elects the address on a foreign chain the caller's prize is paid to if they win, instead
+    // of their account. Passing `None` goes back to being paid on NEAR. The locked tokens are
+    // still refunded on NEAR.
+    pub fn set_foreign_payout(&mut self, raffle_id: String, destination: Option<ForeignAddress>) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "Sorry, no raffle is being conducted by {:?}",
+            raffle_account_id.to_string()
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+
+        assert!(
+            env::block_timestamp() < raffle_details.end,
+            "Foreign payouts can only be elected before the raffle ends"
+        );
+
+        let account_id = env::predecessor_account_id();
+        assert!(
+            raffle_details.participants.get(&account_id).is_some(),
+            "You are not participating in this raffle"
+        );
+        assert!(
+            raffle_details.claim_keys.get(&account_id).is_none(),
+            "The prize is paid through the claim key you registered"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        match destination {
+            Some(destination) => {
+                let supported = self
+                    .chain_signatures
+                    .as_ref()
+                    .and_then(|config| config.chain(destination.chain_id))
+                    .is_some();
+                assert!(
+                    supported,
+                    "Prizes cannot be paid on the chain {}",
+                    destination.chain_id
+                );
+                parse_evm_address(&destination.address);
+
+                raffle_details
+                    .foreign_payouts
+                    .insert(&account_id, &destination);
+            }
+            None => {
+                raffle_details.foreign_payouts.remove(&account_id);
+            }
+        }
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+        assert_storage_covered(
+            initial_storage_usage,
+            raffle_details.participants.get(&account_id).unwrap(),
+        );
+
+        env::log_str(&format!(
+            "The foreign payout of {:?} in the raffle {:?} is {}",
+            account_id.to_string(),
+            raffle_account_id.to_string(),
+            match raffle_details.foreign_payouts.get(&account_id) {
+                Some(destination) => format!(
+                    "{} on the chain {}",
+                    destination.address, destination.chain_id
+                ),
+                None => "unset".to_string(),
+            }
+        ));
+    }
+
+    // Builds the transaction paying an escrowed prize on its foreign chain and requests its
+    // signature from the MPC signer, the attached deposit paying for the signature. The nonce
+    // and the fees are those of the account derived from the configured path on that chain.
+    #[payable]
+    pub fn request_foreign_payout(
+        &mut self,
+        payout_id: u64,
+        nonce: U64,
+        max_priority_fee_per_gas: U128,
+        max_fee_per_gas: U128,
+        gas_limit: U64,
+    ) -> Promise {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can request foreign payouts"
+        );
+
+        let config = self
+            .chain_signatures
+            .clone()
+            .unwrap_or_else(|| env::panic_str("Prizes are not paid on foreign chains"));
+        let mut payout = self
+            .foreign_payouts
+            .get(&payout_id)
+            .unwrap_or_else(|| env::panic_str("No foreign payout with this id"));
+        assert_eq!(
+            payout.status,
+            ForeignPayoutStatus::Pending,
+            "The foreign payout is already being signed"
+        );
+
+        let chain = config
+            .chain(payout.destination.chain_id)
+            .unwrap_or_else(|| env::panic_str("Prizes can no longer be paid on this chain"));
+        let transaction = evm_transfer(
+            chain.chain_id,
+            nonce.0,
+            max_priority_fee_per_gas.0,
+            max_fee_per_gas.0,
+            gas_limit.0,
+            parse_evm_address(&payout.destination.address),
+            wei_amount(payout.amount, chain.wei_per_near.0),
+        );
+        let payload = env::keccak256(&transaction);
+
+        payout.status = ForeignPayoutStatus::Signing;
+        payout.transaction = Some(to_hex(&transaction));
+        self.foreign_payouts.insert(&payout_id, &payout);
+
+        Promise::new(config.signer)
+            .function_call(
+                "sign".to_string(),
+                json!({
+                    "request": {
+                        "payload": payload,
+                        "path": config.path,
+                        "key_version": MPC_KEY_VERSION,
+                    }
+                })
+                .to_string()
+                .into_bytes(),
+                env::attached_deposit(),
+                self.gas_config.mpc_sign,
+            )
+            .then(Promise::new(env::current_account_id()).function_call(
+                "on_foreign_payout_signed".to_string(),
+                json!({ "payout_id": payout_id }).to_string().into_bytes(),
+                0,
+                self.gas_config.mpc_sign_callback,
+            ))
+    }
+
+    // Records the signature of a foreign payout. The escrowed prize goes to the treasury, which
+    // funds the account paying the winners on the foreign chains. The payout can be requested
+    // again if the signer failed.
+    #[private]
+    pub fn on_foreign_payout_signed(&mut self, payout_id: u64) {
+        let mut payout = match self.foreign_payouts.get(&payout_id) {
+            Some(payout) => payout,
+            None => return,
+        };
+
+        match env::promise_result(0) {
+            PromiseResult::Successful(signature) => {
+                payout.status = ForeignPayoutStatus::Signed;
+                payout.signature = Some(String::from_utf8_lossy(&signature).to_string());
+                Promise::new(self.treasury.clone()).transfer(payout.amount);
+
+                log_event(
+                    "foreign_payout_signed",
+                    json!({
+                        "payout_id": payout_id,
+                        "chain_id": payout.destination.chain_id,
+                        "transaction": payout.transaction,
+                        "signature": payout.signature,
+                    }),
+                );
+            }
+            _ => {
+                payout.status = ForeignPayoutStatus::Pending;
+                payout.transaction = None;
+                env::log_str(&format!(
+                    "The signature of the foreign payout {} failed, it can be requested again",
+                    payout_id
+                ));
+            }
+        }
+        self.foreign_payouts.insert(&payout_id, &payout);
+    }
+
+    // Lets the winner of an escrowed foreign payout take the prize on NEAR instead, as long as
+    // its signature wasn't requested
+    pub fn cancel_foreign_payout(&mut self, payout_id: u64) {
+        let payout = self
+            .foreign_payouts
+            .get(&payout_id)
+            .unwrap_or_else(|| env::panic_str("No foreign payout with this id"));
+
+        assert_eq!(
+            env::predecessor_account_id(),
+            payout.winner,
+            "Only the winner can cancel their foreign payout"
+        );
+        assert_eq!(
+            payout.status,
+            ForeignPayoutStatus::Pending,
+            "The foreign payout is already being signed"
+        );
+
+        self.foreign_payouts.remove(&payout_id);
+        Promise::new(payout.winner.clone()).transfer(payout.amount);
+
+        env::log_str(&format!(
+            "The prize of the raffle {:?} was paid to {:?} on NEAR instead",
+            payout.raffle_id.to_string(),
+            payout.winner.to_string()
+        ));
+    }
+
+    pub fn get_chain_signatures(&self) -> Option<ChainSignaturesConfig> {
+        self.chain_signatures.clone()
+    }
+
+    pub fn get_foreign_payout(&self, payout_id: u64) -> Option<ForeignPayoutView> {
+        self.foreign_payouts
+            .get(&payout_id)
+            .map(|payout| ForeignPayoutView {
+                raffle_id: payout.raffle_id,
+                winner: payout.winner,
+                destination: payout.destination,
+                amount: U128(payout.amount),
+                status: payout.status,
+                transaction: payout.transaction,
+                signature: payout.signature,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_evm_transfer_encoding() {
+        // 1 wei to 0x00..01 with a zero nonce and fees on mainnet
+        let to = parse_evm_address("0x0000000000000000000000000000000000000001");
+        let transaction = evm_transfer(1, 0, 0, 0, 21_000, to, 1);
+        assert_eq!(
+            to_hex(&transaction),
+            "02df0180808082520894000000000000000000000000000000000000000101 80c0".replace(' ', "")
+        );
+    }
+
+    #[test]
+    fn check_foreign_payout() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_chain_signatures(Some(ChainSignaturesConfig {
+            signer: mike_account_id(),
+            path: "raffle-payouts".to_string(),
+            chains: vec![ForeignChain {
+                chain_id: 1,
+                wei_per_near: U128(1_000_000_000_000_000),
+            }],
+        }));
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+        contract.set_foreign_payout(
+            alice_account_id().to_string(),
+            Some(ForeignAddress {
+                chain_id: 1,
+                address: "0x00000000000000000000000000000000000000b0".to_string(),
+            }),
+        );
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        complete_draw(&mut context, &mut contract, alice_account_id());
+
+        let payout = contract.get_foreign_payout(1).unwrap();
+        assert_eq!(payout.winner, bob_account_id());
+        assert_eq!(payout.status, ForeignPayoutStatus::Pending);
+
+        context.predecessor_account_id(raffle_dapp_account_id());
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        contract.request_foreign_payout(
+            1,
+            U64(0),
+            U128(1_000_000_000),
+            U128(30_000_000_000),
+            U64(21_000),
+        );
+        assert_eq!(
+            contract.get_foreign_payout(1).unwrap().status,
+            ForeignPayoutStatus::Signing
+        );
+
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"{\"recovery_id\":0}".to_vec())],
+        );
+
+        contract.on_foreign_payout_signed(1);
+        assert_eq!(
+            contract.get_foreign_payout(1).unwrap().status,
+            ForeignPayoutStatus::Signed
+        );
+    }
+
+    #[test]
+    fn check_foreign_payout_leave_raffle() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_chain_signatures(Some(ChainSignaturesConfig {
+            signer: mike_account_id(),
+            path: "raffle-payouts".to_string(),
+            chains: vec![ForeignChain {
+                chain_id: 1,
+                wei_per_near: U128(1_000_000_000_000_000),
+            }],
+        }));
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        for account_id in [bob_account_id(), jacob_account_id()] {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(2 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+            contract.set_foreign_payout(
+                alice_account_id().to_string(),
+                Some(ForeignAddress {
+                    chain_id: 1,
+                    address: "0x00000000000000000000000000000000000000b0".to_string(),
+                }),
+            );
+        }
+
+        // the foreign payouts go along with the entries whose deposits paid for them
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.leave_raffle(alice_account_id().to_string());
+
+        context.predecessor_account_id(jacob_account_id());
+        testing_env!(context.build());
+
+        contract.transfer_ticket(
+            alice_account_id().to_string(),
+            mike_account_id().to_string(),
+            None,
+        );
+
+        assert!(contract
+            .raffles
+            .get(&alice_account_id())
+            .unwrap()
+            .foreign_payouts
+            .is_empty());
+    }
+}
@@ -0,0 +1,476 @@
+use crate::events::log_event;
+use crate::raffle::RaffleStore;
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::Serialize;
+use serde_json::json;
+
+// largest length of the description of a physical prize, in bytes
+const MAX_PRIZE_DESCRIPTION_LENGTH: usize = 200;
+
+// time the organizer has to deliver a physical prize once the raffle is settled, after which its
+// winner can dispute the delivery (30 days, in ns)
+#[cfg(not(feature = "testnet"))]
+const DELIVERY_WINDOW: Timestamp = 30 * 24 * NANOSECONDS_PER_HOUR;
+#[cfg(feature = "testnet")]
+const DELIVERY_WINDOW: Timestamp = NANOSECONDS_PER_HOUR;
+
+// time the winner has to dispute the delivery once the delivery window is over, after which the
+// organizer can take the bond back (14 days, in ns)
+#[cfg(not(feature = "testnet"))]
+const DISPUTE_WINDOW: Timestamp = 14 * 24 * NANOSECONDS_PER_HOUR;
+#[cfg(feature = "testnet")]
+const DISPUTE_WINDOW: Timestamp = NANOSECONDS_PER_HOUR;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum FulfillmentStatus {
+    // the organizer still has to send the prize
+    AwaitingDelivery,
+    // the organizer sent the prize, the winner still has to confirm its receipt
+    Shipped,
+    // the winner disputed the delivery, a moderator decides who gets the bond
+    Disputed,
+}
+
+// Delivery of the physical prize of a settled raffle, the prize money being kept as a bond until
+// the winner confirms its receipt
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Fulfillment {
+    pub(crate) raffle_id: AccountId,
+    pub(crate) owner: AccountId,
+    pub(crate) winner: AccountId,
+    pub(crate) description: String,
+    pub(crate) bond: Balance,
+    pub(crate) delivery_deadline: Timestamp,
+    pub(crate) status: FulfillmentStatus,
+}
+
+// JSON representation of a fulfillment, `delivery_deadline` and `dispute_deadline` are in ms
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FulfillmentView {
+    pub(crate) raffle_id: AccountId,
+    pub(crate) owner: AccountId,
+    pub(crate) winner: AccountId,
+    pub(crate) description: String,
+    pub(crate) bond: U128,
+    pub(crate) delivery_deadline: U64,
+    pub(crate) dispute_deadline: U64,
+    pub(crate) status: FulfillmentStatus,
+}
+
+impl Fulfillment {
+    fn dispute_deadline(&self) -> Timestamp {
+        self.delivery_deadline + DISPUTE_WINDOW
+    }
+}
+
+impl RaffleDapp {
+    // Keeps the prize money of a raffle with a physical prize as a bond until its winner confirms
+    // the delivery
+    pub(crate) fn internal_open_fulfillment(
+        &mut self,
+        raffle_account_id: &AccountId,
+        raffle_details: &RaffleDetails,
+        winner_account_id: &AccountId,
+        bond: Balance,
+    ) {
+        self.fulfillment_seq += 1;
+        let fulfillment = Fulfillment {
+            raffle_id: raffle_account_id.clone(),
+            owner: raffle_details.owner.clone(),
+            winner: winner_account_id.clone(),
+            description: raffle_details.physical_prize.clone().unwrap_or_default(),
+            bond,
+            delivery_deadline: env::block_timestamp() + DELIVERY_WINDOW,
+            status: FulfillmentStatus::AwaitingDelivery,
+        };
+        self.fulfillments
+            .insert(&self.fulfillment_seq, &fulfillment);
+
+        log_event(
+            "fulfillment_opened",
+            json!({
+                "fulfillment_id": self.fulfillment_seq,
+                "raffle_id": raffle_account_id.to_string(),
+                "owner": fulfillment.owner.to_string(),
+                "winner": winner_account_id.to_string(),
+                "bond": U128(bond),
+                "delivery_deadline": U64(fulfillment.delivery_deadline / TO_FROM_NANOSECONDS),
+            }),
+        );
+    }
+
+    // Closes a fulfillment, paying its bond to `recipient`
+    fn internal_close_fulfillment(
+        &mut self,
+        fulfillment_id: u64,
+        fulfillment: &Fulfillment,
+        recipient: &AccountId,
+        event: &str,
+    ) {
+        self.fulfillments.remove(&fulfillment_id);
+        Promise::new(recipient.clone()).transfer(fulfillment.bond);
+
+        log_event(
+            event,
+            json!({
+                "fulfillment_id": fulfillment_id,
+                "raffle_id": fulfillment.raffle_id.to_string(),
+                "bond": U128(fulfillment.bond),
+                "recipient": recipient.to_string(),
+            }),
+        );
+    }
+
+    fn internal_get_fulfillment(&self, fulfillment_id: u64) -> Fulfillment {
+        self.fulfillments
+            .get(&fulfillment_id)
+            .unwrap_or_else(|| env::panic_str("No fulfillment with this id"))
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Makes the prize of the raffle a physical one, e.g. merch or tickets, passing `None` makes it
+    // the prize money again. The prize money is then kept as a bond when the raffle is settled,
+    // until the winner confirms the delivery. It can only be changed before anyone entered, and
+    // the attached deposit pays for the storage of the description.
+    #[payable]
+    pub fn set_physical_prize(&mut self, raffle_id: String, description: Option<String>) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can set its physical prize"
+        );
+        assert!(
+            raffle_details.participants.is_empty() && raffle_details.waitlist.is_empty(),
+            "The physical prize can only be changed before anyone entered the raffle"
+        );
+        if let Some(description) = &description {
+            assert!(
+                !description.is_empty() && description.len() <= MAX_PRIZE_DESCRIPTION_LENGTH,
+                "The description of the prize should be between 1 and {} bytes long",
+                MAX_PRIZE_DESCRIPTION_LENGTH
+            );
+            assert!(
+                raffle_details.pick_range.is_none(),
+                "A number pick raffle cannot have a physical prize"
+            );
+            assert!(
+                raffle_details.mystery_prize.is_none(),
+                "A raffle with a mystery prize cannot have a physical prize"
+            );
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        let is_set = description.is_some();
+        raffle_details.physical_prize = description;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        // The storage released by a shorter description is refunded right away
+        let storage_cost = storage_cost_since(initial_storage_usage);
+        let released = (initial_storage_usage.saturating_sub(env::storage_usage())) as Balance
+            * env::storage_byte_cost();
+        assert!(
+            env::attached_deposit() >= storage_cost,
+            "The attached deposit should cover the storage of the description, {} yoctoNEAR",
+            storage_cost
+        );
+        raffle_details.storage_cost = raffle_details.storage_cost + storage_cost - released;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        let refund = env::attached_deposit() - storage_cost + released;
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+
+        env::log_str(&format!(
+            "The physical prize of the raffle {:?} was {}",
+            raffle_account_id.to_string(),
+            if is_set { "set" } else { "removed" }
+        ));
+    }
+
+    // Lets the organizer tell the winner the prize was sent, along with its tracking details
+    pub fn mark_prize_shipped(&mut self, fulfillment_id: u64, tracking: Option<String>) {
+        let mut fulfillment = self.internal_get_fulfillment(fulfillment_id);
+        assert_eq!(
+            env::predecessor_account_id(),
+            fulfillment.owner,
+            "Only the organizer can mark the prize as shipped"
+        );
+        assert_ne!(
+            fulfillment.status,
+            FulfillmentStatus::Disputed,
+            "The delivery of the prize is disputed"
+        );
+
+        fulfillment.status = FulfillmentStatus::Shipped;
+        self.fulfillments.insert(&fulfillment_id, &fulfillment);
+
+        log_event(
+            "prize_shipped",
+            json!({
+                "fulfillment_id": fulfillment_id,
+                "raffle_id": fulfillment.raffle_id.to_string(),
+                "tracking": tracking,
+            }),
+        );
+    }
+
+    // Confirms the winner received the prize, which returns the bond to the organizer. It also
+    // settles a dispute the winner opened.
+    pub fn confirm_delivery(&mut self, fulfillment_id: u64) {
+        let fulfillment = self.internal_get_fulfillment(fulfillment_id);
+        assert_eq!(
+            env::predecessor_account_id(),
+            fulfillment.winner,
+            "Only the winner can confirm the delivery of the prize"
+        );
+
+        let owner = fulfillment.owner.clone();
+        self.internal_close_fulfillment(fulfillment_id, &fulfillment, &owner, "prize_delivered");
+    }
+
+    // Disputes the delivery of a prize the winner didn't receive by the delivery deadline, until
+    // the end of the dispute window
+    pub fn dispute_delivery(&mut self, fulfillment_id: u64) {
+        let mut fulfillment = self.internal_get_fulfillment(fulfillment_id);
+        assert_eq!(
+            env::predecessor_account_id(),
+            fulfillment.winner,
+            "Only the winner can dispute the delivery of the prize"
+        );
+        assert_ne!(
+            fulfillment.status,
+            FulfillmentStatus::Disputed,
+            "The delivery of the prize is already disputed"
+        );
+
+        let current_timestamp = env::block_timestamp();
+        assert!(
+            current_timestamp >= fulfillment.delivery_deadline
+                && current_timestamp < fulfillment.dispute_deadline(),
+            "The delivery can only be disputed between the delivery deadline and the end of the dispute window"
+        );
+
+        fulfillment.status = FulfillmentStatus::Disputed;
+        self.fulfillments.insert(&fulfillment_id, &fulfillment);
+
+        log_event(
+            "delivery_disputed",
+            json!({
+                "fulfillment_id": fulfillment_id,
+                "raffle_id": fulfillment.raffle_id.to_string(),
+                "winner": fulfillment.winner.to_string(),
+            }),
+        );
+    }
+
+    // Settles a disputed delivery, the bond going back to the organizer if the prize was
+    // delivered and being slashed to the winner otherwise
+    pub fn resolve_delivery_dispute(&mut self, fulfillment_id: u64, delivered: bool) {
+        let account_id = env::predecessor_account_id();
+        assert!(
+            account_id == env::current_account_id() || self.moderators.contains(&account_id),
+            "Only the contract account or a moderator can resolve the disputes"
+        );
+
+        let fulfillment = self.internal_get_fulfillment(fulfillment_id);
+        assert_eq!(
+            fulfillment.status,
+            FulfillmentStatus::Disputed,
+            "The delivery of the prize isn't disputed"
+        );
+
+        if delivered {
+            let owner = fulfillment.owner.clone();
+            self.internal_close_fulfillment(
+                fulfillment_id,
+                &fulfillment,
+                &owner,
+                "prize_delivered",
+            );
+        } else {
+            let winner = fulfillment.winner.clone();
+            self.internal_close_fulfillment(fulfillment_id, &fulfillment, &winner, "bond_slashed");
+        }
+    }
+
+    // Returns the bond to the organizer once the dispute window is over without a dispute
+    pub fn release_fulfillment_bond(&mut self, fulfillment_id: u64) {
+        let fulfillment = self.internal_get_fulfillment(fulfillment_id);
+        assert_ne!(
+            fulfillment.status,
+            FulfillmentStatus::Disputed,
+            "The delivery of the prize is disputed"
+        );
+        assert!(
+            env::block_timestamp() >= fulfillment.dispute_deadline(),
+            "The bond can only be released once the dispute window is over"
+        );
+
+        let owner = fulfillment.owner.clone();
+        self.internal_close_fulfillment(fulfillment_id, &fulfillment, &owner, "bond_released");
+    }
+
+    pub fn get_fulfillment(&self, fulfillment_id: u64) -> Option<FulfillmentView> {
+        self.fulfillments
+            .get(&fulfillment_id)
+            .map(|fulfillment| FulfillmentView {
+                dispute_deadline: U64(fulfillment.dispute_deadline() / TO_FROM_NANOSECONDS),
+                raffle_id: fulfillment.raffle_id,
+                owner: fulfillment.owner,
+                winner: fulfillment.winner,
+                description: fulfillment.description,
+                bond: U128(fulfillment.bond),
+                delivery_deadline: U64(fulfillment.delivery_deadline / TO_FROM_NANOSECONDS),
+                status: fulfillment.status,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    // Settles a raffle of alice with a physical prize, entered by bob and jacob, and returns the
+    // fulfillment it opened
+    fn settle_physical_raffle(
+        context: &mut VMContextBuilder,
+        contract: &mut RaffleDapp,
+    ) -> FulfillmentView {
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(ONE_NEAR / 10);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.set_physical_prize(
+            alice_account_id().to_string(),
+            Some("Concert tickets".to_string()),
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        for account_id in [bob_account_id(), jacob_account_id()] {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(2 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+        }
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        complete_draw(context, contract, alice_account_id());
+
+        contract.get_fulfillment(1).unwrap()
+    }
+
+    #[test]
+    fn check_confirm_delivery() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        let fulfillment = settle_physical_raffle(&mut context, &mut contract);
+        assert_eq!(fulfillment.description, "Concert tickets");
+        assert_eq!(fulfillment.status, FulfillmentStatus::AwaitingDelivery);
+        assert!(fulfillment.bond.0 > 0);
+
+        context.predecessor_account_id(alice_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.mark_prize_shipped(1, Some("TRACK-42".to_string()));
+        assert_eq!(
+            contract.get_fulfillment(1).unwrap().status,
+            FulfillmentStatus::Shipped
+        );
+
+        context.predecessor_account_id(fulfillment.winner);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.confirm_delivery(1);
+        assert!(contract.get_fulfillment(1).is_none());
+    }
+
+    #[test]
+    fn check_dispute_slashed_to_winner() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        let fulfillment = settle_physical_raffle(&mut context, &mut contract);
+
+        context.block_timestamp(fulfillment.delivery_deadline.0 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(fulfillment.winner.clone());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.dispute_delivery(1);
+        assert_eq!(
+            contract.get_fulfillment(1).unwrap().status,
+            FulfillmentStatus::Disputed
+        );
+
+        context.predecessor_account_id(raffle_dapp_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.resolve_delivery_dispute(1, false);
+        assert!(contract.get_fulfillment(1).is_none());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The delivery can only be disputed between the delivery deadline and the end of the dispute window"
+    )]
+    fn check_dispute_before_deadline() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        let fulfillment = settle_physical_raffle(&mut context, &mut contract);
+
+        context.predecessor_account_id(fulfillment.winner);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.dispute_delivery(1);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::Gas;
+
+// Gas budgets of the cross-contract calls made by the contract, set by the contract account
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GasConfig {
+    // kept by `finalize_raffle` to schedule its next call and save the state, the rest of the
+    // prepaid gas being passed on to that call
+    pub(crate) finalize_reserve: Gas,
+    // least gas passed on to the next call of `finalize_raffle`, enough to settle a full raffle
+    pub(crate) min_finalize_call: Gas,
+    // attached to the `migrate` call following the deployment of a new version
+    pub(crate) migrate: Gas,
+    // attached to the call checking the token held by an entrant of a gated raffle, and to
+    // the callback completing the entry
+    pub(crate) entry_gate_query: Gas,
+    pub(crate) entry_gate_callback: Gas,
+    // attached to the call minting the participation badges of a settled raffle
+    pub(crate) badge_mint: Gas,
+    // attached to each transfer of a pooled NFT
+    pub(crate) nft_transfer: Gas,
+    // attached to the callback recording the outcome of each payout of a settled raffle
+    pub(crate) payout_callback: Gas,
+    // attached to the signature request of each foreign payout, and to its callback
+    pub(crate) mpc_sign: Gas,
+    pub(crate) mpc_sign_callback: Gas,
+    // attached to the import of each raffle exported to a new deployment, and to its callback
+    pub(crate) state_export: Gas,
+    pub(crate) state_export_callback: Gas,
+    // attached to the price query of the oracle, and to the callback caching the prices
+    pub(crate) price_query: Gas,
+    pub(crate) price_callback: Gas,
+    // attached to the wrapping of a prize paid in a token, to its swap on the DEX, to the
+    // callback recording the outcome of the swap and to the callback of the unwrapping of the
+    // NEAR refunded by a failed swap
+    pub(crate) near_wrap: Gas,
+    pub(crate) prize_swap: Gas,
+    pub(crate) prize_swap_callback: Gas,
+    pub(crate) prize_unwrap_callback: Gas,
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        Self {
+            finalize_reserve: Gas(20_000_000_000_000),
+            min_finalize_call: Gas(50_000_000_000_000),
+            migrate: Gas(50_000_000_000_000),
+            entry_gate_query: Gas(10_000_000_000_000),
+            entry_gate_callback: Gas(20_000_000_000_000),
+            badge_mint: Gas(30_000_000_000_000),
+            nft_transfer: Gas(15_000_000_000_000),
+            payout_callback: Gas(5_000_000_000_000),
+            mpc_sign: Gas(100_000_000_000_000),
+            mpc_sign_callback: Gas(10_000_000_000_000),
+            state_export: Gas(50_000_000_000_000),
+            state_export_callback: Gas(20_000_000_000_000),
+            price_query: Gas(10_000_000_000_000),
+            price_callback: Gas(10_000_000_000_000),
+            near_wrap: Gas(10_000_000_000_000),
+            prize_swap: Gas(100_000_000_000_000),
+            prize_swap_callback: Gas(25_000_000_000_000),
+            prize_unwrap_callback: Gas(10_000_000_000_000),
+        }
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    pub fn set_gas_config(&mut self, gas_config: GasConfig) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can change the gas budgets"
+        );
+
+        self.gas_config = gas_config;
+        env::log_str(&format!(
+            "Gas budgets set to {} for the finalize reserve, {} for the next finalize call, {} for the migration and {}/{} for the entry gate checks and {} for the badges, {} for each NFT transfer {} for each payout callback, {}/{} for the signatures of the foreign payouts, {}/{} for the exports, {}/{} for the prices and {}/{}/{}/{} for the prize swaps",
+            gas_config.finalize_reserve.0,
+            gas_config.min_finalize_call.0,
+            gas_config.migrate.0,
+            gas_config.entry_gate_query.0,
+            gas_config.entry_gate_callback.0,
+            gas_config.badge_mint.0,
+            gas_config.nft_transfer.0,
+            gas_config.payout_callback.0,
+            gas_config.mpc_sign.0,
+            gas_config.mpc_sign_callback.0,
+            gas_config.state_export.0,
+            gas_config.state_export_callback.0,
+            gas_config.price_query.0,
+            gas_config.price_callback.0,
+            gas_config.near_wrap.0,
+            gas_config.prize_swap.0,
+            gas_config.prize_swap_callback.0,
+            gas_config.prize_unwrap_callback.0
+        ));
+    }
+
+    pub fn get_gas_config(&self) -> GasConfig {
+        self.gas_config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    #[should_panic(expected = "Not enough gas attached to finalize the raffle")]
+    fn check_finalize_raffle_not_enough_gas() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_gas_config(GasConfig {
+            min_finalize_call: Gas(300_000_000_000_000),
+            ..GasConfig::default()
+        });
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+    }
+}
@@ -0,0 +1,687 @@
+use crate::participation::InternalParticipation;
+use crate::raffle::{RaffleDetails, RaffleStore, FLAG_FREE_ENTRY};
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{Gas, PromiseResult};
+use serde_json::json;
+
+// Token that entrants of a raffle must hold, checked with a call to its contract before the
+// entry is accepted, the result being handled by `on_entry_gate_checked`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde", tag = "kind", rename_all = "snake_case")]
+pub enum EntryGate {
+    // at least one token of the NEP-171 collection
+    Nft {
+        contract_id: AccountId,
+    },
+    // at least `min_balance` of the NEP-141 token
+    Ft {
+        contract_id: AccountId,
+        min_balance: U128,
+    },
+    // at least `min_staked` yoctoNEAR delegated to the staking pool
+    Staking {
+        pool_id: AccountId,
+        min_staked: U128,
+    },
+    // an account verified as human by the proof-of-personhood registry, e.g. I-Am-Human
+    Personhood {
+        registry_id: AccountId,
+    },
+}
+
+impl EntryGate {
+    // Asks the token's contract what `account_id` holds
+    fn query(&self, account_id: &AccountId, gas: Gas) -> Promise {
+        match self {
+            EntryGate::Nft { contract_id } => Promise::new(contract_id.clone()).function_call(
+                "nft_tokens_for_owner".to_string(),
+                json!({ "account_id": account_id.to_string(), "limit": 1 })
+                    .to_string()
+                    .into_bytes(),
+                0,
+                gas,
+            ),
+            EntryGate::Ft { contract_id, .. } => Promise::new(contract_id.clone()).function_call(
+                "ft_balance_of".to_string(),
+                json!({ "account_id": account_id.to_string() })
+                    .to_string()
+                    .into_bytes(),
+                0,
+                gas,
+            ),
+            EntryGate::Staking { pool_id, .. } => Promise::new(pool_id.clone()).function_call(
+                "get_account_staked_balance".to_string(),
+                json!({ "account_id": account_id.to_string() })
+                    .to_string()
+                    .into_bytes(),
+                0,
+                gas,
+            ),
+            EntryGate::Personhood { registry_id } => Promise::new(registry_id.clone())
+                .function_call(
+                    "is_human".to_string(),
+                    json!({ "account": account_id.to_string() })
+                        .to_string()
+                        .into_bytes(),
+                    0,
+                    gas,
+                ),
+        }
+    }
+
+    // Whether the result of `query` lets the account in
+    fn accepts(&self, result: &[u8]) -> bool {
+        match self {
+            // the registry lists the tokens proving the personhood of the account
+            EntryGate::Nft { .. } | EntryGate::Personhood { .. } => {
+                serde_json::from_slice::<Vec<serde_json::Value>>(result)
+                    .map(|tokens| !tokens.is_empty())
+                    .unwrap_or(false)
+            }
+            EntryGate::Ft { min_balance, .. } => serde_json::from_slice::<U128>(result)
+                .map(|balance| balance.0 >= min_balance.0)
+                .unwrap_or(false),
+            EntryGate::Staking { min_staked, .. } => serde_json::from_slice::<U128>(result)
+                .map(|staked| staked.0 >= min_staked.0)
+                .unwrap_or(false),
+        }
+    }
+}
+
+// Rejects the entry points that can't check the raffle's entry gate
+pub(crate) fn assert_ungated(raffle_details: &RaffleDetails) {
+    assert!(
+        raffle_details.entry_gate.is_none(),
+        "This raffle is restricted to token holders, it can only be entered with `participate`"
+    );
+    assert!(
+        raffle_details.nft_pool.is_none(),
+        "Entries to this raffle are made by transferring an NFT of its pool"
+    );
+}
+
+impl RaffleDapp {
+    // Checks the entry gate of the raffle for `account_id`, the entry being completed by
+    // `on_entry_gate_checked` once the token's contract answered
+    pub(crate) fn internal_check_entry_gate(
+        &self,
+        raffle_account_id: &AccountId,
+        entry_gate: &EntryGate,
+        account_id: &AccountId,
+        locked_tokens: Balance,
+    ) {
+        if let EntryGate::Ft { contract_id, .. } = entry_gate {
+            self.assert_accepted_token(contract_id);
+        }
+
+        entry_gate
+            .query(account_id, self.gas_config.entry_gate_query)
+            .then(
+                Promise::new(env::current_account_id()).function_call(
+                    "on_entry_gate_checked".to_string(),
+                    json!({
+                        "raffle_id": raffle_account_id.to_string(),
+                        "account_id": account_id.to_string(),
+                        "locked_tokens": U128(locked_tokens),
+                    })
+                    .to_string()
+                    .into_bytes(),
+                    0,
+                    self.gas_config.entry_gate_callback,
+                ),
+            );
+    }
+
+    // Sets the token the entrants of the raffle must hold. The entry gate can only be changed
+    // while the raffle has no participants.
+    fn internal_set_entry_gate(&mut self, raffle_id: String, entry_gate: Option<EntryGate>) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can set its entry gate"
+        );
+
+        assert!(
+            raffle_details.participants.is_empty() && raffle_details.waitlist.is_empty(),
+            "The entry gate cannot be changed once the raffle has participants"
+        );
+
+        assert!(
+            entry_gate.is_none() || raffle_details.nft_pool.is_none(),
+            "A raffle restricted to token holders cannot be an NFT pool"
+        );
+
+        raffle_details.entry_gate = entry_gate;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        match &raffle_details.entry_gate {
+            Some(EntryGate::Nft { contract_id }) => env::log_str(&format!(
+                "The raffle {:?} is now restricted to the holders of an NFT of {:?}",
+                raffle_account_id.to_string(),
+                contract_id.to_string()
+            )),
+            Some(EntryGate::Ft {
+                contract_id,
+                min_balance,
+            }) => env::log_str(&format!(
+                "The raffle {:?} is now restricted to the holders of at least {} of {:?}",
+                raffle_account_id.to_string(),
+                min_balance.0,
+                contract_id.to_string()
+            )),
+            Some(EntryGate::Staking {
+                pool_id,
+                min_staked,
+            }) => env::log_str(&format!(
+                "The raffle {:?} is now restricted to the accounts delegating at least {} yoctoNEAR to {:?}",
+                raffle_account_id.to_string(),
+                min_staked.0,
+                pool_id.to_string()
+            )),
+            Some(EntryGate::Personhood { registry_id }) => env::log_str(&format!(
+                "The raffle {:?} is now restricted to the accounts verified as human by {:?}",
+                raffle_account_id.to_string(),
+                registry_id.to_string()
+            )),
+            None => env::log_str(&format!(
+                "The raffle {:?} is open to everyone",
+                raffle_account_id.to_string()
+            )),
+        }
+    }
+
+    // Whether the raffle can still take the entry of `account_id`, which may have changed while
+    // its entry gate was checked
+    fn can_still_enter(&self, raffle_account_id: &AccountId, account_id: &AccountId) -> bool {
+        let current_timestamp = env::block_timestamp();
+        self.raffles
+            .get(raffle_account_id)
+            .map(|raffle_details| {
+                raffle_details.participants.get(account_id).is_none()
+                    && raffle_details.participants.len() < raffle_details.max_participants
+                    && current_timestamp < raffle_details.end
+                    && current_timestamp < raffle_details.entries_close()
+            })
+            .unwrap_or(false)
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Restricts the raffle to the holders of at least one token of the NFT collection
+    // `nft_contract_id`. Passing `None` lifts the restriction.
+    pub fn set_nft_gate(&mut self, raffle_id: String, nft_contract_id: Option<String>) {
+        self.internal_set_entry_gate(
+            raffle_id,
+            nft_contract_id.map(|contract_id| EntryGate::Nft {
+                contract_id: AccountId::try_from(contract_id).unwrap(),
+            }),
+        );
+    }
+
+    // Restricts the raffle to the holders of at least `min_balance` of the fungible token
+    // `ft_contract_id`, which has to be an accepted token. Passing `None` lifts the restriction.
+    pub fn set_ft_gate(
+        &mut self,
+        raffle_id: String,
+        ft_contract_id: Option<String>,
+        min_balance: U128,
+    ) {
+        assert!(
+            ft_contract_id.is_none() || min_balance.0 > 0,
+            "The minimum balance should be greater than 0"
+        );
+
+        let ft_contract_id =
+            ft_contract_id.map(|contract_id| AccountId::try_from(contract_id).unwrap());
+        if let Some(contract_id) = &ft_contract_id {
+            self.assert_accepted_amount(contract_id, min_balance.0);
+        }
+
+        self.internal_set_entry_gate(
+            raffle_id,
+            ft_contract_id.map(|contract_id| EntryGate::Ft {
+                contract_id,
+                min_balance,
+            }),
+        );
+    }
+
+    // Restricts the raffle to the accounts delegating at least `min_staked` yoctoNEAR to the
+    // staking pool `pool_id`, for the raffles of a validator's community. Passing `None` lifts the
+    // restriction.
+    pub fn set_staking_gate(
+        &mut self,
+        raffle_id: String,
+        pool_id: Option<String>,
+        min_staked: U128,
+    ) {
+        assert!(
+            pool_id.is_none() || min_staked.0 > 0,
+            "The minimum stake should be greater than 0"
+        );
+
+        self.internal_set_entry_gate(
+            raffle_id,
+            pool_id.map(|pool_id| EntryGate::Staking {
+                pool_id: AccountId::try_from(pool_id).unwrap(),
+                min_staked,
+            }),
+        );
+    }
+
+    // Restricts the raffle to the accounts verified as human by the proof-of-personhood registry
+    // `registry_id`, which keeps a single person from entering a free raffle with many accounts.
+    // Passing `None` lifts the restriction.
+    pub fn set_personhood_gate(&mut self, raffle_id: String, registry_id: Option<String>) {
+        self.internal_set_entry_gate(
+            raffle_id,
+            registry_id.map(|registry_id| EntryGate::Personhood {
+                registry_id: AccountId::try_from(registry_id).unwrap(),
+            }),
+        );
+    }
+
+    // Completes the entry of `account_id` once the token's contract answered, refunding the
+    // locked tokens if the account doesn't hold the token or the raffle can't take it anymore
+    #[private]
+    pub fn on_entry_gate_checked(
+        &mut self,
+        raffle_id: AccountId,
+        account_id: AccountId,
+        locked_tokens: U128,
+    ) -> bool {
+        let entry_gate = self
+            .raffles
+            .get(&raffle_id)
+            .and_then(|raffle_details| raffle_details.entry_gate);
+        let accepted = match (entry_gate, env::promise_result(0)) {
+            (Some(entry_gate), PromiseResult::Successful(result)) => entry_gate.accepts(&result),
+            _ => false,
+        };
+
+        if !accepted || !self.can_still_enter(&raffle_id, &account_id) {
+            self.metrics.record_failure(FailureCode::EntryRejected);
+            if locked_tokens.0 > 0 {
+                Promise::new(account_id.clone()).transfer(locked_tokens.0);
+            }
+            env::log_str(&format!(
+                "The entry of {:?} in the raffle of {:?} was rejected, its locked tokens are refunded",
+                account_id.to_string(),
+                raffle_id.to_string()
+            ));
+            return false;
+        }
+
+        // The entries of free raffles are paid from the sponsorship allowance
+        let initial_storage_usage = env::storage_usage();
+        self.internal_enter(&raffle_id, &account_id, locked_tokens.0);
+        if self
+            .raffles
+            .get(&raffle_id)
+            .unwrap()
+            .has_flag(FLAG_FREE_ENTRY)
+        {
+            self.internal_debit_sponsor_pool(&raffle_id, initial_storage_usage);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instant_wins::InstantWinTier;
+    use crate::raffle::DEFAULT_RAFFLE_FLAGS;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn register_gated_raffle(context: &mut VMContextBuilder, contract: &mut RaffleDapp) {
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.set_nft_gate(
+            alice_account_id().to_string(),
+            Some(mike_account_id().to_string()),
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+        assert!(contract
+            .raffles
+            .get(&alice_account_id())
+            .unwrap()
+            .participants
+            .is_empty());
+    }
+
+    fn check_gate(contract: &mut RaffleDapp, context: &mut VMContextBuilder, result: &str) -> bool {
+        context.predecessor_account_id(raffle_dapp_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(result.as_bytes().to_vec())]
+        );
+
+        contract.on_entry_gate_checked(alice_account_id(), bob_account_id(), U128(2 * ONE_NEAR))
+    }
+
+    #[test]
+    fn check_nft_gate() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_gated_raffle(&mut context, &mut contract);
+
+        assert!(check_gate(
+            &mut contract,
+            &mut context,
+            r#"[{"token_id":"1","owner_id":"bob.testnet"}]"#
+        ));
+        assert_eq!(
+            contract
+                .raffles
+                .get(&alice_account_id())
+                .unwrap()
+                .participants
+                .get(&bob_account_id()),
+            Some(2 * ONE_NEAR)
+        );
+    }
+
+    #[test]
+    fn check_nft_gate_rejected() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_gated_raffle(&mut context, &mut contract);
+
+        assert!(!check_gate(&mut contract, &mut context, "[]"));
+        assert!(contract
+            .raffles
+            .get(&alice_account_id())
+            .unwrap()
+            .participants
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "This raffle is restricted to token holders")]
+    fn check_nft_gate_sponsored_entry() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_gated_raffle(&mut context, &mut contract);
+
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.participate_sponsored(alice_account_id().to_string(), None);
+    }
+
+    #[test]
+    fn check_personhood_gate_free_entry() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            Some(DEFAULT_RAFFLE_FLAGS | FLAG_FREE_ENTRY),
+            None,
+            None,
+        );
+
+        context.attached_deposit(ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.fund_sponsored_entries(alice_account_id().to_string());
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.set_personhood_gate(
+            alice_account_id().to_string(),
+            Some(mike_account_id().to_string()),
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.predecessor_account_id(raffle_dapp_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(
+                br#"[["mike.testnet",[1]]]"#.to_vec()
+            )]
+        );
+
+        // the entry locks no tokens, its storage being paid from the sponsorship allowance
+        assert!(contract.on_entry_gate_checked(alice_account_id(), bob_account_id(), U128(0)));
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(raffle_details.participants.get(&bob_account_id()), Some(0));
+        assert!(raffle_details.sponsor_pool < ONE_NEAR);
+    }
+
+    #[test]
+    fn check_ft_gate() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_accepted_token(
+            mike_account_id().to_string(),
+            Some(AcceptedToken {
+                decimals: 18,
+                min_amount: U128(10),
+            }),
+        );
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.set_ft_gate(
+            alice_account_id().to_string(),
+            Some(mike_account_id().to_string()),
+            U128(100),
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        assert!(!check_gate(&mut contract, &mut context, r#""99""#));
+        assert!(check_gate(&mut contract, &mut context, r#""100""#));
+    }
+
+    #[test]
+    fn check_staking_gate() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.set_staking_gate(
+            alice_account_id().to_string(),
+            Some(mike_account_id().to_string()),
+            U128(100 * ONE_NEAR),
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        assert!(!check_gate(
+            &mut contract,
+            &mut context,
+            &format!("\"{}\"", 99 * ONE_NEAR)
+        ));
+        assert!(check_gate(
+            &mut contract,
+            &mut context,
+            &format!("\"{}\"", 100 * ONE_NEAR)
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "is not accepted")]
+    fn check_ft_gate_not_accepted() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_ft_gate(
+            alice_account_id().to_string(),
+            Some(mike_account_id().to_string()),
+            U128(100),
+        );
+    }
+
+    #[test]
+    fn check_nft_gate_instant_win() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_gated_raffle(&mut context, &mut contract);
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(3 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.set_instant_wins(
+            alice_account_id().to_string(),
+            vec![InstantWinTier {
+                odds_bps: 10_000,
+                prize: U128(ONE_NEAR),
+            }],
+        );
+        let pool = contract
+            .get_instant_wins(alice_account_id().to_string())
+            .unwrap()
+            .pool
+            .0;
+
+        assert!(check_gate(
+            &mut contract,
+            &mut context,
+            r#"[{"token_id":"1","owner_id":"bob.testnet"}]"#
+        ));
+        assert_eq!(
+            contract
+                .get_instant_wins(alice_account_id().to_string())
+                .unwrap()
+                .pool
+                .0,
+            pool - ONE_NEAR
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use crate::events::log_event;
+use crate::raffle::{RaffleConfig, ORGANIZER_BOND};
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use serde_json::json;
+
+// one basis point is a hundredth of a percent
+const BASIS_POINTS: u16 = 10_000;
+
+// deposited on top of the prize to pay for the storage of a house raffle, what is left of it
+// being added to the prize
+const HOUSE_STORAGE_ALLOWANCE: Balance = ONE_NEAR / 10;
+
+// Raffles run by the contract itself out of a share of the fees it collects, started by keepers
+// with `start_house_raffle` and owned by the contract account
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HouseRaffleConfig {
+    // share of every fee set aside for the house raffles, in basis points
+    pub(crate) fee_share_bps: u16,
+    pub(crate) prize: U128,
+    // time between the starts of two house raffles, and how long each of them lasts
+    pub(crate) interval_hours: u64,
+    pub(crate) duration_hours: u64,
+    // consecutive house raffles an account has to lose to be paid the pity prize out of the
+    // pool, 0 disabling it
+    pub(crate) pity_losses: u32,
+    pub(crate) pity_prize: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HouseRafflesView {
+    pub(crate) config: Option<HouseRaffleConfig>,
+    pub(crate) pool: U128,
+    // in ms
+    pub(crate) next_start: Timestamp,
+}
+
+impl RaffleDapp {
+    // Sets the share of a fee going to the house raffles aside, returning what is left of it
+    pub(crate) fn internal_fund_house_pool(&mut self, fee: Balance) -> Balance {
+        let share = self
+            .house_config
+            .map(|config| fee * config.fee_share_bps as Balance / BASIS_POINTS as Balance)
+            .unwrap_or(0);
+        self.house_pool += share;
+        fee - share
+    }
+
+    // Counts the losing streaks of the participants of a house raffle, paying the pity prize to
+    // the ones reaching the configured number of losses while the pool can fund it. The ones it
+    // can't fund keep their streak, and are paid after their next loss.
+    pub(crate) fn record_house_streaks(
+        &mut self,
+        winner_account_id: &AccountId,
+        participants: Vec<AccountId>,
+    ) {
+        self.house_streaks.remove(winner_account_id);
+        let config = match self.house_config {
+            Some(config) if config.pity_losses > 0 => config,
+            _ => return,
+        };
+
+        for account_id in participants {
+            if &account_id == winner_account_id {
+                continue;
+            }
+            let losses = self.house_streaks.get(&account_id).unwrap_or(0) + 1;
+            if losses < config.pity_losses || self.house_pool < config.pity_prize.0 {
+                self.house_streaks.insert(&account_id, &losses);
+                continue;
+            }
+
+            self.house_streaks.remove(&account_id);
+            self.house_pool -= config.pity_prize.0;
+            Promise::new(account_id.clone()).transfer(config.pity_prize.0);
+            log_event(
+                "pity_prize_paid",
+                json!({
+                    "account_id": account_id.to_string(),
+                    "losses": losses,
+                    "prize": config.pity_prize,
+                }),
+            );
+        }
+    }
+
+    // Pays the owner of a raffle, the amounts owed to the contract account going back to the pool
+    // of the house raffles
+    pub(crate) fn internal_pay_owner(&mut self, owner: AccountId, amount: Balance) {
+        if owner == env::current_account_id() {
+            self.house_pool += amount;
+        } else if amount > 0 {
+            Promise::new(owner).transfer(amount);
+        }
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Configures the house raffles, passing `None` stops them. The pool collected so far is kept
+    // for when they resume.
+    pub fn set_house_raffles(&mut self, config: Option<HouseRaffleConfig>) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can configure the house raffles"
+        );
+        self.assert_not_decommissioned();
+
+        if let Some(config) = config {
+            assert!(
+                config.fee_share_bps <= BASIS_POINTS,
+                "The share of the fees cannot exceed {} basis points",
+                BASIS_POINTS
+            );
+            assert!(
+                config.interval_hours > 0 && config.duration_hours > 0,
+                "The interval and the duration of the house raffles should be at least an hour"
+            );
+            assert!(
+                config.pity_losses == 0 || config.pity_prize.0 > 0,
+                "The pity prize should be set when losing streaks are rewarded"
+            );
+        }
+
+        self.house_config = config;
+        match config {
+            Some(config) => env::log_str(&format!(
+                "House raffles of {} yoctoNEAR every {} hours, funded by {} basis points of the fees",
+                config.prize.0, config.interval_hours, config.fee_share_bps
+            )),
+            None => env::log_str("The house raffles are stopped"),
+        }
+    }
+
+    // Starts the next house raffle once it is due and its pool can fund it. Anyone can call it,
+    // and anyone can finalize the house raffle as soon as it ends.
+    pub fn start_house_raffle(&mut self) {
+        assert!(self.house_config.is_some(), "The house raffles are stopped");
+        let config = self.house_config.unwrap();
+
+        assert!(
+            env::block_timestamp() >= self.house_next_start,
+            "The next house raffle is not due yet"
+        );
+
+        let raffle_account_id = env::current_account_id();
+        assert!(
+            self.raffles.get(&raffle_account_id).is_none(),
+            "The previous house raffle hasn't been finalized yet"
+        );
+
+        let deposit = config.prize.0
+            + self.fee_config.creation_flat
+            + ORGANIZER_BOND
+            + HOUSE_STORAGE_ALLOWANCE;
+        assert!(
+            self.house_pool >= deposit,
+            "The pool of the house raffles can't fund the next one yet"
+        );
+        self.house_pool -= deposit;
+
+        let start = env::block_timestamp() / TO_FROM_NANOSECONDS;
+        self.internal_register_raffle(
+            &raffle_account_id,
+            &raffle_account_id,
+            RaffleConfig {
+                raffle_id: raffle_account_id.to_string(),
+                start: U64(start),
+                end: U64(
+                    start + config.duration_hours * NANOSECONDS_PER_HOUR / TO_FROM_NANOSECONDS,
+                ),
+                max_participants: None,
+                flags: None,
+                allowlist_root: None,
+                prize_commitment: None,
+                deposit: U128(deposit),
+            },
+            deposit,
+            None,
+        );
+        self.house_next_start =
+            env::block_timestamp() + config.interval_hours * NANOSECONDS_PER_HOUR;
+
+        log_event(
+            "house_raffle_started",
+            json!({
+                "raffle_id": raffle_account_id.to_string(),
+                "prize": U128(self.raffles.get(&raffle_account_id).unwrap().prize),
+                "pool": U128(self.house_pool),
+            }),
+        );
+    }
+
+    pub fn get_house_raffles(&self) -> HouseRafflesView {
+        HouseRafflesView {
+            config: self.house_config,
+            pool: U128(self.house_pool),
+            next_start: self.house_next_start / TO_FROM_NANOSECONDS,
+        }
+    }
+
+    // Returns the house raffles the account lost in a row since its last win or pity prize
+    pub fn get_house_streak(&self, account_id: String) -> u32 {
+        AccountId::try_from(account_id)
+            .ok()
+            .and_then(|account_id| self.house_streaks.get(&account_id))
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fees::DEFAULT_CREATION_FEE;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_house_raffle() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_house_raffles(Some(HouseRaffleConfig {
+            fee_share_bps: 10_000,
+            prize: U128(ONE_NEAR),
+            interval_hours: 24,
+            duration_hours: 1,
+            pity_losses: 0,
+            pity_prize: U128(0),
+        }));
+
+        // the creation fees of two raffles fund the pool
+        for account_id in [alice_account_id(), bob_account_id()] {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(17 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.register_raffle(
+                Some(U64(1644353705121)),
+                U64(1644353705521),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+        assert_eq!(
+            contract.get_house_raffles().pool,
+            U128(2 * DEFAULT_CREATION_FEE)
+        );
+        // the lower testnet fees don't cover the storage of a full raffle, so the pool is topped up
+        contract.house_pool = 4 * ONE_NEAR;
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.start_house_raffle();
+
+        let raffle_details = contract.raffles.get(&raffle_dapp_account_id()).unwrap();
+        assert_eq!(raffle_details.owner, raffle_dapp_account_id());
+        assert!(raffle_details.prize >= ONE_NEAR);
+        // the creation fee of the house raffle goes back to its pool
+        assert_eq!(
+            contract.get_house_raffles().pool,
+            U128(4 * ONE_NEAR - ONE_NEAR - ORGANIZER_BOND - HOUSE_STORAGE_ALLOWANCE)
+        );
+
+        // nobody entered, so a keeper finalizing it returns everything to the pool
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS + 2 * NANOSECONDS_PER_HOUR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.finalize_raffle(raffle_dapp_account_id().to_string());
+        assert!(contract.raffles.get(&raffle_dapp_account_id()).is_none());
+        assert_eq!(contract.get_house_raffles().pool, U128(4 * ONE_NEAR));
+    }
+
+    #[test]
+    fn check_house_streaks() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_house_raffles(Some(HouseRaffleConfig {
+            fee_share_bps: 10_000,
+            prize: U128(ONE_NEAR),
+            interval_hours: 24,
+            duration_hours: 1,
+            pity_losses: 2,
+            pity_prize: U128(ONE_NEAR / 10),
+        }));
+        contract.house_pool = ONE_NEAR;
+
+        let participants = vec![alice_account_id(), bob_account_id()];
+        contract.record_house_streaks(&alice_account_id(), participants.clone());
+        assert_eq!(contract.get_house_streak(alice_account_id().to_string()), 0);
+        assert_eq!(contract.get_house_streak(bob_account_id().to_string()), 1);
+
+        // bob loses a second time in a row and is paid the pity prize
+        contract.record_house_streaks(&alice_account_id(), participants.clone());
+        assert_eq!(contract.get_house_streak(bob_account_id().to_string()), 0);
+        assert_eq!(contract.house_pool, ONE_NEAR - ONE_NEAR / 10);
+
+        // a win resets the streak
+        contract.record_house_streaks(&alice_account_id(), participants.clone());
+        contract.record_house_streaks(&bob_account_id(), participants);
+        assert_eq!(contract.get_house_streak(bob_account_id().to_string()), 0);
+        assert_eq!(contract.get_house_streak(alice_account_id().to_string()), 1);
+        assert_eq!(contract.get_house_streak("Not an id!".to_string()), 0);
+    }
+}
@@ -0,0 +1,445 @@
+use crate::events::log_event;
+use crate::raffle::RaffleStore;
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use serde_json::json;
+
+// one basis point is a hundredth of a percent
+const BASIS_POINTS: u32 = 10_000;
+
+// largest number of prizes an instant-win table can have
+const MAX_INSTANT_WIN_TIERS: usize = 5;
+
+// Prize won instantly by an entry with a probability of `odds_bps` basis points
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InstantWinTier {
+    pub(crate) odds_bps: u16,
+    pub(crate) prize: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InstantWinsView {
+    pub(crate) tiers: Vec<InstantWinTier>,
+    pub(crate) pool: U128,
+}
+
+impl RaffleDapp {
+    // Resolves whether the entry `account_id` just made wins instantly, paying the prize of the
+    // tier it falls in from the raffle's escrowed pool. The roll mixes the block's random seed
+    // with the entrant and the number of entries, so entries of the same block roll apart. An
+    // account only rolls once per raffle, so it can't drain the pool by leaving and entering
+    // again.
+    pub(crate) fn internal_resolve_instant_win(
+        &mut self,
+        raffle_account_id: &AccountId,
+        account_id: &AccountId,
+    ) {
+        let mut raffle_details = self.raffles.get(raffle_account_id).unwrap();
+        if raffle_details.instant_wins.is_empty()
+            || !raffle_details.instant_rolls.insert(account_id)
+        {
+            return;
+        }
+        self.internal_save_raffle(raffle_account_id, &raffle_details);
+
+        let roll_seed = env::sha256(
+            &[
+                env::random_seed().as_slice(),
+                account_id.as_bytes(),
+                &raffle_details.participants.len().to_le_bytes(),
+            ]
+            .concat(),
+        );
+        let roll = u32::from_le_bytes(roll_seed[..4].try_into().unwrap()) % BASIS_POINTS;
+
+        let mut odds = 0;
+        let tier = raffle_details.instant_wins.iter().find(|tier| {
+            odds += tier.odds_bps as u32;
+            roll < odds
+        });
+        let prize = match tier {
+            Some(tier) => tier.prize.0,
+            None => return,
+        };
+
+        if raffle_details.instant_pool < prize {
+            env::log_str(&format!(
+                "The instant-win pool of the raffle {:?} can't pay the prize won by {:?}",
+                raffle_account_id.to_string(),
+                account_id.to_string()
+            ));
+            return;
+        }
+        raffle_details.instant_pool -= prize;
+        self.internal_save_raffle(raffle_account_id, &raffle_details);
+        Promise::new(account_id.clone()).transfer(prize);
+
+        log_event(
+            "instant_win",
+            json!({
+                "raffle_id": raffle_account_id.to_string(),
+                "account_id": account_id.to_string(),
+                "prize": U128(prize),
+                "pool": U128(raffle_details.instant_pool),
+            }),
+        );
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Sets the table of prizes won instantly by the entries of the raffle, whichever way they are
+    // made, alongside the scheduled draw. The attached deposit pays for the storage of the table and the rest is
+    // escrowed in the pool paying the instant winners. It can only be changed while the raffle
+    // has no participants.
+    #[payable]
+    pub fn set_instant_wins(&mut self, raffle_id: String, tiers: Vec<InstantWinTier>) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can set its instant wins"
+        );
+
+        assert!(
+            raffle_details.participants.is_empty() && raffle_details.waitlist.is_empty(),
+            "The instant wins cannot be changed once the raffle has participants"
+        );
+
+        assert!(
+            tiers.len() <= MAX_INSTANT_WIN_TIERS,
+            "An instant-win table cannot have more than {} prizes",
+            MAX_INSTANT_WIN_TIERS
+        );
+        assert!(
+            tiers.iter().map(|tier| tier.odds_bps as u32).sum::<u32>() <= BASIS_POINTS,
+            "The odds of the instant wins cannot add up to more than {} basis points",
+            BASIS_POINTS
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        raffle_details.instant_wins = tiers;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        // The pool doesn't change the size of the record and the update is already recorded in the
+        // change feed, so the measured cost stays exact.
+        // Storage released by a smaller table is left in the storage cost, returned at the end.
+        let storage_cost = storage_cost_since(initial_storage_usage);
+        assert!(
+            env::attached_deposit() >= storage_cost,
+            "The attached deposit should cover the storage cost of the instant wins"
+        );
+        raffle_details.storage_cost += storage_cost;
+        raffle_details.instant_pool += env::attached_deposit() - storage_cost;
+        self.internal_store_raffle(&raffle_account_id, &raffle_details);
+
+        env::log_str(&format!(
+            "The raffle {:?} has {} instant-win prizes paid from a pool of {} yoctoNEAR",
+            raffle_account_id.to_string(),
+            raffle_details.instant_wins.len(),
+            raffle_details.instant_pool
+        ));
+    }
+
+    // Adds the attached deposit to the pool paying the instant winners of the raffle. What is
+    // left of the pool goes back to the owner once the raffle ends.
+    #[payable]
+    pub fn fund_instant_wins(&mut self, raffle_id: String) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can fund its instant wins"
+        );
+
+        assert!(
+            env::block_timestamp() < raffle_details.end,
+            "Instant wins can only be funded before the raffle ends"
+        );
+
+        raffle_details.instant_pool += env::attached_deposit();
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        env::log_str(&format!(
+            "The instant-win pool of the raffle {:?} is now {} yoctoNEAR",
+            raffle_account_id.to_string(),
+            raffle_details.instant_pool
+        ));
+    }
+
+    pub fn get_instant_wins(&self, raffle_id: String) -> Option<InstantWinsView> {
+        self.find_raffle(raffle_id)
+            .map(|(_, raffle_details)| InstantWinsView {
+                tiers: raffle_details.instant_wins,
+                pool: U128(raffle_details.instant_pool),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::{testing_env, CryptoHash};
+
+    #[test]
+    fn check_instant_win() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(3 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        // every entry wins
+        contract.set_instant_wins(
+            alice_account_id().to_string(),
+            vec![InstantWinTier {
+                odds_bps: 10_000,
+                prize: U128(ONE_NEAR),
+            }],
+        );
+        let pool = contract
+            .get_instant_wins(alice_account_id().to_string())
+            .unwrap()
+            .pool
+            .0;
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+        assert_eq!(
+            contract
+                .get_instant_wins(alice_account_id().to_string())
+                .unwrap()
+                .pool
+                .0,
+            pool - ONE_NEAR
+        );
+    }
+
+    #[test]
+    fn check_instant_win_promo_code_and_waitlist() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            Some(1),
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(3 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        // every entry wins
+        contract.set_instant_wins(
+            alice_account_id().to_string(),
+            vec![InstantWinTier {
+                odds_bps: 10_000,
+                prize: U128(ONE_NEAR),
+            }],
+        );
+        let pool = contract
+            .get_instant_wins(alice_account_id().to_string())
+            .unwrap()
+            .pool
+            .0;
+
+        context.attached_deposit(ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        let code_hash: CryptoHash = env::sha256(b"GIVEAWAY").try_into().unwrap();
+        contract.add_promo_codes(alice_account_id().to_string(), vec![code_hash.into()]);
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(
+            alice_account_id().to_string(),
+            None,
+            Some("GIVEAWAY".to_string()),
+            None,
+        );
+
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.join_waitlist(alice_account_id().to_string(), None, None);
+
+        // jacob rolls once bob's leaving promotes him from the waitlist
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.leave_raffle(alice_account_id().to_string());
+        assert_eq!(
+            contract
+                .get_instant_wins(alice_account_id().to_string())
+                .unwrap()
+                .pool
+                .0,
+            pool - 2 * ONE_NEAR
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The odds of the instant wins cannot add up to more than")]
+    fn check_instant_win_odds() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_instant_wins(
+            alice_account_id().to_string(),
+            vec![
+                InstantWinTier {
+                    odds_bps: 6_000,
+                    prize: U128(ONE_NEAR),
+                },
+                InstantWinTier {
+                    odds_bps: 5_000,
+                    prize: U128(ONE_NEAR),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn check_instant_win_once_per_account() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(3 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.set_instant_wins(
+            alice_account_id().to_string(),
+            vec![InstantWinTier {
+                odds_bps: 10_000,
+                prize: U128(ONE_NEAR),
+            }],
+        );
+        let pool = contract
+            .get_instant_wins(alice_account_id().to_string())
+            .unwrap()
+            .pool
+            .0;
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.leave_raffle(alice_account_id().to_string());
+
+        // entering again doesn't roll the instant wins a second time
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+        assert_eq!(
+            contract
+                .get_instant_wins(alice_account_id().to_string())
+                .unwrap()
+                .pool
+                .0,
+            pool - ONE_NEAR
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use crate::events::log_event;
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use serde_json::json;
+
+// one basis point is a hundredth of a percent
+const BASIS_POINTS: u16 = 10_000;
+
+// number of latest entrants the jackpot is drawn from, older entries being overwritten
+pub(crate) const MAX_JACKPOT_ENTRANTS: u64 = 1_000;
+
+// Contract-wide jackpot growing with a share of every fee, awarded every `interval_hours` to an
+// entrant drawn among the latest ones
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JackpotConfig {
+    // share of every fee added to the jackpot, in basis points
+    pub(crate) fee_share_bps: u16,
+    pub(crate) interval_hours: u64,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JackpotView {
+    pub(crate) config: Option<JackpotConfig>,
+    pub(crate) amount: U128,
+    // entrants since the last award the next one is drawn from
+    pub(crate) entrants: u64,
+    // in ms
+    pub(crate) next_draw: Timestamp,
+}
+
+impl RaffleDapp {
+    // Adds the share of a fee going to the jackpot, returning what is left of it
+    pub(crate) fn internal_fund_jackpot(&mut self, fee: Balance) -> Balance {
+        let share = self
+            .jackpot_config
+            .map(|config| fee * config.fee_share_bps as Balance / BASIS_POINTS as Balance)
+            .unwrap_or(0);
+        if share > 0 {
+            self.jackpot += share;
+            log_event(
+                "jackpot_grew",
+                json!({
+                    "amount": U128(share),
+                    "jackpot": U128(self.jackpot),
+                }),
+            );
+        }
+        fee - share
+    }
+
+    // Records an entrant the jackpot can be awarded to, overwriting the oldest one once
+    // `MAX_JACKPOT_ENTRANTS` are recorded
+    pub(crate) fn internal_record_jackpot_entrant(&mut self, account_id: &AccountId) {
+        if self.jackpot_config.is_none() {
+            return;
+        }
+        self.jackpot_entrants.insert(
+            &(self.jackpot_entrant_count % MAX_JACKPOT_ENTRANTS),
+            account_id,
+        );
+        self.jackpot_entrant_count += 1;
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Configures the progressive jackpot, passing `None` stops it. The jackpot collected so far
+    // is kept for when it resumes.
+    pub fn set_jackpot(&mut self, config: Option<JackpotConfig>) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can configure the jackpot"
+        );
+        self.assert_not_decommissioned();
+
+        if let Some(config) = config {
+            assert!(
+                config.fee_share_bps <= BASIS_POINTS,
+                "The share of the fees cannot exceed {} basis points",
+                BASIS_POINTS
+            );
+            assert!(
+                config.interval_hours > 0,
+                "The jackpot should be awarded at most every hour"
+            );
+            if self.jackpot_config.is_none() {
+                self.jackpot_next_draw =
+                    env::block_timestamp() + config.interval_hours * NANOSECONDS_PER_HOUR;
+            }
+        }
+
+        self.jackpot_config = config;
+        match config {
+            Some(config) => env::log_str(&format!(
+                "A jackpot funded by {} basis points of the fees is awarded every {} hours",
+                config.fee_share_bps, config.interval_hours
+            )),
+            None => env::log_str("The jackpot is stopped"),
+        }
+    }
+
+    // Awards the jackpot to an entrant drawn among the latest ones once it is due. Anyone can
+    // call it.
+    pub fn award_jackpot(&mut self) {
+        assert!(self.jackpot_config.is_some(), "The jackpot is stopped");
+        let config = self.jackpot_config.unwrap();
+
+        assert!(
+            env::block_timestamp() >= self.jackpot_next_draw,
+            "The jackpot is not due yet"
+        );
+
+        let entrants = self.jackpot_entrant_count.min(MAX_JACKPOT_ENTRANTS);
+        assert!(
+            entrants > 0,
+            "Nobody entered a raffle since the last jackpot"
+        );
+
+        // The bias of the modulo is negligible next to the range of the seed's first 8 bytes
+        let random_seed = env::random_seed();
+        let index = u64::from_le_bytes(random_seed[..8].try_into().unwrap()) % entrants;
+        let winner_account_id = self.jackpot_entrants.get(&index).unwrap();
+
+        let amount = self.jackpot;
+        self.jackpot = 0;
+        self.jackpot_entrant_count = 0;
+        self.jackpot_next_draw =
+            env::block_timestamp() + config.interval_hours * NANOSECONDS_PER_HOUR;
+        if amount > 0 {
+            Promise::new(winner_account_id.clone()).transfer(amount);
+        }
+
+        log_event(
+            "jackpot_awarded",
+            json!({
+                "winner": winner_account_id.to_string(),
+                "amount": U128(amount),
+                "entrants": entrants,
+            }),
+        );
+    }
+
+    pub fn get_jackpot(&self) -> JackpotView {
+        JackpotView {
+            config: self.jackpot_config,
+            amount: U128(self.jackpot),
+            entrants: self.jackpot_entrant_count.min(MAX_JACKPOT_ENTRANTS),
+            next_draw: self.jackpot_next_draw / TO_FROM_NANOSECONDS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fees::DEFAULT_CREATION_FEE;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_jackpot() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_jackpot(Some(JackpotConfig {
+            fee_share_bps: 5_000,
+            interval_hours: 24,
+        }));
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(
+            contract.get_jackpot().amount,
+            U128(DEFAULT_CREATION_FEE / 2)
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+        assert_eq!(contract.get_jackpot().entrants, 1);
+
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.award_jackpot();
+
+        let jackpot = contract.get_jackpot();
+        assert_eq!(jackpot.amount, U128(0));
+        assert_eq!(jackpot.entrants, 0);
+        assert!(jackpot.next_draw > 1644353705125);
+    }
+}
@@ -1,27 +1,220 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::UnorderedMap;
-use near_sdk::{env, near_bindgen, AccountId, Balance, Gas, Promise, Timestamp};
-use serde_json::json;
+use near_sdk::collections::{LookupMap, TreeMap, UnorderedMap, UnorderedSet};
+use near_sdk::{
+    env, near_bindgen, AccountId, Balance, BlockHeight, Promise, PublicKey, StorageUsage, Timestamp,
+};
+
+mod admin;
+mod badges;
+mod changes;
+mod claims;
+mod curation;
+mod drafts;
+mod eligibility;
+mod events;
+mod export;
+mod fees;
+mod finalize;
+mod foreign_payouts;
+mod fulfillment;
+mod gas;
+mod gates;
+mod house;
+mod instant_wins;
+mod jackpot;
+mod merkle;
+mod metadata;
+mod metrics;
+mod mystery;
+mod nft_pool;
+mod number_pick;
+mod organizers;
+mod participation;
+mod payouts;
+mod prices;
+mod prize_swaps;
+mod promo_codes;
+mod promotions;
+mod quiz;
+mod raffle;
+mod scheduled_draws;
+mod sealed;
+mod series;
+mod source_metadata;
+mod sunset;
+#[cfg(test)]
+mod test_utils;
+mod tokens;
+mod upgrade;
+mod views;
+
+use crate::changes::RaffleChange;
+pub use crate::changes::{RaffleChangeKind, RaffleChangeView};
+use crate::claims::PrizeClaim;
+pub use crate::eligibility::{EligibilityView, IneligibilityReason};
+pub use crate::export::RaffleExport;
+use crate::fees::{Donation, FeeConfig, PendingFeeConfig};
+use crate::foreign_payouts::ForeignPayout;
+pub use crate::foreign_payouts::{
+    ChainSignaturesConfig, ForeignAddress, ForeignChain, ForeignPayoutStatus, ForeignPayoutView,
+};
+use crate::fulfillment::Fulfillment;
+pub use crate::fulfillment::{FulfillmentStatus, FulfillmentView};
+pub use crate::gas::GasConfig;
+pub use crate::gates::EntryGate;
+pub use crate::house::{HouseRaffleConfig, HouseRafflesView};
+pub use crate::instant_wins::{InstantWinTier, InstantWinsView};
+pub use crate::jackpot::{JackpotConfig, JackpotView};
+pub use crate::metadata::{LocalizedMetadataView, RaffleMetadata};
+use crate::metrics::UsageMetrics;
+pub use crate::metrics::{FailureCode, UsageMetricsView};
+pub use crate::organizers::{OrganizerAnalyticsView, OrganizerProfileView};
+use crate::organizers::{OrganizerProfile, PendingRating};
+use crate::participation::{Bundle, EntryRateLimit, EntryWindow, DEFAULT_FREE_ENTRY_LIMIT};
+use crate::payouts::Settlement;
+pub use crate::payouts::{PayoutKind, PayoutReceipt, PayoutStatus, SettlementView};
+use crate::prices::CachedPrice;
+pub use crate::prices::{PriceOracleConfig, PriceView, UsdValuesView};
+use crate::prize_swaps::HeldPrize;
+pub use crate::prize_swaps::{HeldPrizeView, PrizeCurrency, PrizeSwapConfig, SwapPool};
+use crate::promotions::Promotion;
+pub use crate::promotions::PromotionView;
+pub use crate::quiz::Quiz;
+pub use crate::raffle::{RaffleConfig, RaffleDetails};
+use crate::scheduled_draws::DrawSchedule;
+pub use crate::scheduled_draws::ScheduledDrawView;
+use crate::series::Series;
+pub use crate::series::{SeriesStanding, SeriesView};
+pub use crate::source_metadata::{BuildInfo, ContractSourceMetadata, Standard};
+pub use crate::sunset::SunsetPhase;
+pub use crate::tokens::{AcceptedToken, AcceptedTokenView};
+pub use crate::views::{
+    BundleView, EntryRateLimitView, EntryView, FeeConfigView, LimitsView, OddsView, Page,
+    PendingDrawView, PendingFeeConfigView, PendingSweep, RaffleSortKey, RaffleStatus, RaffleView,
+    SolvencyView, StorageUsageView, TimelineBucket, TimelineInterval, TreasuryView,
+};
 
 // constant representing 1 NEAR in yoctoNear
-const ONE_NEAR: u128 = 1_000_000_000_000_000_000_000_000;
+pub(crate) const ONE_NEAR: u128 = 1_000_000_000_000_000_000_000_000;
 
 // constant to convert milliseconds to nanoseconds and vice versa
-const TO_FROM_NANOSECONDS: u64 = 1_000_000;
+pub(crate) const TO_FROM_NANOSECONDS: u64 = 1_000_000;
 
-#[derive(BorshDeserialize, BorshSerialize)]
-pub struct RaffleDetails {
-    prize: Balance,
-    start: Timestamp,
-    end: Timestamp,
-    participants: UnorderedMap<AccountId, Balance>,
-    attempts: u8,
+// constant representing one hour in nanoseconds
+pub(crate) const NANOSECONDS_PER_HOUR: u64 = 3_600 * 1_000 * TO_FROM_NANOSECONDS;
+
+// Cost of the storage used by the contract since `initial_storage_usage` was measured
+pub(crate) fn storage_cost_since(initial_storage_usage: StorageUsage) -> Balance {
+    env::storage_usage().saturating_sub(initial_storage_usage) as Balance * env::storage_byte_cost()
 }
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct RaffleDapp {
-    raffles: UnorderedMap<AccountId, RaffleDetails>,
+    pub(crate) raffles: UnorderedMap<AccountId, RaffleDetails>,
+    pub(crate) bundles: LookupMap<AccountId, Vec<Bundle>>,
+    // secondary indexes used to list the raffles in order
+    pub(crate) raffles_by_prize: TreeMap<(Balance, AccountId), ()>,
+    pub(crate) raffles_by_end: TreeMap<(Timestamp, AccountId), ()>,
+    pub(crate) raffles_by_participants: TreeMap<(u64, AccountId), ()>,
+    // sum of the prizes of the registered raffles, kept in sync with the indexes
+    pub(crate) prize_liabilities: Balance,
+    // maximum number of entries per account and time window, across every raffle
+    pub(crate) entry_rate_limit: Option<EntryRateLimit>,
+    pub(crate) entry_windows: LookupMap<AccountId, EntryWindow>,
+    // maximum number of entries locking no tokens per account and time window
+    pub(crate) free_entry_limit: Option<EntryRateLimit>,
+    pub(crate) free_entry_windows: LookupMap<AccountId, EntryWindow>,
+    // whether touching an ended raffle schedules its finalization
+    pub(crate) lazy_finalization: bool,
+    // account receiving the funds of expired raffles
+    pub(crate) treasury: AccountId,
+    // prizes escrowed for the holders of claim keys
+    pub(crate) prize_claims: LookupMap<PublicKey, PrizeClaim>,
+    // feed of the latest changes of the raffles, keyed by their sequence number
+    pub(crate) change_seq: u64,
+    pub(crate) changes: LookupMap<u64, RaffleChange>,
+    pub(crate) fee_config: FeeConfig,
+    // fee schedule proposed by the contract account, applicable once its timelock expires
+    pub(crate) pending_fee_config: Option<PendingFeeConfig>,
+    // share of the fees donated to a public-goods account, and the fees collected so far
+    pub(crate) donation: Option<Donation>,
+    pub(crate) fees_collected: Balance,
+    pub(crate) fees_donated: Balance,
+    // gas budgets of the cross-contract calls made by the contract
+    pub(crate) gas_config: GasConfig,
+    // payloads sealed for the winners of settled raffles, keyed by raffle and winner
+    pub(crate) sealed_prizes: LookupMap<(AccountId, AccountId), String>,
+    // track record of the organizers of past raffles
+    pub(crate) organizers: LookupMap<AccountId, OrganizerProfile>,
+    // ratings the participants of settled raffles can still leave, keyed by raffle and participant
+    pub(crate) pending_ratings: LookupMap<(AccountId, AccountId), PendingRating>,
+    // number of settled raffles of an organizer each account took part in, keyed by organizer
+    // and participant
+    pub(crate) audiences: LookupMap<(AccountId, AccountId), u32>,
+    // named series of raffles keyed by organizer and name, and the series the next raffle of an
+    // organizer joins
+    pub(crate) series: LookupMap<(AccountId, String), Series>,
+    pub(crate) recurring_series: LookupMap<AccountId, String>,
+    // settings of raffles saved before they are funded, keyed by raffle
+    pub(crate) drafts: LookupMap<AccountId, RaffleConfig>,
+    // promotion matching the prizes of new raffles out of its budget
+    pub(crate) promotion: Option<Promotion>,
+    // raffles run by the contract out of a share of the fees, the pool funding them and the
+    // time from which the next one can start
+    pub(crate) house_config: Option<HouseRaffleConfig>,
+    pub(crate) house_pool: Balance,
+    pub(crate) house_next_start: Timestamp,
+    // house raffles lost in a row by each account, towards the pity prize
+    pub(crate) house_streaks: LookupMap<AccountId, u32>,
+    // NEP-393 registry issuing the participation badges
+    pub(crate) badge_registry: Option<AccountId>,
+    // prize of the number pick raffles nobody won, added to the next raffle of their organizer
+    pub(crate) rollovers: LookupMap<AccountId, Balance>,
+    // progressive jackpot growing with a share of the fees, the time from which it can be
+    // awarded and the latest entrants it is drawn from
+    pub(crate) jackpot_config: Option<JackpotConfig>,
+    pub(crate) jackpot: Balance,
+    pub(crate) jackpot_next_draw: Timestamp,
+    pub(crate) jackpot_entrants: LookupMap<u64, AccountId>,
+    pub(crate) jackpot_entrant_count: u64,
+    // raffles highlighted by the official frontend, and the moderators curating them
+    pub(crate) featured_raffles: TreeMap<AccountId, ()>,
+    pub(crate) moderators: UnorderedSet<AccountId>,
+    // retirement of the deployment, once started by the contract account
+    pub(crate) sunset: Option<SunsetPhase>,
+    // payout receipts of the latest settled raffles, keyed by settlement, and the latest
+    // settlement of each raffle
+    pub(crate) settlement_seq: u64,
+    pub(crate) settlements: LookupMap<u64, Settlement>,
+    pub(crate) latest_settlements: LookupMap<AccountId, u64>,
+    // MPC signer paying the winners who elected a foreign chain, and their escrowed prizes
+    pub(crate) chain_signatures: Option<ChainSignaturesConfig>,
+    pub(crate) foreign_payout_seq: u64,
+    pub(crate) foreign_payouts: LookupMap<u64, ForeignPayout>,
+    // DEX the winners can have their prize swapped on, and the prizes held until their winners
+    // elect the currency they are paid in
+    pub(crate) prize_swaps: Option<PrizeSwapConfig>,
+    pub(crate) held_prize_seq: u64,
+    pub(crate) held_prizes: LookupMap<u64, HeldPrize>,
+    // bonds kept until the winners of physical prizes confirm their delivery
+    pub(crate) fulfillment_seq: u64,
+    pub(crate) fulfillments: LookupMap<u64, Fulfillment>,
+    // deployment the raffles are being exported to, the raffles whose import is in flight, and
+    // the previous deployment allowed to import its raffles into this one
+    pub(crate) export_target: Option<AccountId>,
+    pub(crate) pending_exports: LookupMap<AccountId, RaffleExport>,
+    pub(crate) pending_export_count: u64,
+    pub(crate) import_source: Option<AccountId>,
+    // NEP-141 tokens the raffles can be gated with, keyed by their contract
+    pub(crate) accepted_tokens: UnorderedMap<AccountId, AcceptedToken>,
+    // oracle pricing NEAR and the accepted tokens in USD, and the latest prices it returned
+    pub(crate) price_oracle: Option<PriceOracleConfig>,
+    pub(crate) prices: UnorderedMap<AccountId, CachedPrice>,
+    // listings of the raffles in several languages, keyed by raffle
+    pub(crate) raffle_metadata: LookupMap<AccountId, RaffleMetadata>,
+    // calls of the main methods and failures, for monitoring
+    pub(crate) metrics: UsageMetrics,
 }
 
 impl Default for RaffleDapp {
@@ -41,228 +234,73 @@ impl RaffleDapp {
         );
         Self {
             raffles: UnorderedMap::new(b"r"),
+            bundles: LookupMap::new(b"b"),
+            raffles_by_prize: TreeMap::new(b"p"),
+            raffles_by_end: TreeMap::new(b"e"),
+            raffles_by_participants: TreeMap::new(b"c"),
+            prize_liabilities: 0,
+            entry_rate_limit: None,
+            lazy_finalization: false,
+            entry_windows: LookupMap::new(b"w"),
+            free_entry_limit: Some(DEFAULT_FREE_ENTRY_LIMIT),
+            free_entry_windows: LookupMap::new(b"fw".to_vec()),
+            treasury: env::current_account_id(),
+            prize_claims: LookupMap::new(b"k"),
+            change_seq: 0,
+            changes: LookupMap::new(b"f"),
+            fee_config: FeeConfig::default(),
+            pending_fee_config: None,
+            donation: None,
+            fees_collected: 0,
+            fees_donated: 0,
+            gas_config: GasConfig::default(),
+            sealed_prizes: LookupMap::new(b"s"),
+            organizers: LookupMap::new(b"o"),
+            pending_ratings: LookupMap::new(b"v"),
+            audiences: LookupMap::new(b"h"),
+            series: LookupMap::new(b"n"),
+            recurring_series: LookupMap::new(b"m"),
+            drafts: LookupMap::new(b"d"),
+            promotion: None,
+            house_config: None,
+            house_pool: 0,
+            house_next_start: 0,
+            house_streaks: LookupMap::new(b"hs".to_vec()),
+            badge_registry: None,
+            rollovers: LookupMap::new(b"l"),
+            jackpot_config: None,
+            jackpot: 0,
+            jackpot_next_draw: 0,
+            jackpot_entrants: LookupMap::new(b"j"),
+            jackpot_entrant_count: 0,
+            featured_raffles: TreeMap::new(b"g"),
+            moderators: UnorderedSet::new(b"q"),
+            sunset: None,
+            settlement_seq: 0,
+            settlements: LookupMap::new(b"x"),
+            latest_settlements: LookupMap::new(b"y"),
+            chain_signatures: None,
+            foreign_payout_seq: 0,
+            foreign_payouts: LookupMap::new(b"z"),
+            prize_swaps: None,
+            held_prize_seq: 0,
+            held_prizes: LookupMap::new(b"hp".to_vec()),
+            fulfillment_seq: 0,
+            fulfillments: LookupMap::new(b"fu".to_vec()),
+            export_target: None,
+            pending_exports: LookupMap::new(b"i"),
+            pending_export_count: 0,
+            import_source: None,
+            accepted_tokens: UnorderedMap::new(b"a"),
+            price_oracle: None,
+            prices: UnorderedMap::new(b"u"),
+            raffle_metadata: LookupMap::new(b"t"),
+            metrics: UsageMetrics {
+                since: env::block_timestamp(),
+                ..UsageMetrics::default()
+            },
         }
     }
-
-    #[payable]
-    pub fn register_raffle(&mut self, start: Timestamp, end: Timestamp) {
-        // Check if the attached deposit is greater than 2 NEAR to cover storage and service fees
-        // Thus, Prize = attached depost (in NEAR) - 2 NEAR
-        assert!(
-            env::attached_deposit() > 2 * ONE_NEAR,
-            "Prize money should be greater than 2 NEAR"
-        );
-
-        // Allowing to register only one raffle per account,
-        // they can register a new raffle after their current raffle ends
-        assert!(
-            self.raffles.get(&env::predecessor_account_id()).is_none(),
-            "You have already registered a raffle"
-        );
-
-        assert!(
-            end > start,
-            "The raffle's end date should be greater than its start date"
-        );
-
-        let raffle_details = RaffleDetails {
-            prize: env::attached_deposit() - 2 * ONE_NEAR,
-            start: start * TO_FROM_NANOSECONDS,
-            end: end * TO_FROM_NANOSECONDS,
-            participants: UnorderedMap::new(env::sha256(&env::predecessor_account_id().as_bytes())),
-            attempts: 0,
-        };
-
-        self.raffles
-            .insert(&env::predecessor_account_id(), &raffle_details);
-
-        let raffle_details: RaffleDetails =
-            self.raffles.get(&env::predecessor_account_id()).unwrap();
-
-        env::log_str(&format!(
-            "Raffle registered succesfully for {:?} with prize money {:?} NEAR starting from {:?} ms till {:?} ms",
-            env::predecessor_account_id().to_string(),
-            raffle_details.prize / ONE_NEAR,
-            raffle_details.start/TO_FROM_NANOSECONDS,
-            raffle_details.end/TO_FROM_NANOSECONDS
-        ));
-    }
-
-    #[payable]
-    pub fn participate(&mut self, raffle_id: String) {
-        // Users can participate in the raffle by locking at least 1 NEAR token to prevent spam or duplicate entries to some extent.
-        // The participant's locked NEAR tokens plays no role while deciding the winner to conduct an unbiased raffle.
-        assert!(
-            env::attached_deposit() >= 1 * ONE_NEAR,
-            "The locked amount should be at least 1 NEAR"
-        );
-
-        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
-
-        assert_ne!(
-            env::predecessor_account_id(),
-            env::current_account_id(),
-            "The contract account cannot participate in raffles for security reasons"
-        );
-
-        assert_ne!(
-            env::predecessor_account_id(),
-            raffle_account_id,
-            "You cannot participate in your own raffle"
-        );
-
-        assert!(
-            self.raffles.get(&raffle_account_id).is_some(),
-            "Sorry, no raffle is being conducted by {:?}",
-            raffle_account_id.to_string()
-        );
-
-        assert!(
-            self.raffles
-                .get(&raffle_account_id)
-                .unwrap()
-                .participants
-                .get(&env::predecessor_account_id())
-                .is_none(),
-            "You have already participated in this raffle"
-        );
-
-        assert!(
-            self.raffles
-                .get(&raffle_account_id)
-                .unwrap()
-                .participants
-                .len()
-                <= 256,
-            "Sorry, the raffle's maximum participants limit reached"
-        );
-
-        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
-
-        let current_timestamp = env::block_timestamp();
-        assert!(
-            current_timestamp > raffle_details.start && current_timestamp < raffle_details.end,
-            "The raffle has either not started yet or has finished already"
-        );
-
-        let locked_tokens = env::attached_deposit();
-        raffle_details
-            .participants
-            .insert(&env::predecessor_account_id(), &locked_tokens);
-
-        self.raffles.insert(&raffle_account_id, &raffle_details);
-
-        env::log_str(&format!(
-            "{:?} has sucessfully participated in the raffle of {:?} with {:?} NEAR token(s) locked",
-            env::predecessor_account_id().to_string(),
-            raffle_account_id.to_string(),
-            self.raffles
-                .get(&raffle_account_id)
-                .unwrap()
-                .participants
-                .get(&env::predecessor_account_id())
-                .unwrap()
-                / ONE_NEAR
-        ));
-    }
-
-    pub fn finalize_raffle(&mut self, raffle_id: String) {
-        let raffle_account_id: AccountId = AccountId::try_from(raffle_id.clone()).unwrap();
-        assert!(
-            env::predecessor_account_id() == raffle_account_id
-                || env::predecessor_account_id() == env::current_account_id(),
-            "Only the raffle's owner or the contract account can finalize the raffle"
-        );
-
-        assert!(
-            self.raffles.get(&raffle_account_id).is_some(),
-            "No raffle registered from this account"
-        );
-
-        let raffle_detail: RaffleDetails = self.raffles.get(&raffle_account_id).unwrap();
-        let current_time = env::block_timestamp();
-
-        assert!(
-            current_time > raffle_detail.end,
-            "You can only finalize raffle after it ends"
-        );
-
-        let participants: UnorderedMap<AccountId, Balance> = raffle_detail.participants;
-
-        if participants.len() == 0 {
-            self.raffles.remove(&raffle_account_id);
-            Promise::new(raffle_account_id).transfer(raffle_detail.prize);
-            env::log_str("Nobody participated in your raffle");
-            return;
-        }
-
-        let participants_vec = participants.to_vec();
-
-        env::log_str(&format!(
-            "Total number of participants: {:?}",
-            participants.len()
-        ));
-
-        let length = participants_vec.len() as u8;
-        let random_seed = env::random_seed();
-        env::log_str(&format!("env::random_seed = {:?}", random_seed));
-
-        let mut random_index: u8 = random_seed[0];
-        let mut found = false;
-
-        for x in random_seed.iter() {
-            if *x < length {
-                random_index = *x;
-                found = true;
-                break;
-            }
-        }
-
-        let mut raffle_detail: RaffleDetails = self.raffles.get(&raffle_account_id).unwrap();
-        raffle_detail.attempts += 1;
-
-        if !found {
-            self.raffles.insert(&raffle_account_id, &raffle_detail);
-            env::log_str(
-                "Failed to discover Random index in this block, searching it in the future blocks...",
-            );
-            Promise::new(env::current_account_id()).function_call(
-                "finalize_raffle".to_string(),
-                json!({ "raffle_id": raffle_account_id.to_string() })
-                    .to_string()
-                    .into_bytes(),
-                0,
-                Gas::from(env::prepaid_gas() - env::used_gas() * 2),
-            );
-            return;
-        }
-
-        let winner_id = (participants_vec[random_index as usize].0).to_string();
-        let winner_locked_tokens = participants_vec[random_index as usize].1;
-
-        Promise::new(AccountId::try_from(winner_id.clone()).unwrap())
-            .transfer(raffle_detail.prize + winner_locked_tokens);
-
-        env::log_str(&format!(
-            "The winner for this raffle is {:?} and his locked tokens was {:?} NEAR",
-            winner_id,
-            winner_locked_tokens / ONE_NEAR
-        ));
-
-        env::log_str(&format!(
-            "The Random index {:?} was discovered in {:?} attempt(s)",
-            random_index, raffle_detail.attempts
-        ));
-
-        for (participants_account_id, locked_tokens) in participants_vec {
-            if participants_account_id.to_string() == winner_id {
-                continue;
-            }
-            Promise::new(participants_account_id).transfer(locked_tokens);
-        }
-
-        self.raffles.remove(&raffle_account_id);
-    }
 }
 
 /*
@@ -270,49 +308,27 @@ impl RaffleDapp {
  * to run these, the command will be:
  * cargo test --package raffle-dapp -- --nocapture
  * Note: 'raffle-dapp' comes from Cargo.toml's 'name' key
+ * Each module keeps its own tests, sharing the helpers of `test_utils`
  */
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use near_sdk::test_utils::VMContextBuilder;
-    use near_sdk::{testing_env, AccountId};
-
-    // Setting up a mock context with current account as 'raffle-dapp.testnet'
-    fn get_context() -> VMContextBuilder {
-        let mut builder = VMContextBuilder::new();
-        builder.current_account_id(raffle_dapp_account_id());
-        builder
-    }
-
-    fn raffle_dapp_account_id() -> AccountId {
-        AccountId::new_unchecked("raffle-dapp.testnet".to_string())
-    }
-
-    fn alice_account_id() -> AccountId {
-        AccountId::new_unchecked("alice.testnet".to_string())
-    }
-
-    fn bob_account_id() -> AccountId {
-        AccountId::new_unchecked("bob.testnet".to_string())
-    }
-
-    fn jacob_account_id() -> AccountId {
-        AccountId::new_unchecked("jacob.testnet".to_string())
-    }
-
-    fn mike_account_id() -> AccountId {
-        AccountId::new_unchecked("mike.testnet".to_string())
-    }
-
-    fn jack_account_id() -> AccountId {
-        AccountId::new_unchecked("jack.testnet".to_string())
-    }
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
 
     #[test]
     #[should_panic(expected = "The smart contract should be initialized before usage")]
     fn check_default() {
-        RaffleDapp::default().register_raffle(1644353705121, 1644353705130);
+        RaffleDapp::default().register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705130),
+            None,
+            None,
+            None,
+            None,
+        );
     }
 
     #[test]
@@ -323,175 +339,4 @@ mod tests {
         testing_env!(context.build());
         RaffleDapp::new();
     }
-
-    #[test]
-    #[should_panic(expected = "Prize money should be greater than 2 N")]
-    fn check_register_raffles_less_than_two_near() {
-        let mut context = get_context();
-        context.predecessor_account_id(raffle_dapp_account_id());
-        testing_env!(context.build());
-        let mut contract = RaffleDapp::new();
-
-        context.predecessor_account_id(alice_account_id());
-        context.attached_deposit(1 * ONE_NEAR);
-        testing_env!(context.build());
-
-        contract.register_raffle(1644353705121, 1644353705130);
-    }
-
-    #[test]
-    fn check_register_raffles() {
-        let mut context = get_context();
-        context.predecessor_account_id(raffle_dapp_account_id());
-        testing_env!(context.build());
-        let mut contract = RaffleDapp::new();
-
-        context.predecessor_account_id(alice_account_id());
-        context.attached_deposit(3 * ONE_NEAR);
-        testing_env!(context.build());
-
-        contract.register_raffle(1644353705121, 1644353705130);
-    }
-
-    #[test]
-    #[should_panic(expected = "You have already registered a raffle")]
-    fn check_register_raffles_already_registered() {
-        let mut context = get_context();
-        context.predecessor_account_id(raffle_dapp_account_id());
-        testing_env!(context.build());
-        let mut contract = RaffleDapp::new();
-
-        context.predecessor_account_id(alice_account_id());
-        context.attached_deposit(3 * ONE_NEAR);
-        testing_env!(context.build());
-
-        contract.register_raffle(1644353705121, 1644353705130);
-        contract.register_raffle(1644353705128, 1644353705140);
-    }
-
-    #[test]
-    fn check_participate() {
-        let mut context = get_context();
-        context.predecessor_account_id(raffle_dapp_account_id());
-        testing_env!(context.build());
-
-        let mut contract = RaffleDapp::new();
-
-        context.predecessor_account_id(alice_account_id());
-        context.attached_deposit(17 * ONE_NEAR);
-        testing_env!(context.build());
-
-        contract.register_raffle(1644353705121, 1644353705130);
-
-        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
-        context.predecessor_account_id(bob_account_id());
-        context.attached_deposit(2 * ONE_NEAR);
-        testing_env!(context.build());
-
-        contract.participate(alice_account_id().to_string());
-    }
-
-    #[test]
-    fn check_randomness() {
-        let mut map: UnorderedMap<String, u8> = UnorderedMap::new(b"c");
-
-        map.insert(&"alice 1".to_string(), &1);
-        map.insert(&"alice 2".to_string(), &2);
-        map.insert(&"alice 3".to_string(), &3);
-        map.insert(&"alice 4".to_string(), &4);
-        map.insert(&"alice 5".to_string(), &5);
-        map.insert(&"alice 6".to_string(), &6);
-        map.insert(&"alice 7".to_string(), &7);
-        map.insert(&"alice 8".to_string(), &8);
-        map.insert(&"alice 9".to_string(), &9);
-        map.insert(&"alice 10".to_string(), &10);
-
-        let mut context = get_context();
-        let v = vec![
-            150, 255, 1, 8, 45, 32, 101, 50, 123, 221, 58, 3, 127, 202, 56, 16, 32, 9, 111, 255,
-            49, 45, 77, 17, 25, 26, 37, 79, 210, 159, 31, 56,
-        ];
-        context.random_seed(v);
-        testing_env!(context.build());
-
-        let map_vec = map.to_vec();
-        let length = map_vec.len() as u8;
-        let random_seed = env::random_seed();
-        let mut random_index = random_seed[0];
-        let mut found = false;
-
-        for x in random_seed.iter() {
-            if *x < length {
-                random_index = *x;
-                found = true;
-                break;
-            }
-        }
-
-        // Should print RANDOM: ["alice 2" , 2] | INDEX: 1
-        if !found {
-            println!("Failed to discover Random index in this block, searching it in the future blocks...");
-        } else {
-            println!(
-                "RANDOM: [{:?} , {:?}] | INDEX: {:?}",
-                map_vec[random_index as usize].0,
-                map_vec[random_index as usize].1,
-                random_index as usize
-            );
-        }
-    }
-
-    #[test]
-    fn check_finalize_raffle() {
-        let mut context = get_context();
-        context.predecessor_account_id(raffle_dapp_account_id());
-        testing_env!(context.build());
-
-        let mut contract = RaffleDapp::new();
-
-        context.predecessor_account_id(alice_account_id());
-        context.attached_deposit(17 * ONE_NEAR);
-        testing_env!(context.build());
-
-        contract.register_raffle(1644353705121, 1644353705521);
-
-        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
-        context.predecessor_account_id(bob_account_id());
-        context.attached_deposit(2 * ONE_NEAR);
-        testing_env!(context.build());
-
-        contract.participate(alice_account_id().to_string());
-
-        context.block_timestamp(1644353705135 * TO_FROM_NANOSECONDS);
-        context.predecessor_account_id(jacob_account_id());
-        context.attached_deposit(10 * ONE_NEAR);
-        testing_env!(context.build());
-
-        contract.participate(alice_account_id().to_string());
-
-        context.block_timestamp(1644353705145 * TO_FROM_NANOSECONDS);
-        context.predecessor_account_id(mike_account_id());
-        context.attached_deposit(15 * ONE_NEAR);
-        testing_env!(context.build());
-
-        contract.participate(alice_account_id().to_string());
-
-        context.block_timestamp(1644353705150 * TO_FROM_NANOSECONDS);
-        context.predecessor_account_id(jack_account_id());
-        context.attached_deposit(27 * ONE_NEAR);
-        testing_env!(context.build());
-
-        contract.participate(alice_account_id().to_string());
-
-        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
-        context.predecessor_account_id(alice_account_id());
-        let v = vec![
-            150, 255, 1, 8, 45, 32, 101, 50, 123, 221, 58, 3, 127, 202, 56, 16, 32, 9, 111, 255,
-            49, 45, 77, 17, 25, 26, 37, 79, 210, 159, 31, 56,
-        ];
-        context.random_seed(v);
-        testing_env!(context.build());
-
-        contract.finalize_raffle(alice_account_id().to_string());
-    }
 }
@@ -0,0 +1,58 @@
+use near_sdk::{env, AccountId, CryptoHash};
+
+fn sha256(value: &[u8]) -> CryptoHash {
+    env::sha256(value).try_into().unwrap()
+}
+
+// Leaf of an allowlist's Merkle tree: the sha256 hash of the account id
+pub(crate) fn allowlist_leaf(account_id: &AccountId) -> CryptoHash {
+    sha256(account_id.as_bytes())
+}
+
+// Hash of two sibling nodes. The pair is sorted first, so proofs don't need to tell on
+// which side each sibling sits.
+pub(crate) fn hash_pair(a: &CryptoHash, b: &CryptoHash) -> CryptoHash {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    sha256(&[left.as_slice(), right.as_slice()].concat())
+}
+
+// Checks that `leaf` belongs to the tree of `root`, `proof` holding the siblings on the path
+// from the leaf up to the root
+pub(crate) fn verify_proof(root: &CryptoHash, leaf: CryptoHash, proof: &[CryptoHash]) -> bool {
+    let computed_root = proof
+        .iter()
+        .fold(leaf, |node, sibling| hash_pair(&node, sibling));
+    &computed_root == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn check_verify_proof() {
+        let leaves: Vec<CryptoHash> = [
+            alice_account_id(),
+            bob_account_id(),
+            jacob_account_id(),
+            mike_account_id(),
+        ]
+        .iter()
+        .map(allowlist_leaf)
+        .collect();
+
+        let left = hash_pair(&leaves[0], &leaves[1]);
+        let right = hash_pair(&leaves[2], &leaves[3]);
+        let root = hash_pair(&left, &right);
+
+        assert!(verify_proof(&root, leaves[2], &[leaves[3], left]));
+        assert!(verify_proof(&root, leaves[1], &[leaves[0], right]));
+        assert!(!verify_proof(&root, leaves[2], &[leaves[0], right]));
+        assert!(!verify_proof(
+            &root,
+            allowlist_leaf(&jack_account_id()),
+            &[leaves[3], left]
+        ));
+    }
+}
@@ -0,0 +1,301 @@
+use crate::raffle::RaffleStore;
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// largest number of locales the metadata of a raffle can be translated into
+const MAX_LOCALES: usize = 10;
+
+// largest length of a title and of a description, in bytes
+const MAX_TITLE_LENGTH: usize = 100;
+const MAX_DESCRIPTION_LENGTH: usize = 1000;
+
+// Listing of a raffle in several languages, the texts being keyed by locale, e.g. `en` or
+// `es-MX`. The default locale is shown when a text isn't translated into the requested one.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RaffleMetadata {
+    pub(crate) default_locale: String,
+    pub(crate) title: BTreeMap<String, String>,
+    pub(crate) description: BTreeMap<String, String>,
+}
+
+// Metadata of a raffle in a single locale, `locale` being the one the title was found in
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LocalizedMetadataView {
+    pub(crate) locale: String,
+    pub(crate) title: String,
+    pub(crate) description: Option<String>,
+    // every locale the raffle is listed in
+    pub(crate) locales: Vec<String>,
+}
+
+impl RaffleMetadata {
+    fn validate(&self) {
+        assert!(
+            self.title.contains_key(&self.default_locale),
+            "The title should be set in the default locale"
+        );
+        assert!(
+            self.locales().len() <= MAX_LOCALES,
+            "The metadata cannot be translated into more than {} locales",
+            MAX_LOCALES
+        );
+        assert!(
+            self.title
+                .values()
+                .all(|title| title.len() <= MAX_TITLE_LENGTH),
+            "A title cannot be longer than {} bytes",
+            MAX_TITLE_LENGTH
+        );
+        assert!(
+            self.description
+                .values()
+                .all(|description| description.len() <= MAX_DESCRIPTION_LENGTH),
+            "A description cannot be longer than {} bytes",
+            MAX_DESCRIPTION_LENGTH
+        );
+    }
+
+    fn locales(&self) -> Vec<String> {
+        let mut locales: Vec<String> = self
+            .title
+            .keys()
+            .chain(self.description.keys())
+            .cloned()
+            .collect();
+        locales.sort();
+        locales.dedup();
+        locales
+    }
+
+    // Text of `texts` in `locale`, falling back to its language (`es` for `es-MX`) and then to
+    // the default locale. Returns the locale the text was found in.
+    fn localize<'a>(
+        &'a self,
+        texts: &'a BTreeMap<String, String>,
+        locale: &str,
+    ) -> Option<(&'a String, &'a String)> {
+        let language = locale.split('-').next().unwrap_or(locale);
+        [locale, language, self.default_locale.as_str()]
+            .into_iter()
+            .find_map(|locale| texts.get_key_value(locale))
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Sets the listing of the raffle in one or several locales, passing `None` removes it. The
+    // attached deposit pays for the storage of the metadata, which is refunded along with the
+    // storage of the raffle once it is settled.
+    #[payable]
+    pub fn set_raffle_metadata(&mut self, raffle_id: String, metadata: Option<RaffleMetadata>) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can set its metadata"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        match &metadata {
+            Some(metadata) => {
+                metadata.validate();
+                self.raffle_metadata.insert(&raffle_account_id, metadata);
+            }
+            None => {
+                self.raffle_metadata.remove(&raffle_account_id);
+            }
+        }
+
+        // The storage released by smaller metadata is refunded right away
+        let storage_cost = storage_cost_since(initial_storage_usage);
+        let released = (initial_storage_usage.saturating_sub(env::storage_usage())) as Balance
+            * env::storage_byte_cost();
+        assert!(
+            env::attached_deposit() >= storage_cost,
+            "The attached deposit should cover the storage of the metadata, {} yoctoNEAR",
+            storage_cost
+        );
+        raffle_details.storage_cost = raffle_details.storage_cost + storage_cost - released;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        let refund = env::attached_deposit() - storage_cost + released;
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+
+        env::log_str(&format!(
+            "The metadata of the raffle {:?} was {}",
+            raffle_account_id.to_string(),
+            if metadata.is_some() { "set" } else { "removed" }
+        ));
+    }
+
+    // Returns the metadata of the raffle in `locale`, or in the closest locale it is translated
+    // into, the default one if none is given
+    pub fn get_raffle_metadata(
+        &self,
+        raffle_id: String,
+        locale: Option<String>,
+    ) -> Option<LocalizedMetadataView> {
+        let metadata = self
+            .raffle_metadata
+            .get(&AccountId::try_from(raffle_id).ok()?)?;
+        let locale = locale.unwrap_or_else(|| metadata.default_locale.clone());
+
+        let (title_locale, title) = metadata.localize(&metadata.title, &locale)?;
+        Some(LocalizedMetadataView {
+            locale: title_locale.clone(),
+            title: title.clone(),
+            description: metadata
+                .localize(&metadata.description, &locale)
+                .map(|(_, description)| description.clone()),
+            locales: metadata.locales(),
+        })
+    }
+
+    // Returns every translation of the metadata of the raffle
+    pub fn get_raffle_metadata_translations(&self, raffle_id: String) -> Option<RaffleMetadata> {
+        self.raffle_metadata
+            .get(&AccountId::try_from(raffle_id).ok()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_raffle_metadata() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        let storage_cost = contract
+            .raffles
+            .get(&alice_account_id())
+            .unwrap()
+            .storage_cost;
+
+        context.attached_deposit(ONE_NEAR / 10);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.set_raffle_metadata(
+            alice_account_id().to_string(),
+            Some(RaffleMetadata {
+                default_locale: "en".to_string(),
+                title: BTreeMap::from([
+                    ("en".to_string(), "Summer raffle".to_string()),
+                    ("es".to_string(), "Rifa de verano".to_string()),
+                ]),
+                description: BTreeMap::from([(
+                    "en".to_string(),
+                    "Win a prize this summer".to_string(),
+                )]),
+            }),
+        );
+        assert!(
+            contract
+                .raffles
+                .get(&alice_account_id())
+                .unwrap()
+                .storage_cost
+                > storage_cost
+        );
+
+        // the regional locale falls back to its language, and the missing description to the
+        // default locale
+        let metadata = contract
+            .get_raffle_metadata(alice_account_id().to_string(), Some("es-MX".to_string()))
+            .unwrap();
+        assert_eq!(metadata.locale, "es");
+        assert_eq!(metadata.title, "Rifa de verano");
+        assert_eq!(
+            metadata.description,
+            Some("Win a prize this summer".to_string())
+        );
+        assert_eq!(metadata.locales, vec!["en".to_string(), "es".to_string()]);
+
+        let metadata = contract
+            .get_raffle_metadata(alice_account_id().to_string(), Some("fr".to_string()))
+            .unwrap();
+        assert_eq!(metadata.title, "Summer raffle");
+
+        // removing the metadata releases its storage
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.set_raffle_metadata(alice_account_id().to_string(), None);
+        assert!(contract
+            .get_raffle_metadata(alice_account_id().to_string(), None)
+            .is_none());
+        assert_eq!(
+            contract
+                .raffles
+                .get(&alice_account_id())
+                .unwrap()
+                .storage_cost,
+            storage_cost
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The title should be set in the default locale")]
+    fn check_raffle_metadata_default_locale() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        contract.set_raffle_metadata(
+            alice_account_id().to_string(),
+            Some(RaffleMetadata {
+                default_locale: "en".to_string(),
+                title: BTreeMap::from([("es".to_string(), "Rifa de verano".to_string())]),
+                description: BTreeMap::new(),
+            }),
+        );
+    }
+}
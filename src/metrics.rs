@@ -0,0 +1,143 @@
+use crate::*;
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+
+// Outcomes of calls that failed without reverting the state, so that they can be counted. Calls
+// panicking revert the counters along with everything else.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum FailureCode {
+    // the entry to a gated raffle was rejected once the token's contract answered
+    EntryRejected,
+    // no winner could be drawn from the seed of the block
+    DrawRetried,
+    // a payout of a settled raffle failed
+    PayoutFailed,
+    // the import of a raffle into the new deployment failed
+    ExportFailed,
+}
+
+const FAILURE_CODES: [FailureCode; 4] = [
+    FailureCode::EntryRejected,
+    FailureCode::DrawRetried,
+    FailureCode::PayoutFailed,
+    FailureCode::ExportFailed,
+];
+
+// Calls of the main methods and failures counted since `since`
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct UsageMetrics {
+    // raffles registered, whichever method registered them
+    pub(crate) register_raffle: u64,
+    pub(crate) participate: u64,
+    pub(crate) finalize_raffle: u64,
+    // indexed by failure code
+    pub(crate) failures: [u64; 4],
+    pub(crate) since: Timestamp,
+}
+
+// JSON representation of the usage metrics, `since` is in ms
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UsageMetricsView {
+    pub(crate) register_raffle: u64,
+    pub(crate) participate: u64,
+    pub(crate) finalize_raffle: u64,
+    pub(crate) failures: Vec<(FailureCode, u64)>,
+    pub(crate) since: U64,
+}
+
+impl UsageMetrics {
+    pub(crate) fn record_failure(&mut self, code: FailureCode) {
+        self.failures[code as usize] += 1;
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Restarts the usage metrics from zero
+    pub fn reset_metrics(&mut self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can reset the metrics"
+        );
+
+        self.metrics = UsageMetrics {
+            since: env::block_timestamp(),
+            ..UsageMetrics::default()
+        };
+        env::log_str("The usage metrics were reset");
+    }
+
+    pub fn get_metrics(&self) -> UsageMetricsView {
+        UsageMetricsView {
+            register_raffle: self.metrics.register_raffle,
+            participate: self.metrics.participate,
+            finalize_raffle: self.metrics.finalize_raffle,
+            failures: FAILURE_CODES
+                .iter()
+                .map(|code| (*code, self.metrics.failures[*code as usize]))
+                .collect(),
+            since: U64(self.metrics.since / TO_FROM_NANOSECONDS),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_metrics() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+        contract.metrics.record_failure(FailureCode::PayoutFailed);
+
+        let metrics = contract.get_metrics();
+        assert_eq!(
+            (
+                metrics.register_raffle,
+                metrics.participate,
+                metrics.finalize_raffle
+            ),
+            (1, 1, 0)
+        );
+        assert_eq!(metrics.failures[FailureCode::PayoutFailed as usize].1, 1);
+
+        context.predecessor_account_id(raffle_dapp_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.reset_metrics();
+        let metrics = contract.get_metrics();
+        assert_eq!(metrics.participate, 0);
+        assert_eq!(metrics.since.0, 1644353705125);
+    }
+}
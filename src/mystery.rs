@@ -0,0 +1,181 @@
+use crate::events::log_event;
+use crate::raffle::RaffleStore;
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::CryptoHash;
+use serde_json::json;
+
+// Commitment of the organizer to the deposit of a raffle whose prize is kept hidden until it is
+// finalized
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct MysteryPrize {
+    pub(crate) commitment: CryptoHash,
+    pub(crate) deposit: Balance,
+    pub(crate) revealed: bool,
+}
+
+// Commitment to `deposit` (in yoctoNEAR) salted with `salt`: the sha256 of "<deposit>:<salt>"
+pub(crate) fn prize_commitment(deposit: Balance, salt: &str) -> CryptoHash {
+    env::sha256(format!("{}:{}", deposit, salt).as_bytes())
+        .try_into()
+        .unwrap()
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Reveals the mystery prize of the raffle once it ended, checking the salt against the
+    // commitment made at registration. The raffle can only be finalized by its owner or operator
+    // once its prize is revealed.
+    pub fn reveal_mystery_prize(&mut self, raffle_id: String, salt: String) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can reveal its prize"
+        );
+
+        assert!(
+            env::block_timestamp() > raffle_details.end,
+            "The mystery prize can only be revealed after the raffle ends"
+        );
+
+        let mystery_prize = raffle_details
+            .mystery_prize
+            .as_mut()
+            .filter(|mystery_prize| !mystery_prize.revealed);
+        assert!(
+            mystery_prize.is_some(),
+            "The raffle has no hidden prize to reveal"
+        );
+
+        let mystery_prize = mystery_prize.unwrap();
+        assert!(
+            prize_commitment(mystery_prize.deposit, &salt) == mystery_prize.commitment,
+            "The salt doesn't match the commitment to the prize"
+        );
+
+        mystery_prize.revealed = true;
+        let deposit = mystery_prize.deposit;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        log_event(
+            "mystery_prize_revealed",
+            json!({
+                "raffle_id": raffle_account_id.to_string(),
+                "deposit": U128(deposit),
+                "prize": U128(raffle_details.prize),
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_mystery_prize() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            Some(prize_commitment(17 * ONE_NEAR, "salt").into()),
+        );
+        assert!(contract
+            .get_raffles_sorted(RaffleSortKey::End, None, None, None)
+            .items
+            .iter()
+            .all(|raffle| raffle.prize.is_none()));
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        testing_env!(context.build());
+
+        contract.reveal_mystery_prize(alice_account_id().to_string(), "salt".to_string());
+        assert!(contract
+            .get_raffles_sorted(RaffleSortKey::End, None, None, None)
+            .items
+            .iter()
+            .all(|raffle| raffle.prize.is_some()));
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        assert!(contract.raffles.get(&alice_account_id()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "The salt doesn't match the commitment to the prize")]
+    fn check_mystery_prize_wrong_salt() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            Some(prize_commitment(17 * ONE_NEAR, "salt").into()),
+        );
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        testing_env!(context.build());
+
+        contract.reveal_mystery_prize(alice_account_id().to_string(), "pepper".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "The organizer has to reveal the mystery prize")]
+    fn check_finalize_unrevealed_mystery_prize() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            Some(prize_commitment(17 * ONE_NEAR, "salt").into()),
+        );
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+    }
+}
@@ -0,0 +1,358 @@
+use crate::participation::InternalParticipation;
+use crate::quiz::assert_no_quiz;
+use crate::raffle::{RaffleDetails, RaffleStore};
+use crate::*;
+use near_sdk::{Gas, PromiseOrValue};
+use serde_json::json;
+
+// largest number of entrants of an NFT pool raffle, its winner receiving every pooled NFT in the
+// call settling it
+pub(crate) const MAX_NFT_POOL_SIZE: u64 = 10;
+
+// Sends a pooled NFT with a NEP-171 `nft_transfer`
+fn transfer_nft(nft_contract_id: &AccountId, token_id: &str, receiver_id: &AccountId, gas: Gas) {
+    Promise::new(nft_contract_id.clone()).function_call(
+        "nft_transfer".to_string(),
+        json!({ "receiver_id": receiver_id.to_string(), "token_id": token_id })
+            .to_string()
+            .into_bytes(),
+        1,
+        gas,
+    );
+}
+
+impl RaffleDetails {
+    // Gives the NFT pooled by `account_id` back to it, once it left the raffle
+    pub(crate) fn return_pooled_nft(&mut self, account_id: &AccountId, gas: Gas) {
+        if let (Some(nft_contract_id), Some(token_id)) =
+            (self.nft_pool.as_ref(), self.pooled_nfts.remove(account_id))
+        {
+            transfer_nft(nft_contract_id, &token_id, account_id, gas);
+        }
+    }
+
+    // Sends every pooled NFT to the winner, or back to their entrants if there is none or the
+    // NFTs were only staked
+    pub(crate) fn pay_out_nft_pool(&mut self, winner_account_id: Option<&AccountId>, gas: Gas) {
+        let nft_contract_id = match self.nft_pool.as_ref() {
+            Some(nft_contract_id) => nft_contract_id,
+            None => return,
+        };
+        let winner_account_id = winner_account_id.filter(|_| !self.nft_staked);
+        for (account_id, token_id) in self.pooled_nfts.to_vec() {
+            transfer_nft(
+                nft_contract_id,
+                &token_id,
+                winner_account_id.unwrap_or(&account_id),
+                gas,
+            );
+        }
+        self.pooled_nfts.clear();
+    }
+}
+
+impl RaffleDapp {
+    // Sets the collection the entrants of the raffle pool an NFT of, and whether the NFTs are
+    // only staked
+    fn internal_set_nft_pool(
+        &mut self,
+        raffle_id: String,
+        nft_contract_id: Option<String>,
+        staked: bool,
+    ) -> AccountId {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can set its NFT pool"
+        );
+
+        assert!(
+            raffle_details.participants.is_empty() && raffle_details.waitlist.is_empty(),
+            "The NFT pool cannot be changed once the raffle has participants"
+        );
+
+        if nft_contract_id.is_some() {
+            assert!(
+                raffle_details.entry_gate.is_none(),
+                "A raffle restricted to token holders cannot be an NFT pool"
+            );
+            assert!(
+                raffle_details.pick_range.is_none(),
+                "A number pick raffle cannot be an NFT pool"
+            );
+            assert!(
+                raffle_details.max_participants <= MAX_NFT_POOL_SIZE,
+                "An NFT pool raffle cannot have more than {} participants",
+                MAX_NFT_POOL_SIZE
+            );
+        }
+
+        raffle_details.nft_pool =
+            nft_contract_id.map(|contract_id| AccountId::try_from(contract_id).unwrap());
+        raffle_details.nft_staked = staked && raffle_details.nft_pool.is_some();
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+        raffle_account_id
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Turns the raffle into an NFT pool: every entrant transfers one NFT of `nft_contract_id`
+    // with `nft_transfer_call` to enter, and the winner receives all of them. Passing `None` turns
+    // the raffle back into a regular one. It can only be changed while the raffle has no
+    // participants.
+    pub fn set_nft_pool(&mut self, raffle_id: String, nft_contract_id: Option<String>) {
+        let raffle_account_id = self.internal_set_nft_pool(raffle_id, nft_contract_id, false);
+        let raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+
+        match &raffle_details.nft_pool {
+            Some(nft_contract_id) => env::log_str(&format!(
+                "The raffle {:?} is entered with an NFT of {:?}, all of them going to the winner",
+                raffle_account_id.to_string(),
+                nft_contract_id.to_string()
+            )),
+            None => env::log_str(&format!(
+                "The raffle {:?} is no longer an NFT pool",
+                raffle_account_id.to_string()
+            )),
+        }
+    }
+
+    // Lets the raffle be entered by staking an NFT of `nft_contract_id` instead of locking NEAR:
+    // entrants transfer it with `nft_transfer_call` like in an NFT pool, but every NFT goes back
+    // to its entrant at settlement and the winner only receives the prize. Passing `None` turns
+    // the raffle back into a regular one.
+    pub fn set_nft_stake(&mut self, raffle_id: String, nft_contract_id: Option<String>) {
+        let raffle_account_id = self.internal_set_nft_pool(raffle_id, nft_contract_id, true);
+        let raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+
+        match &raffle_details.nft_pool {
+            Some(nft_contract_id) => env::log_str(&format!(
+                "The raffle {:?} is entered by staking an NFT of {:?}, returned at settlement",
+                raffle_account_id.to_string(),
+                nft_contract_id.to_string()
+            )),
+            None => env::log_str(&format!(
+                "The raffle {:?} is no longer entered with NFTs",
+                raffle_account_id.to_string()
+            )),
+        }
+    }
+
+    // NEP-171 receiver entering the previous owner of the NFT into the raffle whose id is `msg`.
+    // The storage of the entry is paid from the allowance of sponsored entries, and the NFT is
+    // returned if the entry is rejected.
+    pub fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: String,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let raffle_account_id: AccountId = AccountId::try_from(msg).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+        assert_eq!(
+            self.raffles.get(&raffle_account_id).unwrap().nft_pool,
+            Some(env::predecessor_account_id()),
+            "The raffle doesn't pool NFTs of this contract"
+        );
+        assert_no_quiz(&self.raffles.get(&raffle_account_id).unwrap());
+
+        let initial_storage_usage = env::storage_usage();
+        self.internal_check_entry(&raffle_account_id, &previous_owner_id, &[]);
+        self.internal_enter(&raffle_account_id, &previous_owner_id, 0);
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        raffle_details
+            .pooled_nfts
+            .insert(&previous_owner_id, &token_id);
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+        self.internal_debit_sponsor_pool(&raffle_account_id, initial_storage_usage);
+
+        env::log_str(&format!(
+            "{:?} pooled the NFT {:?} sent by {:?} to enter the raffle of {:?}",
+            previous_owner_id.to_string(),
+            token_id,
+            sender_id.to_string(),
+            raffle_account_id.to_string()
+        ));
+        PromiseOrValue::Value(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{get_created_receipts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn register_nft_pool_raffle(context: &mut VMContextBuilder, contract: &mut RaffleDapp) {
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            Some(5),
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.fund_sponsored_entries(alice_account_id().to_string());
+        contract.set_nft_pool(
+            alice_account_id().to_string(),
+            Some(mike_account_id().to_string()),
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(mike_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+    }
+
+    #[test]
+    fn check_nft_pool() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_nft_pool_raffle(&mut context, &mut contract);
+
+        for (account_id, token_id) in [(bob_account_id(), "1"), (jacob_account_id(), "2")] {
+            contract.nft_on_transfer(
+                account_id.clone(),
+                account_id,
+                token_id.to_string(),
+                alice_account_id().to_string(),
+            );
+        }
+
+        let mut raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(raffle_details.participants.len(), 2);
+        assert_eq!(
+            raffle_details.pooled_nfts.get(&jacob_account_id()),
+            Some("2".to_string())
+        );
+
+        raffle_details.pay_out_nft_pool(Some(&bob_account_id()), Gas(0));
+        assert!(raffle_details.pooled_nfts.is_empty());
+    }
+
+    #[test]
+    fn check_nft_stake() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_nft_pool_raffle(&mut context, &mut contract);
+
+        // the pool is turned into a stake before anyone enters
+        context.predecessor_account_id(alice_account_id());
+        testing_env!(context.build());
+        contract.set_nft_stake(
+            alice_account_id().to_string(),
+            Some(mike_account_id().to_string()),
+        );
+
+        context.predecessor_account_id(mike_account_id());
+        testing_env!(context.build());
+        contract.nft_on_transfer(
+            bob_account_id(),
+            bob_account_id(),
+            "1".to_string(),
+            alice_account_id().to_string(),
+        );
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert!(raffle_details.nft_staked);
+        assert_eq!(raffle_details.participants.get(&bob_account_id()), Some(0));
+        assert_eq!(
+            raffle_details.pooled_nfts.get(&bob_account_id()),
+            Some("1".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Entries to this raffle are made by transferring an NFT of its pool")]
+    fn check_nft_pool_regular_entry() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_nft_pool_raffle(&mut context, &mut contract);
+
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+    }
+
+    #[test]
+    fn check_sweep_nft_pool() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_nft_pool_raffle(&mut context, &mut contract);
+
+        contract.nft_on_transfer(
+            bob_account_id(),
+            bob_account_id(),
+            "1".to_string(),
+            alice_account_id().to_string(),
+        );
+
+        // a year and a day after the end of the raffle, it is swept without being finalized
+        context.block_timestamp((1644353705521 + 366 * 24 * 3_600 * 1_000) * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(raffle_dapp_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.sweep_expired_raffles(None);
+        assert!(contract.raffles.get(&alice_account_id()).is_none());
+
+        // the NFT is transferred back to bob
+        let nft_transfers: Vec<Vec<u8>> = get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id == mike_account_id())
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                VmAction::FunctionCall {
+                    method_name, args, ..
+                } if method_name == "nft_transfer" => Some(args),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            nft_transfers,
+            vec![br#"{"receiver_id":"bob.testnet","token_id":"1"}"#.to_vec()]
+        );
+    }
+}
@@ -0,0 +1,403 @@
+use crate::events::log_event;
+use crate::participation::assert_storage_covered;
+use crate::payouts::PayoutKind;
+use crate::raffle::{RaffleDetails, RaffleStore};
+use crate::*;
+use near_sdk::json_types::U128;
+use serde_json::json;
+
+// smallest range of numbers a number pick raffle can be played with
+const MIN_PICK_RANGE: u8 = 2;
+
+impl RaffleDapp {
+    // Settles a number pick raffle once its winning number is drawn: the prize is split equally
+    // among the participants who picked it, or rolls over to the organizer's next raffle if
+    // nobody did
+    pub(crate) fn internal_settle_number_pick(
+        &mut self,
+        raffle_account_id: &AccountId,
+        mut raffle_detail: RaffleDetails,
+        winning_number: u8,
+    ) {
+        let winners: Vec<AccountId> = raffle_detail
+            .picks
+            .iter()
+            .filter(|(account_id, number)| {
+                *number == winning_number && raffle_detail.participants.get(account_id).is_some()
+            })
+            .map(|(account_id, _)| account_id)
+            .collect();
+
+        log_event(
+            "number_drawn",
+            json!({
+                "raffle_id": raffle_account_id.to_string(),
+                "winning_number": winning_number,
+                "winners": winners.iter().map(|account_id| account_id.to_string()).collect::<Vec<_>>(),
+            }),
+        );
+
+        if winners.is_empty() {
+            self.internal_roll_over(raffle_account_id, raffle_detail);
+            return;
+        }
+
+        // Every winner but the first is paid their share here, the first one being settled along
+        // with the raffle and taking the finalization bounty and the rounding remainder
+        let share = raffle_detail.prize / winners.len() as Balance;
+        for winner_account_id in &winners[1..] {
+            let (prize_fee, bounty, winner_prize) = self.fee_config.split_prize(share);
+            self.internal_collect_fee(prize_fee);
+            Promise::new(winner_account_id.clone()).transfer(winner_prize + bounty);
+        }
+        raffle_detail.prize -= share * (winners.len() - 1) as Balance;
+
+        self.internal_settle_raffle(raffle_account_id, raffle_detail, &winners[0]);
+    }
+
+    // Refunds the participants of a number pick raffle nobody won and sets its prize aside for
+    // the next raffle of its organizer
+    fn internal_roll_over(
+        &mut self,
+        raffle_account_id: &AccountId,
+        mut raffle_detail: RaffleDetails,
+    ) {
+        let settlement_id = self.internal_open_settlement();
+        let mut payouts = Vec::new();
+        for (participants_account_id, locked_tokens) in raffle_detail.participants.to_vec() {
+            self.internal_payout(
+                settlement_id,
+                &mut payouts,
+                &participants_account_id,
+                locked_tokens,
+                PayoutKind::Refund,
+            );
+        }
+        self.internal_record_settlement(settlement_id, raffle_account_id, payouts, None);
+
+        self.record_completed_raffle(&raffle_detail.owner, 0, raffle_detail.participants.len());
+        self.record_audience(
+            &raffle_detail.owner,
+            raffle_detail.participants.keys().collect(),
+        );
+        self.record_series_edition(&raffle_detail, None);
+        self.internal_issue_badges(raffle_account_id, &mut raffle_detail);
+
+        raffle_detail.clear_storage();
+        self.internal_remove_raffle(raffle_account_id);
+
+        // The promotion's match isn't the organizer's, it goes back to the promotion
+        self.internal_return_prize_match(raffle_detail.promo_match);
+        let rollover = raffle_detail.prize - raffle_detail.promo_match;
+        let total = self.rollovers.get(&raffle_detail.owner).unwrap_or(0) + rollover;
+        self.rollovers.insert(&raffle_detail.owner, &total);
+
+        let bond = Self::internal_release_bond(&raffle_detail);
+        self.internal_pay_owner(
+            raffle_detail.owner.clone(),
+            raffle_detail.storage_cost
+                + raffle_detail.sponsor_pool
+                + raffle_detail.instant_pool
+                + raffle_detail.sealed_prize_deposit()
+                + bond,
+        );
+
+        env::log_str(&format!(
+            "Nobody picked the winning number, {:?} yoctoNEAR roll over to the next raffle of {:?}",
+            rollover,
+            raffle_detail.owner.to_string()
+        ));
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Turns the raffle into a number pick: every participant picks a number from 1 to
+    // `max_number`, and the prize is split among the ones picking the number drawn. Passing
+    // `None` turns the raffle back into a regular one. It can only be changed while the raffle
+    // has no participants.
+    pub fn set_number_pick(&mut self, raffle_id: String, max_number: Option<u8>) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can set its number pick"
+        );
+
+        assert!(
+            raffle_details.participants.is_empty() && raffle_details.waitlist.is_empty(),
+            "The number pick cannot be changed once the raffle has participants"
+        );
+
+        if let Some(max_number) = max_number {
+            assert!(
+                max_number >= MIN_PICK_RANGE,
+                "The numbers should range at least from 1 to {}",
+                MIN_PICK_RANGE
+            );
+            assert!(
+                raffle_details.nft_pool.is_none(),
+                "An NFT pool raffle cannot be a number pick"
+            );
+            assert!(
+                raffle_details.approver.is_none(),
+                "A raffle whose draw needs an approval cannot be a number pick"
+            );
+            assert!(
+                raffle_details.physical_prize.is_none(),
+                "A number pick raffle cannot have a physical prize"
+            );
+        }
+
+        raffle_details.pick_range = max_number;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        match max_number {
+            Some(max_number) => env::log_str(&format!(
+                "The participants of the raffle {:?} pick a number from 1 to {}",
+                raffle_account_id.to_string(),
+                max_number
+            )),
+            None => env::log_str(&format!(
+                "The raffle {:?} is no longer a number pick",
+                raffle_account_id.to_string()
+            )),
+        }
+    }
+
+    // Picks the number of a participant in a number pick raffle, a new pick replacing the
+    // previous one until the raffle ends. The storage of the pick is paid from the tokens locked
+    // by the participant.
+    pub fn pick_number(&mut self, raffle_id: String, number: u8) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "Sorry, no raffle is being conducted by {:?}",
+            raffle_account_id.to_string()
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+
+        let max_number = raffle_details
+            .pick_range
+            .unwrap_or_else(|| env::panic_str("This raffle isn't a number pick"));
+        assert!(
+            (1..=max_number).contains(&number),
+            "The number should be from 1 to {}",
+            max_number
+        );
+
+        assert!(
+            env::block_timestamp() < raffle_details.end,
+            "Numbers can only be picked before the raffle ends"
+        );
+
+        let account_id = env::predecessor_account_id();
+        assert!(
+            raffle_details.participants.get(&account_id).is_some(),
+            "You are not participating in this raffle"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        raffle_details.picks.insert(&account_id, &number);
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+        assert_storage_covered(
+            initial_storage_usage,
+            raffle_details.participants.get(&account_id).unwrap(),
+        );
+
+        env::log_str(&format!(
+            "{:?} picked the number {} in the raffle {:?}",
+            account_id.to_string(),
+            number,
+            raffle_account_id.to_string()
+        ));
+    }
+
+    pub fn get_number_pick(&self, raffle_id: String, account_id: String) -> Option<u8> {
+        let (_, raffle_details) = self.find_raffle(raffle_id)?;
+        raffle_details
+            .picks
+            .get(&AccountId::try_from(account_id).ok()?)
+    }
+
+    // Prize left by the number pick raffles of an organizer nobody won, added to the prize of
+    // its next raffle
+    pub fn get_rollover(&self, account_id: String) -> U128 {
+        U128(
+            AccountId::try_from(account_id)
+                .ok()
+                .and_then(|account_id| self.rollovers.get(&account_id))
+                .unwrap_or(0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_number_pick_roll_over() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_number_pick(alice_account_id().to_string(), Some(10));
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+        contract.pick_number(alice_account_id().to_string(), 7);
+        assert_eq!(
+            contract.get_number_pick(alice_account_id().to_string(), bob_account_id().to_string()),
+            Some(7)
+        );
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        let prize = raffle_details.prize;
+        contract.internal_settle_number_pick(&alice_account_id(), raffle_details, 3);
+        assert!(contract.raffles.get(&alice_account_id()).is_none());
+        assert_eq!(
+            contract.get_rollover(alice_account_id().to_string()),
+            U128(prize)
+        );
+
+        // the next raffle of the organizer carries the prize rolled over
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert!(contract.raffles.get(&alice_account_id()).unwrap().prize > prize * 2 - ONE_NEAR);
+        assert_eq!(
+            contract.get_rollover(alice_account_id().to_string()),
+            U128(0)
+        );
+    }
+
+    #[test]
+    fn check_number_pick_split() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_number_pick(alice_account_id().to_string(), Some(10));
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        for (account_id, number) in [
+            (bob_account_id(), 4),
+            (jacob_account_id(), 4),
+            (mike_account_id(), 5),
+        ] {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(2 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+            contract.pick_number(alice_account_id().to_string(), number);
+        }
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        contract.internal_settle_number_pick(&alice_account_id(), raffle_details, 4);
+        assert!(contract.raffles.get(&alice_account_id()).is_none());
+        assert_eq!(
+            contract.get_rollover(alice_account_id().to_string()),
+            U128(0)
+        );
+    }
+
+    #[test]
+    fn check_number_pick_leave_raffle() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_number_pick(alice_account_id().to_string(), Some(10));
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+        contract.pick_number(alice_account_id().to_string(), 7);
+
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        // the pick leaves the raffle along with the entry
+        contract.leave_raffle(alice_account_id().to_string());
+        assert!(contract
+            .get_number_pick(alice_account_id().to_string(), bob_account_id().to_string())
+            .is_none());
+    }
+}
@@ -0,0 +1,376 @@
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+
+// time the participants of a raffle have to rate its organizer after it is settled
+// (30 days, in ns)
+#[cfg(not(feature = "testnet"))]
+pub(crate) const RATING_PERIOD: Timestamp = 30 * 24 * NANOSECONDS_PER_HOUR;
+#[cfg(feature = "testnet")]
+pub(crate) const RATING_PERIOD: Timestamp = 24 * NANOSECONDS_PER_HOUR;
+
+// Rating that a participant of a settled raffle can still leave
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PendingRating {
+    pub(crate) organizer: AccountId,
+    pub(crate) expires_at: Timestamp,
+}
+
+// Track record of an organizer, updated each time one of their raffles ends
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct OrganizerProfile {
+    pub(crate) raffles_completed: u64,
+    pub(crate) cancellations: u64,
+    pub(crate) total_prizes_paid: Balance,
+    pub(crate) total_participants: u64,
+    pub(crate) ratings_count: u64,
+    pub(crate) ratings_sum: u64,
+    // distinct accounts that took part in their settled raffles, and those that took part in
+    // more than one
+    pub(crate) unique_participants: u64,
+    pub(crate) repeat_participants: u64,
+}
+
+// Audience of an organizer over their settled raffles
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OrganizerAnalyticsView {
+    pub(crate) unique_participants: u64,
+    pub(crate) repeat_participants: u64,
+    // share of the unique participants that came back, in basis points
+    pub(crate) repeat_rate_bps: u64,
+    // entries over all their settled raffles
+    pub(crate) total_participants: u64,
+}
+
+// JSON representation of an organizer's track record
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OrganizerProfileView {
+    pub(crate) raffles_completed: u64,
+    pub(crate) cancellations: u64,
+    pub(crate) total_prizes_paid: U128,
+    // rounded down, over the completed raffles
+    pub(crate) average_participants: u64,
+    // the average rating is `ratings_sum / ratings_count`
+    pub(crate) ratings_count: u64,
+    pub(crate) ratings_sum: u64,
+}
+
+impl RaffleDapp {
+    // Records a raffle of `organizer` that was finalized, paying `prize` to its winner
+    pub(crate) fn record_completed_raffle(
+        &mut self,
+        organizer: &AccountId,
+        prize: Balance,
+        participants: u64,
+    ) {
+        let mut profile = self.organizers.get(organizer).unwrap_or_default();
+        profile.raffles_completed += 1;
+        profile.total_prizes_paid += prize;
+        profile.total_participants += participants;
+        self.organizers.insert(organizer, &profile);
+    }
+
+    // Counts the participants of a settled raffle of `organizer` in their audience, each
+    // participant being counted once however many of their raffles they took part in
+    pub(crate) fn record_audience(&mut self, organizer: &AccountId, participants: Vec<AccountId>) {
+        let mut profile = self.organizers.get(organizer).unwrap_or_default();
+        for participant in participants {
+            let key = (organizer.clone(), participant);
+            let raffles = self.audiences.get(&key).unwrap_or(0) + 1;
+            self.audiences.insert(&key, &raffles);
+            match raffles {
+                1 => profile.unique_participants += 1,
+                2 => profile.repeat_participants += 1,
+                _ => {}
+            }
+        }
+        self.organizers.insert(organizer, &profile);
+    }
+
+    // Lets every participant of the raffle rate its organizer during `RATING_PERIOD`
+    pub(crate) fn open_ratings(
+        &mut self,
+        raffle_account_id: &AccountId,
+        organizer: &AccountId,
+        participants: Vec<AccountId>,
+    ) {
+        let pending_rating = PendingRating {
+            organizer: organizer.clone(),
+            expires_at: env::block_timestamp() + RATING_PERIOD,
+        };
+        for participant in participants {
+            self.pending_ratings
+                .insert(&(raffle_account_id.clone(), participant), &pending_rating);
+        }
+    }
+
+    // Records a raffle of `organizer` that ended without being drawn, because it was abandoned
+    // or swept after expiring
+    pub(crate) fn record_cancelled_raffle(&mut self, organizer: &AccountId) {
+        let mut profile = self.organizers.get(organizer).unwrap_or_default();
+        profile.cancellations += 1;
+        self.organizers.insert(organizer, &profile);
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Rates the organizer of the settled raffle `raffle_id` from 1 to 5. Only its participants
+    // can rate it, once each and within `RATING_PERIOD` of its settlement.
+    pub fn rate_organizer(&mut self, raffle_id: String, rating: u8) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            (1..=5).contains(&rating),
+            "The rating should be between 1 and 5"
+        );
+
+        let pending_rating = self
+            .pending_ratings
+            .remove(&(raffle_account_id.clone(), env::predecessor_account_id()))
+            .unwrap_or_else(|| {
+                env::panic_str("Only the participants of a settled raffle can rate it, once")
+            });
+
+        assert!(
+            env::block_timestamp() <= pending_rating.expires_at,
+            "The rating period of this raffle is over"
+        );
+
+        let mut profile = self
+            .organizers
+            .get(&pending_rating.organizer)
+            .unwrap_or_default();
+        profile.ratings_count += 1;
+        profile.ratings_sum += rating as u64;
+        self.organizers.insert(&pending_rating.organizer, &profile);
+
+        env::log_str(&format!(
+            "{:?} rated the raffle of {:?} {}/5",
+            env::predecessor_account_id().to_string(),
+            raffle_account_id.to_string(),
+            rating
+        ));
+    }
+
+    // Returns the track record of the organizer `account`, so participants can judge them
+    // before locking their tokens
+    pub fn get_organizer_profile(&self, account: String) -> OrganizerProfileView {
+        let profile = AccountId::try_from(account)
+            .ok()
+            .and_then(|account_id| self.organizers.get(&account_id))
+            .unwrap_or_default();
+
+        OrganizerProfileView {
+            raffles_completed: profile.raffles_completed,
+            cancellations: profile.cancellations,
+            total_prizes_paid: U128(profile.total_prizes_paid),
+            average_participants: profile
+                .total_participants
+                .checked_div(profile.raffles_completed)
+                .unwrap_or(0),
+            ratings_count: profile.ratings_count,
+            ratings_sum: profile.ratings_sum,
+        }
+    }
+
+    // Returns the audience of the organizer `account`: how many distinct accounts took part in
+    // their settled raffles and how many of them came back
+    pub fn get_organizer_analytics(&self, account: String) -> OrganizerAnalyticsView {
+        let profile = AccountId::try_from(account)
+            .ok()
+            .and_then(|account_id| self.organizers.get(&account_id))
+            .unwrap_or_default();
+
+        OrganizerAnalyticsView {
+            unique_participants: profile.unique_participants,
+            repeat_participants: profile.repeat_participants,
+            repeat_rate_bps: (profile.repeat_participants * 10_000)
+                .checked_div(profile.unique_participants)
+                .unwrap_or(0),
+            total_participants: profile.total_participants,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_organizer_profile() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        let prize = contract.raffles.get(&alice_account_id()).unwrap().prize;
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        for account_id in [bob_account_id(), jacob_account_id()] {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(2 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+        }
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        complete_draw(&mut context, &mut contract, alice_account_id());
+
+        let profile = contract.get_organizer_profile(alice_account_id().to_string());
+        assert_eq!(profile.raffles_completed, 1);
+        assert_eq!(profile.cancellations, 0);
+        assert_eq!(profile.total_prizes_paid, U128(prize));
+        assert_eq!(profile.average_participants, 2);
+
+        let profile = contract.get_organizer_profile(bob_account_id().to_string());
+        assert_eq!(profile.raffles_completed, 0);
+        assert_eq!(profile.average_participants, 0);
+    }
+
+    fn settle_raffle(context: &mut VMContextBuilder, contract: &mut RaffleDapp) {
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        complete_draw(context, contract, alice_account_id());
+    }
+
+    #[test]
+    fn check_rate_organizer() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        settle_raffle(&mut context, &mut contract);
+
+        context.predecessor_account_id(bob_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.rate_organizer(alice_account_id().to_string(), 4);
+
+        let profile = contract.get_organizer_profile(alice_account_id().to_string());
+        assert_eq!(profile.ratings_count, 1);
+        assert_eq!(profile.ratings_sum, 4);
+    }
+
+    #[test]
+    fn check_organizer_analytics() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        settle_raffle(&mut context, &mut contract);
+
+        let analytics = contract.get_organizer_analytics(alice_account_id().to_string());
+        assert_eq!(analytics.unique_participants, 1);
+        assert_eq!(analytics.repeat_participants, 0);
+
+        // bob comes back
+        context.block_timestamp(0);
+        settle_raffle(&mut context, &mut contract);
+
+        let analytics = contract.get_organizer_analytics(alice_account_id().to_string());
+        assert_eq!(analytics.unique_participants, 1);
+        assert_eq!(analytics.repeat_participants, 1);
+        assert_eq!(analytics.repeat_rate_bps, 10_000);
+        assert_eq!(analytics.total_participants, 2);
+    }
+
+    #[test]
+    fn check_rate_organizer_once() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        settle_raffle(&mut context, &mut contract);
+
+        context.predecessor_account_id(bob_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        assert!(contract
+            .pending_ratings
+            .get(&(alice_account_id(), jacob_account_id()))
+            .is_none());
+
+        contract.rate_organizer(alice_account_id().to_string(), 4);
+        assert!(contract
+            .pending_ratings
+            .get(&(alice_account_id(), bob_account_id()))
+            .is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "The rating period of this raffle is over")]
+    fn check_rate_organizer_expired() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        settle_raffle(&mut context, &mut contract);
+
+        context.block_timestamp(env::block_timestamp() + RATING_PERIOD + 1);
+        context.predecessor_account_id(bob_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.rate_organizer(alice_account_id().to_string(), 4);
+    }
+}
@@ -0,0 +1,2370 @@
+use crate::claims::CLAIM_KEY_ALLOWANCE;
+use crate::events::log_event;
+use crate::gates::assert_ungated;
+use crate::merkle::{allowlist_leaf, verify_proof};
+use crate::quiz::assert_no_quiz;
+use crate::raffle::{
+    RaffleDetails, RaffleStore, FLAG_FREE_ENTRY, FLAG_PRE_REGISTRATION, FLAG_PRIZE_CLAIM_KEYS,
+    FLAG_TICKET_RESALE, FLAG_TRANSFERABLE_TICKETS, FLAG_WAITLIST, FLAG_WITHDRAWALS,
+};
+use crate::*;
+use near_sdk::json_types::{Base58CryptoHash, U128};
+use near_sdk::{CryptoHash, PublicKey, StorageUsage};
+use serde_json::json;
+
+// Entries are refunded in full once removed, which releases their storage, so the locked
+// amount only needs to cover the storage they use in the meantime
+pub(crate) fn assert_storage_covered(initial_storage_usage: StorageUsage, locked_tokens: Balance) {
+    assert!(
+        locked_tokens >= storage_cost_since(initial_storage_usage),
+        "The locked amount doesn't cover the storage cost of the entry"
+    );
+}
+
+// minimum amount locked by a participant in each raffle entered
+#[cfg(not(feature = "testnet"))]
+pub(crate) const MIN_LOCKED_AMOUNT: Balance = ONE_NEAR;
+#[cfg(feature = "testnet")]
+pub(crate) const MIN_LOCKED_AMOUNT: Balance = ONE_NEAR / 100;
+
+// time after the end of a raffle from which its participants can reclaim their deposits
+// if it still hasn't been finalized (30 days, in ns)
+#[cfg(not(feature = "testnet"))]
+pub(crate) const RECLAIM_GRACE_PERIOD: u64 = 30 * 24 * NANOSECONDS_PER_HOUR;
+#[cfg(feature = "testnet")]
+pub(crate) const RECLAIM_GRACE_PERIOD: u64 = NANOSECONDS_PER_HOUR;
+
+// free entries an account can make per window unless the contract account sets another limit
+pub(crate) const DEFAULT_FREE_ENTRY_LIMIT: EntryRateLimit = EntryRateLimit {
+    max_entries: 10,
+    window: 24 * NANOSECONDS_PER_HOUR,
+};
+
+fn decode_proof(proof: Option<Vec<Base58CryptoHash>>) -> Vec<CryptoHash> {
+    proof
+        .unwrap_or_default()
+        .into_iter()
+        .map(CryptoHash::from)
+        .collect()
+}
+
+// Whether the Merkle proof shows that `account_id` is on the raffle's allowlist, or the raffle
+// has none or its priority window is over
+pub(crate) fn is_allowlisted(
+    raffle_details: &RaffleDetails,
+    account_id: &AccountId,
+    proof: &[CryptoHash],
+) -> bool {
+    let public = raffle_details
+        .public_from()
+        .map(|public_from| env::block_timestamp() >= public_from)
+        .unwrap_or(false);
+    if public {
+        return true;
+    }
+
+    raffle_details
+        .allowlist_root
+        .as_ref()
+        .is_none_or(|allowlist_root| {
+            verify_proof(allowlist_root, allowlist_leaf(account_id), proof)
+        })
+}
+
+fn assert_allowlisted(
+    raffle_details: &RaffleDetails,
+    account_id: &AccountId,
+    proof: &[CryptoHash],
+) {
+    assert!(
+        is_allowlisted(raffle_details, account_id, proof),
+        "You are not on the raffle's allowlist"
+    );
+}
+
+// Checks that `account_id` isn't the proposed owner of the raffle, who could otherwise accept
+// the raffle while holding one of its entries
+fn assert_not_pending_owner(raffle_details: &RaffleDetails, account_id: &AccountId) {
+    assert!(
+        raffle_details.pending_owner.as_ref() != Some(account_id),
+        "The proposed owner of the raffle cannot participate in it"
+    );
+}
+
+// Raffles entered together through `participate_bundle`
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Bundle {
+    pub(crate) raffle_ids: Vec<AccountId>,
+    pub(crate) per_raffle_amount: Balance,
+}
+
+// Maximum number of entries an account can make per `window`, in ns
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct EntryRateLimit {
+    pub(crate) max_entries: u32,
+    pub(crate) window: Timestamp,
+}
+
+// Entries made by an account during its current rate limiting window
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct EntryWindow {
+    pub(crate) start: Timestamp,
+    pub(crate) entries: u32,
+}
+
+// Current window of `rate_limit` of `account_id`, a new one if its last window is over
+fn current_entry_window(
+    entry_windows: &LookupMap<AccountId, EntryWindow>,
+    rate_limit: &EntryRateLimit,
+    account_id: &AccountId,
+) -> EntryWindow {
+    let current_timestamp = env::block_timestamp();
+    entry_windows
+        .get(account_id)
+        .filter(|entry_window| current_timestamp < entry_window.start + rate_limit.window)
+        .unwrap_or(EntryWindow {
+            start: current_timestamp,
+            entries: 0,
+        })
+}
+
+// Whether `account_id` can make another entry in its current window of `rate_limit`
+pub(crate) fn has_windowed_entry_left(
+    entry_windows: &LookupMap<AccountId, EntryWindow>,
+    rate_limit: &EntryRateLimit,
+    account_id: &AccountId,
+) -> bool {
+    current_entry_window(entry_windows, rate_limit, account_id).entries < rate_limit.max_entries
+}
+
+// Counts an entry of `account_id` in its current window of `rate_limit`, returning false
+// without counting it once the window is full
+fn record_windowed_entry(
+    entry_windows: &mut LookupMap<AccountId, EntryWindow>,
+    rate_limit: &EntryRateLimit,
+    account_id: &AccountId,
+) -> bool {
+    let mut entry_window = current_entry_window(entry_windows, rate_limit, account_id);
+    if entry_window.entries >= rate_limit.max_entries {
+        return false;
+    }
+
+    entry_window.entries += 1;
+    entry_windows.insert(account_id, &entry_window);
+    true
+}
+
+pub(crate) trait InternalParticipation {
+    fn internal_participate(
+        &mut self,
+        raffle_account_id: &AccountId,
+        account_id: &AccountId,
+        locked_tokens: Balance,
+        proof: &[CryptoHash],
+    );
+    fn internal_check_entry(
+        &mut self,
+        raffle_account_id: &AccountId,
+        account_id: &AccountId,
+        proof: &[CryptoHash],
+    );
+    fn internal_enter(
+        &mut self,
+        raffle_account_id: &AccountId,
+        account_id: &AccountId,
+        locked_tokens: Balance,
+    );
+    fn internal_check_rate_limit(&mut self, account_id: &AccountId);
+    fn internal_check_free_entry_limit(&mut self, account_id: &AccountId);
+    fn internal_debit_sponsor_pool(
+        &mut self,
+        raffle_account_id: &AccountId,
+        initial_storage_usage: StorageUsage,
+    );
+    fn active_bundle_raffles(&self, account_id: &AccountId, bundle: &Bundle) -> Vec<AccountId>;
+}
+
+impl InternalParticipation for RaffleDapp {
+    // Enters `account_id` into the given raffle with `locked_tokens` locked
+    fn internal_participate(
+        &mut self,
+        raffle_account_id: &AccountId,
+        account_id: &AccountId,
+        locked_tokens: Balance,
+        proof: &[CryptoHash],
+    ) {
+        if let Some(raffle_details) = self.raffles.get(raffle_account_id) {
+            assert_ungated(&raffle_details);
+        }
+        self.internal_check_entry(raffle_account_id, account_id, proof);
+        self.internal_enter(raffle_account_id, account_id, locked_tokens);
+    }
+
+    // Checks that `account_id` can enter the given raffle, counting the entry against the
+    // contract's rate limit
+    fn internal_check_entry(
+        &mut self,
+        raffle_account_id: &AccountId,
+        account_id: &AccountId,
+        proof: &[CryptoHash],
+    ) {
+        assert_ne!(
+            account_id,
+            &env::current_account_id(),
+            "The contract account cannot participate in raffles for security reasons"
+        );
+
+        assert!(
+            self.raffles.get(raffle_account_id).is_some(),
+            "Sorry, no raffle is being conducted by {:?}",
+            raffle_account_id.to_string()
+        );
+
+        let raffle_details = self.raffles.get(raffle_account_id).unwrap();
+
+        assert_ne!(
+            account_id, &raffle_details.owner,
+            "You cannot participate in your own raffle"
+        );
+        assert_not_pending_owner(&raffle_details, account_id);
+
+        assert!(
+            raffle_details.participants.get(account_id).is_none(),
+            "You have already participated in this raffle"
+        );
+
+        assert_allowlisted(&raffle_details, account_id, proof);
+
+        assert!(
+            raffle_details.participants.len() < raffle_details.max_participants,
+            "Sorry, the raffle's maximum participants limit reached, you can join its waitlist instead"
+        );
+
+        // Raffles open to pre-registration accept entries before their start, the entries
+        // becoming active once it starts
+        let current_timestamp = env::block_timestamp();
+        let pre_registration = raffle_details.has_flag(FLAG_PRE_REGISTRATION);
+        assert!(
+            (current_timestamp > raffle_details.start || pre_registration)
+                && current_timestamp < raffle_details.end,
+            "The raffle has either not started yet or has finished already"
+        );
+
+        assert!(
+            current_timestamp < raffle_details.entries_close(),
+            "Entries to this raffle are closed"
+        );
+
+        self.internal_check_rate_limit(account_id);
+    }
+
+    // Records the entry of `account_id`, once `internal_check_entry` accepted it, and rolls the
+    // raffle's instant wins for it. The record of the roll is paid along with the entry.
+    fn internal_enter(
+        &mut self,
+        raffle_account_id: &AccountId,
+        account_id: &AccountId,
+        locked_tokens: Balance,
+    ) {
+        let mut raffle_details = self.raffles.get(raffle_account_id).unwrap();
+        raffle_details.add_entry(account_id, locked_tokens);
+        raffle_details.record_entry();
+
+        self.internal_save_raffle(raffle_account_id, &raffle_details);
+        self.internal_record_jackpot_entrant(account_id);
+        self.internal_resolve_instant_win(raffle_account_id, account_id);
+
+        env::log_str(&format!(
+            "{:?} has sucessfully participated in the raffle of {:?} with {:?} NEAR token(s) locked",
+            account_id.to_string(),
+            raffle_account_id.to_string(),
+            locked_tokens / ONE_NEAR
+        ));
+    }
+
+    // Counts an entry of `account_id` against the contract's rate limit, if there is one
+    fn internal_check_rate_limit(&mut self, account_id: &AccountId) {
+        let rate_limit = match self.entry_rate_limit.as_ref() {
+            Some(rate_limit) => rate_limit,
+            None => return,
+        };
+
+        assert!(
+            record_windowed_entry(&mut self.entry_windows, rate_limit, account_id),
+            "Too many entries, you can only enter {} raffle(s) every {} ms",
+            rate_limit.max_entries,
+            rate_limit.window / TO_FROM_NANOSECONDS
+        );
+    }
+
+    // Counts an entry of `account_id` locking no tokens against the limit of free entries, which
+    // keeps a single account from draining the sponsorship allowances of every free raffle
+    fn internal_check_free_entry_limit(&mut self, account_id: &AccountId) {
+        let rate_limit = match self.free_entry_limit.as_ref() {
+            Some(rate_limit) => rate_limit,
+            None => return,
+        };
+
+        assert!(
+            record_windowed_entry(&mut self.free_entry_windows, rate_limit, account_id),
+            "Too many free entries, you can only enter {} free raffle(s) every {} ms",
+            rate_limit.max_entries,
+            rate_limit.window / TO_FROM_NANOSECONDS
+        );
+    }
+
+    // Returns the raffles of the bundle in which `account_id` still holds an entry
+    fn active_bundle_raffles(&self, account_id: &AccountId, bundle: &Bundle) -> Vec<AccountId> {
+        bundle
+            .raffle_ids
+            .iter()
+            .filter(|raffle_account_id| {
+                self.raffles
+                    .get(raffle_account_id)
+                    .map(|raffle_details| raffle_details.participants.get(account_id).is_some())
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Pays the storage of an entry made without locking tokens from the raffle's allowance of
+    // sponsored entries
+    fn internal_debit_sponsor_pool(
+        &mut self,
+        raffle_account_id: &AccountId,
+        initial_storage_usage: StorageUsage,
+    ) {
+        let storage_cost = storage_cost_since(initial_storage_usage);
+
+        let mut raffle_details = self.raffles.get(raffle_account_id).unwrap();
+        assert!(
+            raffle_details.sponsor_pool >= storage_cost,
+            "The raffle's sponsorship allowance is exhausted"
+        );
+        raffle_details.sponsor_pool -= storage_cost;
+        self.internal_save_raffle(raffle_account_id, &raffle_details);
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Entries are recorded under the predecessor rather than the signer, so a participant can
+    // send a NEP-366 delegate action through a relayer paying the gas and still be the one entered.
+    // A promo code of the raffle enters it without locking any tokens, and `answer` answers the
+    // question of the raffle if it has one. The entries of free-entry raffles don't lock any
+    // tokens either, they count against the limit of free entries of the account instead.
+    #[payable]
+    pub fn participate(
+        &mut self,
+        raffle_id: String,
+        proof: Option<Vec<Base58CryptoHash>>,
+        promo_code: Option<String>,
+        answer: Option<String>,
+    ) {
+        self.metrics.participate += 1;
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+        if let Some(promo_code) = promo_code {
+            self.internal_check_answer(&raffle_account_id, answer);
+            self.internal_redeem_promo_code(&raffle_account_id, promo_code, &decode_proof(proof));
+            return;
+        }
+
+        if self.internal_trigger_finalization(&raffle_account_id) {
+            return;
+        }
+        self.internal_check_answer(&raffle_account_id, answer);
+
+        let free_entry = self
+            .raffles
+            .get(&raffle_account_id)
+            .map(|raffle_details| raffle_details.has_flag(FLAG_FREE_ENTRY))
+            .unwrap_or(false);
+        if free_entry {
+            assert_eq!(
+                env::attached_deposit(),
+                0,
+                "Entries to this raffle are free, no tokens should be attached"
+            );
+            self.internal_check_free_entry_limit(&env::predecessor_account_id());
+        } else {
+            // Users can participate in the raffle by locking at least `MIN_LOCKED_AMOUNT` to prevent spam or duplicate entries to some extent.
+            // The participant's locked NEAR tokens plays no role while deciding the winner to conduct an unbiased raffle.
+            assert!(
+                env::attached_deposit() >= MIN_LOCKED_AMOUNT,
+                "The locked amount should be at least {} yoctoNEAR",
+                MIN_LOCKED_AMOUNT
+            );
+        }
+
+        // Entries to token-gated raffles are completed once the token's contract answered
+        let entry_gate = self
+            .raffles
+            .get(&raffle_account_id)
+            .and_then(|raffle_details| raffle_details.entry_gate);
+        if let Some(entry_gate) = entry_gate {
+            self.internal_check_entry(
+                &raffle_account_id,
+                &env::predecessor_account_id(),
+                &decode_proof(proof),
+            );
+            self.internal_check_entry_gate(
+                &raffle_account_id,
+                &entry_gate,
+                &env::predecessor_account_id(),
+                env::attached_deposit(),
+            );
+            return;
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        self.internal_participate(
+            &raffle_account_id,
+            &env::predecessor_account_id(),
+            env::attached_deposit(),
+            &decode_proof(proof),
+        );
+        if free_entry {
+            self.internal_debit_sponsor_pool(&raffle_account_id, initial_storage_usage);
+        } else {
+            assert_storage_covered(initial_storage_usage, env::attached_deposit());
+        }
+    }
+
+    // Enters the raffle without locking any tokens, the storage of the entry being paid from
+    // the allowance funded by the organizer. It doesn't require a deposit, so it can be called
+    // with function call access keys such as the ones of Keypom drops and trial accounts.
+    pub fn participate_sponsored(
+        &mut self,
+        raffle_id: String,
+        proof: Option<Vec<Base58CryptoHash>>,
+    ) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+        if self.internal_trigger_finalization(&raffle_account_id) {
+            return;
+        }
+        if let Some(raffle_details) = self.raffles.get(&raffle_account_id) {
+            assert_no_quiz(&raffle_details);
+        }
+        self.internal_check_free_entry_limit(&env::predecessor_account_id());
+
+        let initial_storage_usage = env::storage_usage();
+        self.internal_participate(
+            &raffle_account_id,
+            &env::predecessor_account_id(),
+            0,
+            &decode_proof(proof),
+        );
+        self.internal_debit_sponsor_pool(&raffle_account_id, initial_storage_usage);
+    }
+
+    // Enters several raffles at once, splitting the attached deposit evenly between them.
+    // The entry is all-or-nothing: if any of the raffles rejects it, none of them is entered.
+    // Free-entry raffles don't lock tokens, so they can't be part of a bundle.
+    #[payable]
+    pub fn participate_bundle(&mut self, raffle_ids: Vec<String>, per_raffle_amount: U128) {
+        assert!(
+            raffle_ids.len() > 1,
+            "A bundle should contain at least two raffles"
+        );
+
+        assert!(
+            per_raffle_amount.0 >= MIN_LOCKED_AMOUNT,
+            "The locked amount should be at least {} yoctoNEAR",
+            MIN_LOCKED_AMOUNT
+        );
+
+        assert_eq!(
+            env::attached_deposit(),
+            per_raffle_amount.0 * raffle_ids.len() as u128,
+            "The attached deposit should be equal to the amount locked in each raffle times the number of raffles"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        let raffle_account_ids: Vec<AccountId> = raffle_ids
+            .into_iter()
+            .map(|raffle_id| AccountId::try_from(raffle_id).unwrap())
+            .collect();
+
+        for (index, raffle_account_id) in raffle_account_ids.iter().enumerate() {
+            assert!(
+                !raffle_account_ids[..index].contains(raffle_account_id),
+                "The raffle {:?} is included more than once in the bundle",
+                raffle_account_id.to_string()
+            );
+            if let Some(raffle_details) = self.raffles.get(raffle_account_id) {
+                assert_no_quiz(&raffle_details);
+                assert!(
+                    !raffle_details.has_flag(FLAG_FREE_ENTRY),
+                    "Entries to the raffle {:?} are free, it can only be entered with `participate` or `participate_sponsored`",
+                    raffle_account_id.to_string()
+                );
+            }
+            self.internal_participate(
+                raffle_account_id,
+                &env::predecessor_account_id(),
+                per_raffle_amount.0,
+                &[],
+            );
+        }
+
+        // Bundles whose raffles have all been settled or left are dropped along the way
+        let mut bundles: Vec<Bundle> = self
+            .bundles
+            .get(&env::predecessor_account_id())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|bundle| {
+                !self
+                    .active_bundle_raffles(&env::predecessor_account_id(), bundle)
+                    .is_empty()
+            })
+            .collect();
+        bundles.push(Bundle {
+            raffle_ids: raffle_account_ids,
+            per_raffle_amount: per_raffle_amount.0,
+        });
+        self.bundles
+            .insert(&env::predecessor_account_id(), &bundles);
+
+        assert_storage_covered(initial_storage_usage, env::attached_deposit());
+    }
+
+    // Once a raffle is full, users can queue up for a slot by escrowing the same deposit
+    // they would lock as participants. Whenever a participant leaves or is removed, the
+    // earliest waitlisted account is promoted to a participant. Like with `participate`,
+    // `answer` answers the question of the raffle if it has one.
+    #[payable]
+    pub fn join_waitlist(
+        &mut self,
+        raffle_id: String,
+        proof: Option<Vec<Base58CryptoHash>>,
+        answer: Option<String>,
+    ) {
+        assert!(
+            env::attached_deposit() >= MIN_LOCKED_AMOUNT,
+            "The locked amount should be at least {} yoctoNEAR",
+            MIN_LOCKED_AMOUNT
+        );
+
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert_ne!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "The contract account cannot participate in raffles for security reasons"
+        );
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "Sorry, no raffle is being conducted by {:?}",
+            raffle_account_id.to_string()
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+
+        assert!(
+            raffle_details.has_flag(FLAG_WAITLIST),
+            "This raffle doesn't have a waitlist"
+        );
+
+        assert_ungated(&raffle_details);
+
+        assert_allowlisted(
+            &raffle_details,
+            &env::predecessor_account_id(),
+            &decode_proof(proof),
+        );
+
+        self.internal_check_answer(&raffle_account_id, answer);
+
+        assert_ne!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "You cannot participate in your own raffle"
+        );
+        assert_not_pending_owner(&raffle_details, &env::predecessor_account_id());
+
+        assert!(
+            raffle_details
+                .participants
+                .get(&env::predecessor_account_id())
+                .is_none(),
+            "You have already participated in this raffle"
+        );
+
+        assert!(
+            raffle_details
+                .waitlist_positions
+                .get(&env::predecessor_account_id())
+                .is_none(),
+            "You are already on the waitlist of this raffle"
+        );
+
+        assert!(
+            raffle_details.participants.len() >= raffle_details.max_participants,
+            "The raffle is not full yet, you can participate directly"
+        );
+
+        let current_timestamp = env::block_timestamp();
+        assert!(
+            current_timestamp > raffle_details.start && current_timestamp < raffle_details.end,
+            "The raffle has either not started yet or has finished already"
+        );
+
+        assert!(
+            current_timestamp < raffle_details.entries_close(),
+            "Entries to this raffle are closed"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        let position = raffle_details.next_waitlist_position;
+        raffle_details.waitlist.insert(
+            &position,
+            &(env::predecessor_account_id(), env::attached_deposit()),
+        );
+        raffle_details
+            .waitlist_positions
+            .insert(&env::predecessor_account_id(), &position);
+        raffle_details.next_waitlist_position += 1;
+
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+        assert_storage_covered(initial_storage_usage, env::attached_deposit());
+
+        env::log_str(&format!(
+            "{:?} has joined the waitlist of the raffle of {:?} at position {:?}",
+            env::predecessor_account_id().to_string(),
+            raffle_account_id.to_string(),
+            raffle_details.waitlist.len()
+        ));
+    }
+
+    // Lets a participant or a waitlisted account leave the raffle before it ends and get
+    // their locked tokens back.
+    pub fn leave_raffle(&mut self, raffle_id: String) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "Sorry, no raffle is being conducted by {:?}",
+            raffle_account_id.to_string()
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+
+        assert!(
+            env::block_timestamp() < raffle_details.end,
+            "You can only leave the raffle before it ends"
+        );
+
+        assert!(
+            raffle_details.has_flag(FLAG_WITHDRAWALS),
+            "Withdrawals are not enabled for this raffle"
+        );
+
+        // Participants leaving pay the withdrawal penalty of the raffle, which is added to its
+        // prize, while the accounts waiting for a slot leave for free
+        let account_id = env::predecessor_account_id();
+        let mut promoted = None;
+        let (locked_tokens, penalty) =
+            if let Some(locked_tokens) = raffle_details.remove_entry(&account_id) {
+                raffle_details.record_exit();
+                raffle_details.ticket_listings.remove(&account_id);
+                raffle_details.return_pooled_nft(&account_id, self.gas_config.nft_transfer);
+                promoted = raffle_details.promote_from_waitlist(&raffle_account_id);
+                let penalty = locked_tokens * raffle_details.withdrawal_penalty as u128 / 100;
+                raffle_details.prize += penalty;
+                (locked_tokens, penalty)
+            } else if let Some(position) = raffle_details.waitlist_positions.remove(&account_id) {
+                (raffle_details.waitlist.remove(&position).unwrap().1, 0)
+            } else {
+                env::panic_str("You are not participating in this raffle")
+            };
+
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+        if let Some(promoted) = promoted {
+            self.internal_resolve_instant_win(&raffle_account_id, &promoted);
+        }
+
+        if locked_tokens > penalty {
+            Promise::new(account_id.clone()).transfer(locked_tokens - penalty);
+        }
+
+        env::log_str(&format!(
+            "{:?} has left the raffle of {:?} and {:?} NEAR token(s) were refunded",
+            account_id.to_string(),
+            raffle_account_id.to_string(),
+            (locked_tokens - penalty) / ONE_NEAR
+        ));
+        if penalty > 0 {
+            log_event(
+                "withdrawal_penalty_paid",
+                json!({
+                    "raffle_id": raffle_account_id.to_string(),
+                    "account_id": account_id.to_string(),
+                    "penalty": U128(penalty),
+                }),
+            );
+        }
+    }
+
+    // Returns the caller's locked tokens from a raffle that ended more than `RECLAIM_GRACE_PERIOD`
+    // ago without being finalized. The raffle is then marked as abandoned, so no winner is drawn.
+    pub fn reclaim_deposit(&mut self, raffle_id: String) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "Sorry, no raffle is being conducted by {:?}",
+            raffle_account_id.to_string()
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+
+        assert!(
+            env::block_timestamp() > raffle_details.end + RECLAIM_GRACE_PERIOD,
+            "Deposits can only be reclaimed {} ms after the raffle ends",
+            RECLAIM_GRACE_PERIOD / TO_FROM_NANOSECONDS
+        );
+
+        let account_id = env::predecessor_account_id();
+        let locked_tokens = if let Some(locked_tokens) = raffle_details.remove_entry(&account_id) {
+            raffle_details.ticket_listings.remove(&account_id);
+            raffle_details.return_pooled_nft(&account_id, self.gas_config.nft_transfer);
+            locked_tokens
+        } else if let Some(position) = raffle_details.waitlist_positions.remove(&account_id) {
+            raffle_details.waitlist.remove(&position).unwrap().1
+        } else {
+            env::panic_str("You are not participating in this raffle")
+        };
+
+        raffle_details.abandoned = true;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        Promise::new(account_id.clone()).transfer(locked_tokens);
+
+        env::log_str(&format!(
+            "{:?} has reclaimed {:?} NEAR token(s) from the abandoned raffle of {:?}",
+            account_id.to_string(),
+            locked_tokens / ONE_NEAR,
+            raffle_account_id.to_string()
+        ));
+    }
+
+    // Registers the public key of a key pair that can claim the prize if the caller wins, e.g. to
+    // hand it over to someone without a funded account. The prize then stays escrowed for the
+    // key until it is claimed with `claim_prize`.
+    pub fn set_claim_key(&mut self, raffle_id: String, public_key: PublicKey) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "Sorry, no raffle is being conducted by {:?}",
+            raffle_account_id.to_string()
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+
+        assert!(
+            raffle_details.has_flag(FLAG_PRIZE_CLAIM_KEYS),
+            "Prize claim keys are not enabled for this raffle"
+        );
+
+        assert!(
+            env::block_timestamp() < raffle_details.end,
+            "Claim keys can only be set before the raffle ends"
+        );
+
+        let account_id = env::predecessor_account_id();
+        assert!(
+            raffle_details.participants.get(&account_id).is_some(),
+            "You are not participating in this raffle"
+        );
+
+        assert!(
+            raffle_details.prize > CLAIM_KEY_ALLOWANCE,
+            "The prize doesn't cover the allowance of a claim key"
+        );
+        assert!(
+            raffle_details.foreign_payouts.get(&account_id).is_none(),
+            "The prize is paid on the foreign chain you elected"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        raffle_details.claim_keys.insert(&account_id, &public_key);
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+        assert_storage_covered(
+            initial_storage_usage,
+            raffle_details.participants.get(&account_id).unwrap(),
+        );
+
+        env::log_str(&format!(
+            "{:?} can claim the prize of the raffle {:?} with their claim key",
+            account_id.to_string(),
+            raffle_account_id.to_string()
+        ));
+    }
+
+    // Lets a participant hand their entry over to another account before the raffle ends.
+    // The locked tokens go along with the ticket, so they are refunded to the new holder. The
+    // receiver of a ticket of an allowlisted raffle has to be on the allowlist, `proof` being the
+    // proof of its account.
+    pub fn transfer_ticket(
+        &mut self,
+        raffle_id: String,
+        to: String,
+        proof: Option<Vec<Base58CryptoHash>>,
+    ) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+        let receiver_id: AccountId = AccountId::try_from(to).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "Sorry, no raffle is being conducted by {:?}",
+            raffle_account_id.to_string()
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+
+        assert!(
+            env::block_timestamp() < raffle_details.end,
+            "Tickets can only be transferred before the raffle ends"
+        );
+
+        assert!(
+            raffle_details.has_flag(FLAG_TRANSFERABLE_TICKETS),
+            "Ticket transfers are not enabled for this raffle"
+        );
+
+        assert_ungated(&raffle_details);
+
+        // The receiver of the ticket never answered the question of the raffle
+        assert_no_quiz(&raffle_details);
+
+        assert!(
+            receiver_id != env::current_account_id() && receiver_id != raffle_details.owner,
+            "The ticket cannot be transferred to the raffle's owner or the contract account"
+        );
+        assert_not_pending_owner(&raffle_details, &receiver_id);
+
+        assert!(
+            raffle_details.participants.get(&receiver_id).is_none()
+                && raffle_details
+                    .waitlist_positions
+                    .get(&receiver_id)
+                    .is_none(),
+            "The receiver is already participating in this raffle"
+        );
+
+        assert!(
+            is_allowlisted(&raffle_details, &receiver_id, &decode_proof(proof)),
+            "The receiver is not on the raffle's allowlist"
+        );
+
+        let sender_id = env::predecessor_account_id();
+        if !raffle_details.move_entry(&sender_id, &receiver_id) {
+            env::panic_str("You are not participating in this raffle");
+        }
+        raffle_details.ticket_listings.remove(&sender_id);
+
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        log_event(
+            "ticket_transferred",
+            json!({
+                "raffle_id": raffle_account_id.to_string(),
+                "old_owner_id": sender_id.to_string(),
+                "new_owner_id": receiver_id.to_string(),
+            }),
+        );
+    }
+
+    // Puts the caller's ticket on sale for `price` yoctoNEAR. Like with `transfer_ticket`,
+    // the locked tokens go along with the ticket, so the price should account for them.
+    pub fn list_ticket(&mut self, raffle_id: String, price: U128) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "Sorry, no raffle is being conducted by {:?}",
+            raffle_account_id.to_string()
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+
+        assert!(
+            env::block_timestamp() < raffle_details.end,
+            "Tickets can only be sold before the raffle ends"
+        );
+
+        assert!(
+            raffle_details.has_flag(FLAG_TICKET_RESALE),
+            "Ticket resale is not enabled for this raffle"
+        );
+
+        assert!(
+            raffle_details
+                .participants
+                .get(&env::predecessor_account_id())
+                .is_some(),
+            "You are not participating in this raffle"
+        );
+
+        assert!(price.0 > 0, "The ticket's price should be greater than 0");
+
+        raffle_details
+            .ticket_listings
+            .insert(&env::predecessor_account_id(), &price.0);
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        log_event(
+            "ticket_listed",
+            json!({
+                "raffle_id": raffle_account_id.to_string(),
+                "account_id": env::predecessor_account_id().to_string(),
+                "price": price,
+            }),
+        );
+    }
+
+    pub fn delist_ticket(&mut self, raffle_id: String) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "Sorry, no raffle is being conducted by {:?}",
+            raffle_account_id.to_string()
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+
+        assert!(
+            raffle_details
+                .ticket_listings
+                .remove(&env::predecessor_account_id())
+                .is_some(),
+            "Your ticket is not on sale"
+        );
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        log_event(
+            "ticket_delisted",
+            json!({
+                "raffle_id": raffle_account_id.to_string(),
+                "account_id": env::predecessor_account_id().to_string(),
+            }),
+        );
+    }
+
+    // Buys the ticket listed by `seller`. The attached deposit should cover the asking price,
+    // the owner's royalty is taken from it and the rest is paid to the seller. Any excess is
+    // refunded to the buyer. Buyers of tickets of an allowlisted raffle supply the proof of
+    // their account, and the buyers of tickets of a raffle with a question answer it.
+    #[payable]
+    pub fn buy_ticket(
+        &mut self,
+        raffle_id: String,
+        seller: String,
+        proof: Option<Vec<Base58CryptoHash>>,
+        answer: Option<String>,
+    ) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+        let seller_id: AccountId = AccountId::try_from(seller).unwrap();
+        let buyer_id = env::predecessor_account_id();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "Sorry, no raffle is being conducted by {:?}",
+            raffle_account_id.to_string()
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+
+        assert!(
+            env::block_timestamp() < raffle_details.end,
+            "Tickets can only be sold before the raffle ends"
+        );
+
+        assert!(
+            raffle_details.has_flag(FLAG_TICKET_RESALE),
+            "Ticket resale is not enabled for this raffle"
+        );
+
+        assert_ungated(&raffle_details);
+
+        assert!(
+            buyer_id != env::current_account_id() && buyer_id != raffle_details.owner,
+            "The raffle's owner or the contract account cannot buy tickets"
+        );
+        assert_not_pending_owner(&raffle_details, &buyer_id);
+
+        assert!(
+            raffle_details.participants.get(&buyer_id).is_none()
+                && raffle_details.waitlist_positions.get(&buyer_id).is_none(),
+            "You are already participating in this raffle"
+        );
+
+        assert_allowlisted(&raffle_details, &buyer_id, &decode_proof(proof));
+        self.internal_check_answer(&raffle_account_id, answer);
+
+        let price = raffle_details
+            .ticket_listings
+            .remove(&seller_id)
+            .unwrap_or_else(|| env::panic_str("This ticket is not on sale"));
+
+        assert!(
+            env::attached_deposit() >= price,
+            "The attached deposit should cover the ticket's price"
+        );
+
+        raffle_details.move_entry(&seller_id, &buyer_id);
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        let royalty = price * raffle_details.resale_royalty as u128 / 100;
+        if royalty > 0 {
+            Promise::new(raffle_details.owner).transfer(royalty);
+        }
+        Promise::new(seller_id.clone()).transfer(price - royalty);
+        if env::attached_deposit() > price {
+            Promise::new(buyer_id.clone()).transfer(env::attached_deposit() - price);
+        }
+
+        log_event(
+            "ticket_sold",
+            json!({
+                "raffle_id": raffle_account_id.to_string(),
+                "old_owner_id": seller_id.to_string(),
+                "new_owner_id": buyer_id.to_string(),
+                "price": U128(price),
+                "royalty": U128(royalty),
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instant_wins::InstantWinTier;
+    use crate::merkle::hash_pair;
+    use crate::raffle::DEFAULT_RAFFLE_FLAGS;
+    use crate::test_utils::*;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_participate() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705130),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Sorry, the raffle's maximum participants limit reached, you can join its waitlist instead"
+    )]
+    fn check_participate_full_raffle() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            Some(1),
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.predecessor_account_id(jacob_account_id());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+    }
+
+    #[test]
+    fn check_pre_registration() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            Some(FLAG_WITHDRAWALS | FLAG_PRE_REGISTRATION),
+            None,
+            None,
+        );
+
+        // bob and jacob commit their entry before the start, and jacob changes his mind
+        context.block_timestamp(1644353705100 * TO_FROM_NANOSECONDS);
+        context.attached_deposit(2 * ONE_NEAR);
+        for account_id in [bob_account_id(), jacob_account_id()] {
+            context.predecessor_account_id(account_id);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+        }
+        contract.leave_raffle(alice_account_id().to_string());
+
+        // entries made once the raffle started aren't pre-registrations
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(mike_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(raffle_details.participants.len(), 2);
+        assert_eq!(raffle_details.pre_registered, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "The raffle has either not started yet or has finished already")]
+    fn check_participate_before_start() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705100 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+    }
+
+    #[test]
+    fn check_waitlist_promotion() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            Some(1),
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(3 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.join_waitlist(alice_account_id().to_string(), None, None);
+
+        context.predecessor_account_id(mike_account_id());
+        testing_env!(context.build());
+
+        contract.join_waitlist(alice_account_id().to_string(), None, None);
+
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.leave_raffle(alice_account_id().to_string());
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert!(raffle_details.participants.get(&bob_account_id()).is_none());
+        assert_eq!(
+            raffle_details.participants.get(&jacob_account_id()),
+            Some(3 * ONE_NEAR)
+        );
+        assert_eq!(raffle_details.waitlist.len(), 1);
+        assert!(raffle_details
+            .waitlist_positions
+            .get(&mike_account_id())
+            .is_some());
+    }
+
+    #[test]
+    fn check_withdrawal_penalty() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        let prize = contract.raffles.get(&alice_account_id()).unwrap().prize;
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.set_withdrawal_penalty(alice_account_id().to_string(), 5);
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.leave_raffle(alice_account_id().to_string());
+
+        // 5% of the 2 NEAR locked by bob are added to the prize
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert!(raffle_details.participants.get(&bob_account_id()).is_none());
+        assert_eq!(raffle_details.prize, prize + ONE_NEAR / 10);
+        assert_eq!(contract.prize_liabilities, prize + ONE_NEAR / 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "The raffle is not full yet, you can participate directly")]
+    fn check_join_waitlist_not_full() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            Some(2),
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.join_waitlist(alice_account_id().to_string(), None, None);
+    }
+
+    #[test]
+    fn check_transfer_ticket() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.transfer_ticket(
+            alice_account_id().to_string(),
+            jacob_account_id().to_string(),
+            None,
+        );
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert!(raffle_details.participants.get(&bob_account_id()).is_none());
+        assert_eq!(
+            raffle_details.participants.get(&jacob_account_id()),
+            Some(2 * ONE_NEAR)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The receiver is already participating in this raffle")]
+    fn check_transfer_ticket_to_participant() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.predecessor_account_id(jacob_account_id());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.transfer_ticket(
+            alice_account_id().to_string(),
+            jacob_account_id().to_string(),
+            None,
+        );
+    }
+
+    #[test]
+    fn check_buy_ticket() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_resale_royalty(alice_account_id().to_string(), 10);
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.list_ticket(alice_account_id().to_string(), U128(3 * ONE_NEAR));
+        assert_eq!(
+            contract
+                .get_ticket_listings(alice_account_id().to_string(), None, None)
+                .unwrap()
+                .items,
+            vec![(bob_account_id(), U128(3 * ONE_NEAR))]
+        );
+
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(3 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.buy_ticket(
+            alice_account_id().to_string(),
+            bob_account_id().to_string(),
+            None,
+            None,
+        );
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert!(raffle_details.participants.get(&bob_account_id()).is_none());
+        assert_eq!(
+            raffle_details.participants.get(&jacob_account_id()),
+            Some(2 * ONE_NEAR)
+        );
+        assert!(raffle_details.ticket_listings.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "The attached deposit should cover the ticket's price")]
+    fn check_buy_ticket_insufficient_deposit() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.list_ticket(alice_account_id().to_string(), U128(3 * ONE_NEAR));
+
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.buy_ticket(
+            alice_account_id().to_string(),
+            bob_account_id().to_string(),
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn check_participate_bundle() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.predecessor_account_id(jacob_account_id());
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(4 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate_bundle(
+            vec![
+                alice_account_id().to_string(),
+                jacob_account_id().to_string(),
+            ],
+            U128(2 * ONE_NEAR),
+        );
+
+        for raffle_account_id in [alice_account_id(), jacob_account_id()] {
+            let raffle_details = contract.raffles.get(&raffle_account_id).unwrap();
+            assert_eq!(
+                raffle_details.participants.get(&bob_account_id()),
+                Some(2 * ONE_NEAR)
+            );
+        }
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.leave_raffle(jacob_account_id().to_string());
+
+        let bundles = contract.get_bundles(bob_account_id().to_string());
+        assert_eq!(bundles.len(), 1);
+        assert_eq!(bundles[0].raffle_ids, vec![alice_account_id()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Sorry, no raffle is being conducted by \"mike.testnet\"")]
+    fn check_participate_bundle_all_or_nothing() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(4 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate_bundle(
+            vec![
+                alice_account_id().to_string(),
+                mike_account_id().to_string(),
+            ],
+            U128(2 * ONE_NEAR),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Entries to the raffle \"jacob.testnet\" are free")]
+    fn check_participate_bundle_free_entry() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.predecessor_account_id(jacob_account_id());
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            Some(DEFAULT_RAFFLE_FLAGS | FLAG_FREE_ENTRY),
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(4 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate_bundle(
+            vec![
+                alice_account_id().to_string(),
+                jacob_account_id().to_string(),
+            ],
+            U128(2 * ONE_NEAR),
+        );
+    }
+
+    #[test]
+    fn check_participate_bundle_instant_win() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        for account_id in [alice_account_id(), jacob_account_id()] {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(17 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.register_raffle(
+                Some(U64(1644353705121)),
+                U64(1644353705521),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(3 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        // every entry wins
+        contract.set_instant_wins(
+            alice_account_id().to_string(),
+            vec![InstantWinTier {
+                odds_bps: 10_000,
+                prize: U128(ONE_NEAR),
+            }],
+        );
+        let pool = contract
+            .get_instant_wins(alice_account_id().to_string())
+            .unwrap()
+            .pool
+            .0;
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(4 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate_bundle(
+            vec![
+                alice_account_id().to_string(),
+                jacob_account_id().to_string(),
+            ],
+            U128(2 * ONE_NEAR),
+        );
+        assert_eq!(
+            contract
+                .get_instant_wins(alice_account_id().to_string())
+                .unwrap()
+                .pool
+                .0,
+            pool - ONE_NEAR
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Ticket transfers are not enabled for this raffle")]
+    fn check_transfer_ticket_not_enabled() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            Some(0),
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.transfer_ticket(
+            alice_account_id().to_string(),
+            jacob_account_id().to_string(),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many entries, you can only enter 2 raffle(s) every 60000 ms")]
+    fn check_entry_rate_limit() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_entry_rate_limit(2, 60000);
+
+        context.attached_deposit(17 * ONE_NEAR);
+        for account_id in [alice_account_id(), jacob_account_id(), mike_account_id()] {
+            context.predecessor_account_id(account_id);
+            testing_env!(context.build());
+
+            contract.register_raffle(
+                Some(U64(1644353705121)),
+                U64(1644353905121),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+        contract.participate(jacob_account_id().to_string(), None, None, None);
+
+        // a new window starts once the previous one is over
+        context.block_timestamp(1644353765125 * TO_FROM_NANOSECONDS);
+        testing_env!(context.build());
+
+        contract.participate(mike_account_id().to_string(), None, None, None);
+
+        context.predecessor_account_id(jack_account_id());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+        contract.participate(jacob_account_id().to_string(), None, None, None);
+        contract.participate(mike_account_id().to_string(), None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Deposits can only be reclaimed")]
+    fn check_reclaim_deposit_too_early() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.block_timestamp(1644353706000 * TO_FROM_NANOSECONDS);
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.reclaim_deposit(alice_account_id().to_string());
+    }
+
+    #[test]
+    fn check_reclaim_deposit() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.attached_deposit(2 * ONE_NEAR);
+        for account_id in [bob_account_id(), jacob_account_id()] {
+            context.predecessor_account_id(account_id);
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+        }
+
+        // 31 days after the end of the raffle
+        context.block_timestamp((1644353705521 + 31 * 24 * 3_600 * 1_000) * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.reclaim_deposit(alice_account_id().to_string());
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert!(raffle_details.abandoned);
+        assert!(raffle_details.participants.get(&bob_account_id()).is_none());
+        assert_eq!(
+            contract
+                .get_raffles_filtered(None, Some(RaffleStatus::Abandoned), None, None)
+                .items
+                .len(),
+            1
+        );
+
+        // finalizing refunds the remaining participants instead of drawing a winner
+        context.predecessor_account_id(alice_account_id());
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        assert!(contract.raffles.get(&alice_account_id()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "You are not on the raffle's allowlist")]
+    fn check_participate_allowlist() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        let bob_leaf = allowlist_leaf(&bob_account_id());
+        let jacob_leaf = allowlist_leaf(&jacob_account_id());
+        let allowlist_root = hash_pair(&bob_leaf, &jacob_leaf);
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            Some(allowlist_root.into()),
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(
+            alice_account_id().to_string(),
+            Some(vec![jacob_leaf.into()]),
+            None,
+            None,
+        );
+        assert!(contract
+            .raffles
+            .get(&alice_account_id())
+            .unwrap()
+            .participants
+            .get(&bob_account_id())
+            .is_some());
+
+        context.predecessor_account_id(mike_account_id());
+        testing_env!(context.build());
+
+        contract.participate(
+            alice_account_id().to_string(),
+            Some(vec![jacob_leaf.into()]),
+            None,
+            None,
+        );
+    }
+
+    // Registers a two hour raffle of alice restricted to the allowlist of bob and jacob for
+    // its first hour, returning bob's proof
+    fn register_priority_raffle(
+        context: &mut VMContextBuilder,
+        contract: &mut RaffleDapp,
+    ) -> Vec<Base58CryptoHash> {
+        let bob_leaf = allowlist_leaf(&bob_account_id());
+        let jacob_leaf = allowlist_leaf(&jacob_account_id());
+        let allowlist_root = hash_pair(&bob_leaf, &jacob_leaf);
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705121 + 2 * 3_600_000),
+            None,
+            None,
+            Some(allowlist_root.into()),
+            None,
+        );
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.set_priority_window(alice_account_id().to_string(), 60);
+
+        vec![jacob_leaf.into()]
+    }
+
+    #[test]
+    fn check_priority_window() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        let proof = register_priority_raffle(&mut context, &mut contract);
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), Some(proof), None, None);
+
+        // the raffle opens to everyone once its priority window is over
+        context.block_timestamp((1644353705121 + 3_600_000) * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(mike_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(raffle_details.participants.len(), 2);
+        assert_eq!(
+            raffle_details.public_from(),
+            Some((1644353705121 + 3_600_000) * TO_FROM_NANOSECONDS)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "You are not on the raffle's allowlist")]
+    fn check_priority_window_not_allowlisted() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_priority_raffle(&mut context, &mut contract);
+
+        context.block_timestamp((1644353705121 + 3_599_999) * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(mike_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+    }
+
+    // Registers a raffle of alice restricted to the allowlist of bob and jacob, entered by bob,
+    // and returns jacob's proof
+    fn register_allowlisted_entry(
+        context: &mut VMContextBuilder,
+        contract: &mut RaffleDapp,
+    ) -> Vec<Base58CryptoHash> {
+        let bob_leaf = allowlist_leaf(&bob_account_id());
+        let jacob_leaf = allowlist_leaf(&jacob_account_id());
+        let allowlist_root = hash_pair(&bob_leaf, &jacob_leaf);
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            Some(allowlist_root.into()),
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(
+            alice_account_id().to_string(),
+            Some(vec![jacob_leaf.into()]),
+            None,
+            None,
+        );
+
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        vec![bob_leaf.into()]
+    }
+
+    #[test]
+    fn check_transfer_ticket_allowlist() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        let jacob_proof = register_allowlisted_entry(&mut context, &mut contract);
+
+        contract.transfer_ticket(
+            alice_account_id().to_string(),
+            jacob_account_id().to_string(),
+            Some(jacob_proof),
+        );
+        assert!(contract
+            .raffles
+            .get(&alice_account_id())
+            .unwrap()
+            .participants
+            .get(&jacob_account_id())
+            .is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "The receiver is not on the raffle's allowlist")]
+    fn check_transfer_ticket_not_allowlisted() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        let jacob_proof = register_allowlisted_entry(&mut context, &mut contract);
+
+        contract.transfer_ticket(
+            alice_account_id().to_string(),
+            mike_account_id().to_string(),
+            Some(jacob_proof),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "You are not on the raffle's allowlist")]
+    fn check_buy_ticket_not_allowlisted() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_allowlisted_entry(&mut context, &mut contract);
+
+        contract.list_ticket(alice_account_id().to_string(), U128(3 * ONE_NEAR));
+
+        context.predecessor_account_id(mike_account_id());
+        context.attached_deposit(3 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.buy_ticket(
+            alice_account_id().to_string(),
+            bob_account_id().to_string(),
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn check_participate_through_relayer() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        // jack relays bob's delegate action and pays for its gas
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.signer_account_id(jack_account_id());
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(
+            raffle_details.participants.get(&bob_account_id()),
+            Some(2 * ONE_NEAR)
+        );
+        assert!(raffle_details
+            .participants
+            .get(&jack_account_id())
+            .is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "The raffle's sponsorship allowance is exhausted")]
+    fn check_participate_sponsored() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.predecessor_account_id(jacob_account_id());
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.fund_sponsored_entries(alice_account_id().to_string());
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.participate_sponsored(alice_account_id().to_string(), None);
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(raffle_details.participants.get(&bob_account_id()), Some(0));
+        assert!(raffle_details.sponsor_pool < ONE_NEAR);
+
+        // jacob's raffle has no sponsorship allowance
+        contract.participate_sponsored(jacob_account_id().to_string(), None);
+    }
+
+    fn register_free_raffle(context: &mut VMContextBuilder, contract: &mut RaffleDapp) {
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            Some(DEFAULT_RAFFLE_FLAGS | FLAG_FREE_ENTRY),
+            None,
+            None,
+        );
+
+        context.attached_deposit(ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.fund_sponsored_entries(alice_account_id().to_string());
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+    }
+
+    #[test]
+    fn check_participate_free_entry() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_free_raffle(&mut context, &mut contract);
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(raffle_details.participants.get(&bob_account_id()), Some(0));
+        assert!(raffle_details.sponsor_pool < ONE_NEAR);
+        assert_eq!(
+            contract
+                .free_entry_windows
+                .get(&bob_account_id())
+                .unwrap()
+                .entries,
+            1
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Too many free entries, you can only enter 1 free raffle(s) every 60000 ms"
+    )]
+    fn check_free_entry_limit() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_free_entry_limit(1, 60000);
+        register_free_raffle(&mut context, &mut contract);
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+        contract.participate(alice_account_id().to_string(), None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Entries to this raffle are closed")]
+    fn check_participate_after_entry_cutoff() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        // the raffle lasts 10 minutes and its entries close 5 minutes before its end
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644354305121),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_entry_cutoff(alice_account_id().to_string(), 5);
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.block_timestamp(1644354005121 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(jacob_account_id());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+    }
+}
@@ -0,0 +1,765 @@
+use crate::events::log_event;
+use crate::finalize::{draw_weighted, DRAW_DELAY_BLOCKS};
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use near_sdk::PromiseResult;
+use serde_json::json;
+
+// number of settlements whose payout receipts are kept, older ones are dropped as new ones are
+// recorded
+const MAX_RETAINED_SETTLEMENTS: u64 = 1_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum PayoutKind {
+    // prize paid to the winner, along with its locked tokens
+    Prize,
+    // locked tokens returned to a participant
+    Refund,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum PayoutStatus {
+    // the transfer was sent, its outcome is resolved by `on_payout_resolved`
+    Pending,
+    Succeeded,
+    Failed,
+    // the transfer was sent without a callback, the gas left by the settlement couldn't pay for
+    // it
+    Unresolved,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PayoutReceipt {
+    pub(crate) recipient: AccountId,
+    pub(crate) amount: U128,
+    pub(crate) kind: PayoutKind,
+    pub(crate) status: PayoutStatus,
+}
+
+// How the prize of a settlement is paid again when its payout fails
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum RedrawRule {
+    // a new winner is drawn among the refunded participants, in proportion to their refunds when
+    // the raffle has weighted odds
+    Draw {
+        weighted: bool,
+    },
+    // the prize is split among the other winners of a number pick raffle, or rolls over to the
+    // next raffle of its organizer when none of them is left
+    Split {
+        winners: Vec<AccountId>,
+        owner: AccountId,
+    },
+}
+
+// Redraw waiting for the seed of its committed block
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PendingRedraw {
+    pub(crate) failed_winner: AccountId,
+    pub(crate) amount: Balance,
+    pub(crate) draw_block: BlockHeight,
+}
+
+// Payouts of a settled raffle, kept after the raffle is removed. Settlements without a prize
+// have no redraw rule.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Settlement {
+    pub(crate) raffle_id: AccountId,
+    pub(crate) settled_at: Timestamp,
+    pub(crate) payouts: Vec<PayoutReceipt>,
+    pub(crate) redraw_rule: Option<RedrawRule>,
+    pub(crate) pending_redraw: Option<PendingRedraw>,
+}
+
+// JSON representation of a settlement, `settled_at` is in ms. `redraw_block` is the block from
+// which `redraw_winner` can draw the new winner of a failed prize.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SettlementView {
+    pub(crate) id: u64,
+    pub(crate) raffle_id: AccountId,
+    pub(crate) settled_at: Timestamp,
+    pub(crate) payouts: Vec<PayoutReceipt>,
+    pub(crate) redraw_block: Option<BlockHeight>,
+}
+
+impl RaffleDapp {
+    // Reserves the id of the settlement the payouts of a raffle are recorded under
+    pub(crate) fn internal_open_settlement(&mut self) -> u64 {
+        self.settlement_seq += 1;
+        self.settlement_seq
+    }
+
+    // Sends `amount` to `recipient` and adds its receipt to `payouts`. The outcome of the
+    // transfer is resolved by `on_payout_resolved` as long as the gas left allows it.
+    pub(crate) fn internal_payout(
+        &self,
+        settlement_id: u64,
+        payouts: &mut Vec<PayoutReceipt>,
+        recipient: &AccountId,
+        amount: Balance,
+        kind: PayoutKind,
+    ) {
+        if amount == 0 {
+            return;
+        }
+
+        let transfer = Promise::new(recipient.clone()).transfer(amount);
+        let gas_left = env::prepaid_gas() - env::used_gas();
+        let status = if gas_left.0
+            > self.gas_config.payout_callback.0 + self.gas_config.finalize_reserve.0
+        {
+            transfer.then(
+                Promise::new(env::current_account_id()).function_call(
+                    "on_payout_resolved".to_string(),
+                    json!({ "settlement_id": settlement_id, "index": payouts.len() })
+                        .to_string()
+                        .into_bytes(),
+                    0,
+                    self.gas_config.payout_callback,
+                ),
+            );
+            PayoutStatus::Pending
+        } else {
+            PayoutStatus::Unresolved
+        };
+
+        payouts.push(PayoutReceipt {
+            recipient: recipient.clone(),
+            amount: U128(amount),
+            kind,
+            status,
+        });
+    }
+
+    // Stores the payouts of the settlement of a raffle, dropping the oldest settlement once
+    // `MAX_RETAINED_SETTLEMENTS` are kept
+    pub(crate) fn internal_record_settlement(
+        &mut self,
+        settlement_id: u64,
+        raffle_account_id: &AccountId,
+        payouts: Vec<PayoutReceipt>,
+        redraw_rule: Option<RedrawRule>,
+    ) {
+        self.settlements.insert(
+            &settlement_id,
+            &Settlement {
+                raffle_id: raffle_account_id.clone(),
+                settled_at: env::block_timestamp(),
+                payouts,
+                redraw_rule,
+                pending_redraw: None,
+            },
+        );
+        self.latest_settlements
+            .insert(raffle_account_id, &settlement_id);
+
+        if settlement_id > MAX_RETAINED_SETTLEMENTS {
+            let dropped_id = settlement_id - MAX_RETAINED_SETTLEMENTS;
+            if let Some(dropped) = self.settlements.remove(&dropped_id) {
+                if self.latest_settlements.get(&dropped.raffle_id) == Some(dropped_id) {
+                    self.latest_settlements.remove(&dropped.raffle_id);
+                }
+            }
+        }
+    }
+
+    // Pays again the prize of a settlement whose payout failed, the winner's account having been
+    // deleted. The prize of a number pick raffle is split among its other winners right away.
+    // Otherwise the new winner is drawn from the seed of a block at least `DRAW_DELAY_BLOCKS`
+    // later, like the first one, so the seed can't be known when the payout fails.
+    fn internal_repay_prize(
+        &mut self,
+        settlement_id: u64,
+        settlement: &mut Settlement,
+        failed_winner: &AccountId,
+        amount: Balance,
+    ) {
+        match &settlement.redraw_rule {
+            Some(RedrawRule::Split { .. }) => {
+                self.internal_split_prize(settlement_id, settlement, failed_winner, amount)
+            }
+            Some(RedrawRule::Draw { .. }) => {
+                let draw_block = env::block_height() + DRAW_DELAY_BLOCKS;
+                settlement.pending_redraw = Some(PendingRedraw {
+                    failed_winner: failed_winner.clone(),
+                    amount,
+                    draw_block,
+                });
+                env::log_str(&format!(
+                    "The new winner will be drawn from the random seed of the block {:?} or a later one",
+                    draw_block
+                ));
+            }
+            None => {}
+        }
+    }
+
+    // Accounts whose payouts of the settlement failed
+    fn failed_recipients(settlement: &Settlement) -> Vec<AccountId> {
+        settlement
+            .payouts
+            .iter()
+            .filter(|receipt| receipt.status == PayoutStatus::Failed)
+            .map(|receipt| receipt.recipient.clone())
+            .collect()
+    }
+
+    // Splits the failed prize of a number pick raffle equally among its other winners, the first
+    // of them taking the rounding remainder. Winners whose payouts failed are left out, and the
+    // prize rolls over to the next raffle of the organizer when nobody is left.
+    fn internal_split_prize(
+        &mut self,
+        settlement_id: u64,
+        settlement: &mut Settlement,
+        failed_winner: &AccountId,
+        amount: Balance,
+    ) {
+        let failed_recipients = Self::failed_recipients(settlement);
+        let (winners, owner) = match &settlement.redraw_rule {
+            Some(RedrawRule::Split { winners, owner }) => (winners.clone(), owner.clone()),
+            _ => return,
+        };
+        let winners: Vec<AccountId> = winners
+            .into_iter()
+            .filter(|account_id| !failed_recipients.contains(account_id))
+            .collect();
+
+        if winners.is_empty() {
+            let total = self.rollovers.get(&owner).unwrap_or(0) + amount;
+            self.rollovers.insert(&owner, &total);
+            env::log_str(&format!(
+                "No winner of the raffle {:?} is left to be paid its prize, {:?} yoctoNEAR roll over to the next raffle of {:?}",
+                settlement.raffle_id.to_string(),
+                amount,
+                owner.to_string()
+            ));
+            return;
+        }
+
+        let share = amount / winners.len() as Balance;
+        let remainder = amount - share * winners.len() as Balance;
+        for (index, winner_account_id) in winners.iter().enumerate() {
+            let winner_share = if index == 0 { share + remainder } else { share };
+            self.internal_payout(
+                settlement_id,
+                &mut settlement.payouts,
+                winner_account_id,
+                winner_share,
+                PayoutKind::Prize,
+            );
+        }
+
+        log_event(
+            "prize_split",
+            json!({
+                "raffle_id": settlement.raffle_id.to_string(),
+                "failed_winner": failed_winner.to_string(),
+                "winners": winners.iter().map(|account_id| account_id.to_string()).collect::<Vec<_>>(),
+                "amount": U128(amount),
+            }),
+        );
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Records the outcome of a payout transfer in its settlement, drawing a new winner when the
+    // prize couldn't be paid
+    #[private]
+    pub fn on_payout_resolved(&mut self, settlement_id: u64, index: u32) {
+        let mut settlement = match self.settlements.get(&settlement_id) {
+            Some(settlement) => settlement,
+            None => return,
+        };
+
+        let receipt = &mut settlement.payouts[index as usize];
+        receipt.status = match env::promise_result(0) {
+            PromiseResult::Successful(_) => PayoutStatus::Succeeded,
+            _ => PayoutStatus::Failed,
+        };
+        let receipt = receipt.clone();
+        if receipt.status == PayoutStatus::Failed {
+            self.metrics.record_failure(FailureCode::PayoutFailed);
+            log_event(
+                "payout_failed",
+                json!({
+                    "raffle_id": settlement.raffle_id.to_string(),
+                    "recipient": receipt.recipient.to_string(),
+                    "amount": receipt.amount,
+                }),
+            );
+            if receipt.kind == PayoutKind::Prize {
+                self.internal_repay_prize(
+                    settlement_id,
+                    &mut settlement,
+                    &receipt.recipient,
+                    receipt.amount.0,
+                );
+            }
+        }
+        self.settlements.insert(&settlement_id, &settlement);
+    }
+
+    // Draws the new winner of a settlement whose prize payout failed, once the block committed to
+    // by the failure is reached. The winner is drawn among the participants refunded by the
+    // settlement, in proportion to their refunds when the raffle has weighted odds, and is paid
+    // the whole failed payout, the locked tokens of the failed winner included since they can't
+    // be returned. Accounts whose payouts failed are left out, and the amount stays with the
+    // contract when nobody is left. Anyone can call it.
+    pub fn redraw_winner(&mut self, settlement_id: u64) {
+        let mut settlement = self
+            .settlements
+            .get(&settlement_id)
+            .expect("No settlement with this id");
+        let pending = settlement
+            .pending_redraw
+            .take()
+            .expect("No winner of this settlement is waiting to be redrawn");
+        assert!(
+            env::block_height() >= pending.draw_block,
+            "The new winner can only be drawn from the block {:?}",
+            pending.draw_block
+        );
+
+        let weighted = matches!(
+            settlement.redraw_rule,
+            Some(RedrawRule::Draw { weighted: true })
+        );
+        let failed_recipients = Self::failed_recipients(&settlement);
+        let candidates: Vec<(AccountId, Balance)> = settlement
+            .payouts
+            .iter()
+            .filter(|receipt| {
+                receipt.kind == PayoutKind::Refund
+                    && !failed_recipients.contains(&receipt.recipient)
+            })
+            .map(|receipt| (receipt.recipient.clone(), receipt.amount.0))
+            .collect();
+        if candidates.is_empty() {
+            env::log_str(&format!(
+                "No participant of the raffle {:?} is left to be paid its prize",
+                settlement.raffle_id.to_string()
+            ));
+            self.settlements.insert(&settlement_id, &settlement);
+            return;
+        }
+
+        let weights: Vec<Balance> = candidates
+            .iter()
+            .map(|(_, refund)| if weighted { *refund } else { 1 })
+            .collect();
+        let random_index = match draw_weighted(&env::random_seed(), &weights) {
+            Some(random_index) => random_index,
+            None => {
+                env::log_str(
+                    "Failed to draw the new winner in this block, try again in a later one",
+                );
+                return;
+            }
+        };
+
+        let winner_account_id = &candidates[random_index].0;
+        self.internal_payout(
+            settlement_id,
+            &mut settlement.payouts,
+            winner_account_id,
+            pending.amount,
+            PayoutKind::Prize,
+        );
+        self.settlements.insert(&settlement_id, &settlement);
+
+        log_event(
+            "winner_redrawn",
+            json!({
+                "raffle_id": settlement.raffle_id.to_string(),
+                "failed_winner": pending.failed_winner.to_string(),
+                "winner": winner_account_id.to_string(),
+                "amount": U128(pending.amount),
+            }),
+        );
+    }
+
+    pub fn get_settlement(&self, settlement_id: u64) -> Option<SettlementView> {
+        self.settlements
+            .get(&settlement_id)
+            .map(|settlement| SettlementView {
+                id: settlement_id,
+                raffle_id: settlement.raffle_id,
+                settled_at: settlement.settled_at / TO_FROM_NANOSECONDS,
+                payouts: settlement.payouts,
+                redraw_block: settlement.pending_redraw.map(|pending| pending.draw_block),
+            })
+    }
+
+    // Returns the payouts of the latest settlement of the raffle, which is no longer registered
+    pub fn get_raffle_settlement(&self, raffle_id: String) -> Option<SettlementView> {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).ok()?;
+        self.latest_settlements
+            .get(&raffle_account_id)
+            .and_then(|settlement_id| self.get_settlement(settlement_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raffle::FLAG_WEIGHTED_ODDS;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn settle_raffle(
+        context: &mut VMContextBuilder,
+        contract: &mut RaffleDapp,
+        flags: Option<u32>,
+        entrants: Vec<(AccountId, Balance)>,
+    ) {
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            flags,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        for (account_id, locked_tokens) in entrants {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(locked_tokens);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+        }
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        complete_draw(context, contract, alice_account_id());
+    }
+
+    #[test]
+    fn check_payout_receipts() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        settle_raffle(
+            &mut context,
+            &mut contract,
+            None,
+            vec![
+                (bob_account_id(), 2 * ONE_NEAR),
+                (jacob_account_id(), 2 * ONE_NEAR),
+            ],
+        );
+
+        let settlement = contract
+            .get_raffle_settlement(alice_account_id().to_string())
+            .unwrap();
+        assert_eq!(settlement.payouts.len(), 2);
+        assert_eq!(
+            settlement
+                .payouts
+                .iter()
+                .filter(|receipt| receipt.kind == PayoutKind::Prize)
+                .count(),
+            1
+        );
+        assert!(settlement
+            .payouts
+            .iter()
+            .all(|receipt| receipt.status == PayoutStatus::Pending));
+
+        // the refund bounced
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        let index = settlement
+            .payouts
+            .iter()
+            .position(|receipt| receipt.kind == PayoutKind::Refund)
+            .unwrap();
+        contract.on_payout_resolved(settlement.id, index as u32);
+        assert_eq!(
+            contract.get_settlement(settlement.id).unwrap().payouts[index].status,
+            PayoutStatus::Failed
+        );
+    }
+
+    #[test]
+    fn check_redraw_winner() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        settle_raffle(
+            &mut context,
+            &mut contract,
+            None,
+            vec![
+                (bob_account_id(), 2 * ONE_NEAR),
+                (jacob_account_id(), 2 * ONE_NEAR),
+            ],
+        );
+
+        let settlement = contract
+            .get_raffle_settlement(alice_account_id().to_string())
+            .unwrap();
+        let (index, prize) = settlement
+            .payouts
+            .iter()
+            .enumerate()
+            .find(|(_, receipt)| receipt.kind == PayoutKind::Prize)
+            .map(|(index, receipt)| (index, receipt.clone()))
+            .unwrap();
+        let refunded = settlement
+            .payouts
+            .iter()
+            .find(|receipt| receipt.kind == PayoutKind::Refund)
+            .unwrap()
+            .recipient
+            .clone();
+
+        // the winner's account was deleted, so the prize goes to the other participant
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        contract.on_payout_resolved(settlement.id, index as u32);
+        let settlement = contract.get_settlement(settlement.id).unwrap();
+        assert_eq!(settlement.payouts[index].status, PayoutStatus::Failed);
+        assert_eq!(
+            settlement.redraw_block,
+            Some(env::block_height() + DRAW_DELAY_BLOCKS)
+        );
+
+        // the new winner is drawn from the committed block
+        context.block_index(settlement.redraw_block.unwrap());
+        testing_env!(context.build());
+
+        contract.redraw_winner(settlement.id);
+        let settlement = contract.get_settlement(settlement.id).unwrap();
+        assert!(settlement.redraw_block.is_none());
+        let redrawn = settlement.payouts.last().unwrap();
+        assert_eq!(redrawn.kind, PayoutKind::Prize);
+        assert_eq!(redrawn.recipient, refunded);
+        assert_eq!(redrawn.amount, prize.amount);
+    }
+
+    #[test]
+    #[should_panic(expected = "The new winner can only be drawn from the block")]
+    fn check_redraw_winner_before_draw_block() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        settle_raffle(
+            &mut context,
+            &mut contract,
+            None,
+            vec![
+                (bob_account_id(), 2 * ONE_NEAR),
+                (jacob_account_id(), 2 * ONE_NEAR),
+            ],
+        );
+
+        let settlement = contract
+            .get_raffle_settlement(alice_account_id().to_string())
+            .unwrap();
+        let index = settlement
+            .payouts
+            .iter()
+            .position(|receipt| receipt.kind == PayoutKind::Prize)
+            .unwrap();
+
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        contract.on_payout_resolved(settlement.id, index as u32);
+        contract.redraw_winner(settlement.id);
+    }
+
+    #[test]
+    fn check_redraw_winner_weighted() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        settle_raffle(
+            &mut context,
+            &mut contract,
+            Some(FLAG_WEIGHTED_ODDS),
+            vec![
+                (bob_account_id(), 2 * ONE_NEAR),
+                (jacob_account_id(), 2 * ONE_NEAR),
+                (mike_account_id(), 6 * ONE_NEAR),
+            ],
+        );
+
+        let settlement = contract
+            .get_raffle_settlement(alice_account_id().to_string())
+            .unwrap();
+        let index = settlement
+            .payouts
+            .iter()
+            .position(|receipt| receipt.kind == PayoutKind::Prize)
+            .unwrap();
+        let refunds: Vec<PayoutReceipt> = settlement
+            .payouts
+            .iter()
+            .filter(|receipt| receipt.kind == PayoutKind::Refund)
+            .cloned()
+            .collect();
+
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_payout_resolved(settlement.id, index as u32);
+
+        // the seed falls right past the refund of the first candidate, which a uniform draw
+        // would pick since that refund is even
+        let mut random_seed = refunds[0].amount.0.to_le_bytes().to_vec();
+        random_seed.resize(32, 0);
+        context.random_seed(random_seed);
+        context.block_index(env::block_height() + DRAW_DELAY_BLOCKS);
+        testing_env!(context.build());
+
+        contract.redraw_winner(settlement.id);
+        let redrawn = contract
+            .get_settlement(settlement.id)
+            .unwrap()
+            .payouts
+            .pop()
+            .unwrap();
+        assert_eq!(redrawn.kind, PayoutKind::Prize);
+        assert_eq!(redrawn.recipient, refunds[1].recipient);
+    }
+
+    #[test]
+    fn check_redraw_winner_number_pick() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_number_pick(alice_account_id().to_string(), Some(10));
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        for (account_id, number) in [
+            (bob_account_id(), 4),
+            (jacob_account_id(), 4),
+            (mike_account_id(), 5),
+        ] {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(2 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+            contract.pick_number(alice_account_id().to_string(), number);
+        }
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        contract.internal_settle_number_pick(&alice_account_id(), raffle_details, 4);
+
+        let settlement = contract
+            .get_raffle_settlement(alice_account_id().to_string())
+            .unwrap();
+        let (index, prize) = settlement
+            .payouts
+            .iter()
+            .enumerate()
+            .find(|(_, receipt)| receipt.kind == PayoutKind::Prize)
+            .map(|(index, receipt)| (index, receipt.clone()))
+            .unwrap();
+        assert_eq!(prize.recipient, bob_account_id());
+
+        // bob's account was deleted, so his share goes to jacob who also picked the number
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        contract.on_payout_resolved(settlement.id, index as u32);
+        let settlement = contract.get_settlement(settlement.id).unwrap();
+        assert!(settlement.redraw_block.is_none());
+        let split = settlement.payouts.last().unwrap();
+        assert_eq!(split.kind, PayoutKind::Prize);
+        assert_eq!(split.recipient, jacob_account_id());
+        assert_eq!(split.amount, prize.amount);
+
+        // jacob's account is gone too, so the prize rolls over instead of going to mike
+        contract.on_payout_resolved(settlement.id, settlement.payouts.len() as u32 - 1);
+        assert_eq!(
+            contract
+                .get_settlement(settlement.id)
+                .unwrap()
+                .payouts
+                .len(),
+            settlement.payouts.len()
+        );
+        assert_eq!(
+            contract.get_rollover(alice_account_id().to_string()),
+            prize.amount
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use crate::views::MAX_PAGE_LIMIT;
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::PromiseResult;
+use serde_json::json;
+
+// time after which a cached price is too old to value anything (6 hours, in ns)
+const PRICE_MAX_AGE: Timestamp = 6 * NANOSECONDS_PER_HOUR;
+
+// Price oracle the prices are fetched from with `get_price_data`, and its id of wrapped NEAR
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceOracleConfig {
+    pub(crate) oracle: AccountId,
+    pub(crate) near_asset_id: AccountId,
+}
+
+// Price of an asset as reported by the oracle: `multiplier / 10^decimals` USD for each of its
+// smallest units
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
+pub struct CachedPrice {
+    pub(crate) multiplier: U128,
+    pub(crate) decimals: u8,
+    pub(crate) updated_at: Timestamp,
+}
+
+// JSON representation of a cached price, `updated_at` is in ms
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceView {
+    pub(crate) asset_id: AccountId,
+    pub(crate) multiplier: U128,
+    pub(crate) decimals: u8,
+    pub(crate) updated_at: U64,
+}
+
+impl CachedPrice {
+    fn is_fresh(&self) -> bool {
+        env::block_timestamp() <= self.updated_at + PRICE_MAX_AGE
+    }
+
+    // Smallest units of the asset priced by `to` worth `amount` smallest units of this one, if
+    // both prices are recent enough
+    pub(crate) fn convert(&self, amount: Balance, to: &CachedPrice) -> Option<Balance> {
+        if !self.is_fresh() || !to.is_fresh() || to.multiplier.0 == 0 {
+            return None;
+        }
+        let value = amount.checked_mul(self.multiplier.0)?;
+        let scaled = if to.decimals >= self.decimals {
+            value.checked_mul(10u128.checked_pow((to.decimals - self.decimals) as u32)?)?
+        } else {
+            value / 10u128.checked_pow((self.decimals - to.decimals) as u32)?
+        };
+        Some(scaled / to.multiplier.0)
+    }
+
+    // Value of `amount` smallest units in USD cents, if the price is recent enough
+    fn usd_cents(&self, amount: Balance) -> Option<U64> {
+        if !self.is_fresh() {
+            return None;
+        }
+        let cents = amount
+            .checked_mul(self.multiplier.0)?
+            .checked_mul(100)?
+            .checked_div(10u128.checked_pow(self.decimals as u32)?)?;
+        u64::try_from(cents).ok().map(U64)
+    }
+}
+
+// Approximate USD values of a set of raffles, in cents, `None` for the raffles that aren't
+// registered or have a hidden prize and for everything once the price of NEAR is stale
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UsdValuesView {
+    // the prize of each raffle, and the pot adding the tokens locked by its participants
+    pub(crate) prizes: Vec<Option<U64>>,
+    pub(crate) pots: Vec<Option<U64>>,
+    pub(crate) jackpot: Option<U64>,
+    // in ms
+    pub(crate) price_updated_at: Option<U64>,
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Sets the oracle the prices of NEAR and of the accepted tokens are fetched from, passing
+    // `None` stops valuing amounts in USD
+    pub fn set_price_oracle(&mut self, config: Option<PriceOracleConfig>) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can set the price oracle"
+        );
+
+        self.price_oracle = config;
+        match &self.price_oracle {
+            Some(config) => env::log_str(&format!(
+                "Prices are fetched from {:?}",
+                config.oracle.to_string()
+            )),
+            None => {
+                self.prices.clear();
+                env::log_str("Prices are no longer fetched")
+            }
+        }
+    }
+
+    // Fetches the prices of NEAR and of the accepted tokens from the oracle, cached by
+    // `on_prices_fetched`. Anyone can call it, e.g. a keeper keeping the listings priced.
+    pub fn refresh_prices(&mut self) -> Promise {
+        let config = self
+            .price_oracle
+            .clone()
+            .unwrap_or_else(|| env::panic_str("No price oracle is set"));
+
+        let asset_ids: Vec<String> = std::iter::once(config.near_asset_id)
+            .chain(self.accepted_tokens.keys())
+            .map(|asset_id| asset_id.to_string())
+            .collect();
+
+        Promise::new(config.oracle)
+            .function_call(
+                "get_price_data".to_string(),
+                json!({ "asset_ids": asset_ids }).to_string().into_bytes(),
+                0,
+                self.gas_config.price_query,
+            )
+            .then(Promise::new(env::current_account_id()).function_call(
+                "on_prices_fetched".to_string(),
+                Vec::new(),
+                0,
+                self.gas_config.price_callback,
+            ))
+    }
+
+    // Caches the prices returned by the oracle, skipping the assets it has no price for
+    #[private]
+    pub fn on_prices_fetched(&mut self) {
+        let price_data = match env::promise_result(0) {
+            PromiseResult::Successful(result) => {
+                serde_json::from_slice::<serde_json::Value>(&result).ok()
+            }
+            _ => None,
+        };
+        let prices = match price_data
+            .as_ref()
+            .and_then(|data| data["prices"].as_array())
+        {
+            Some(prices) => prices,
+            None => {
+                env::log_str("The price oracle didn't return any price");
+                return;
+            }
+        };
+
+        let mut updated = 0;
+        for price in prices {
+            let asset_id = price["asset_id"]
+                .as_str()
+                .and_then(|asset_id| AccountId::try_from(asset_id.to_string()).ok());
+            let multiplier = price["price"]["multiplier"]
+                .as_str()
+                .and_then(|multiplier| multiplier.parse::<u128>().ok());
+            let decimals = price["price"]["decimals"].as_u64();
+            if let (Some(asset_id), Some(multiplier), Some(decimals)) =
+                (asset_id, multiplier, decimals)
+            {
+                self.prices.insert(
+                    &asset_id,
+                    &CachedPrice {
+                        multiplier: U128(multiplier),
+                        decimals: decimals as u8,
+                        updated_at: env::block_timestamp(),
+                    },
+                );
+                updated += 1;
+            }
+        }
+
+        env::log_str(&format!("{} price(s) refreshed", updated));
+    }
+
+    pub fn get_prices(&self) -> Vec<PriceView> {
+        self.prices
+            .iter()
+            .map(|(asset_id, price)| PriceView {
+                asset_id,
+                multiplier: price.multiplier,
+                decimals: price.decimals,
+                updated_at: U64(price.updated_at / TO_FROM_NANOSECONDS),
+            })
+            .collect()
+    }
+
+    // Values the prizes and pots of the given raffles and the jackpot in USD, at the cached
+    // price of NEAR. The raffles past the first `MAX_PAGE_LIMIT` aren't valued.
+    pub fn get_usd_values(&self, raffle_ids: Vec<String>) -> UsdValuesView {
+        let near_price = self
+            .price_oracle
+            .as_ref()
+            .and_then(|config| self.prices.get(&config.near_asset_id));
+        let usd_cents = |amount: Balance| near_price.and_then(|price| price.usd_cents(amount));
+
+        let raffles: Vec<Option<RaffleDetails>> = raffle_ids
+            .into_iter()
+            .enumerate()
+            .map(|(index, raffle_id)| {
+                if index as u64 >= MAX_PAGE_LIMIT {
+                    return None;
+                }
+                self.find_raffle(raffle_id)
+                    .map(|(_, raffle_details)| raffle_details)
+                    .filter(|raffle_details| !raffle_details.prize_hidden())
+            })
+            .collect();
+
+        UsdValuesView {
+            prizes: raffles
+                .iter()
+                .map(|raffle| raffle.as_ref().and_then(|raffle| usd_cents(raffle.prize)))
+                .collect(),
+            pots: raffles
+                .iter()
+                .map(|raffle| {
+                    raffle.as_ref().and_then(|raffle| {
+                        usd_cents(raffle.prize + raffle.participants.values().sum::<Balance>())
+                    })
+                })
+                .collect(),
+            jackpot: usd_cents(self.jackpot),
+            price_updated_at: near_price.map(|price| U64(price.updated_at / TO_FROM_NANOSECONDS)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_usd_values() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.set_price_oracle(Some(PriceOracleConfig {
+            oracle: mike_account_id(),
+            near_asset_id: AccountId::try_from("wrap.testnet".to_string()).unwrap(),
+        }));
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        // 1 NEAR is worth 3 USD
+        context.predecessor_account_id(raffle_dapp_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(
+                br#"{"timestamp":"0","recency_duration_sec":90,"prices":[{"asset_id":"wrap.testnet","price":{"multiplier":"30000","decimals":28}}]}"#
+                    .to_vec()
+            )],
+        );
+        contract.on_prices_fetched();
+
+        let prize = contract.raffles.get(&alice_account_id()).unwrap().prize;
+        let values = contract.get_usd_values(vec![
+            alice_account_id().to_string(),
+            bob_account_id().to_string(),
+        ]);
+        assert_eq!(values.prizes[0], Some(U64((prize * 300 / ONE_NEAR) as u64)));
+        assert!(values.prizes[1].is_none());
+
+        // the price goes stale
+        context.block_timestamp(PRICE_MAX_AGE + 1);
+        testing_env!(context.build());
+        assert!(contract
+            .get_usd_values(vec![alice_account_id().to_string()])
+            .prizes[0]
+            .is_none());
+    }
+}
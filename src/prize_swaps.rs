@@ -0,0 +1,586 @@
+use crate::events::log_event;
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::PromiseResult;
+use serde_json::json;
+
+// largest slippage (in basis points) the contract account can allow on the swaps of the prizes
+const MAX_SLIPPAGE_BPS: u16 = 1_000;
+
+// Pool of the DEX swapping wrapped NEAR for an accepted token
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapPool {
+    pub(crate) token_id: AccountId,
+    pub(crate) pool_id: u64,
+}
+
+// DEX the winners can have their prize swapped on, e.g. Ref Finance, along with the wrapped NEAR
+// contract the prizes are wrapped with. `claim_window` is in ms, and `max_slippage_bps` bounds the
+// minimum amount out a winner can accept against the oracle's prices.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PrizeSwapConfig {
+    pub(crate) dex_id: AccountId,
+    pub(crate) wrap_id: AccountId,
+    pub(crate) pools: Vec<SwapPool>,
+    pub(crate) claim_window: U64,
+    pub(crate) max_slippage_bps: u16,
+}
+
+impl PrizeSwapConfig {
+    fn pool(&self, token_id: &AccountId) -> Option<&SwapPool> {
+        self.pools.iter().find(|pool| &pool.token_id == token_id)
+    }
+}
+
+// Token a winner elects to receive the prize in, and the least amount of it they accept
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PrizeCurrency {
+    pub(crate) token_id: AccountId,
+    pub(crate) min_amount_out: U128,
+}
+
+// Prize of a settled raffle held until its winner elects the currency it is paid in
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct HeldPrize {
+    pub(crate) raffle_id: AccountId,
+    pub(crate) winner: AccountId,
+    pub(crate) amount: Balance,
+    // the winner can only have the prize swapped until then, anyone can pay it in NEAR afterwards
+    pub(crate) claim_until: Timestamp,
+    // set while the prize is being swapped
+    pub(crate) swapping: bool,
+}
+
+// JSON representation of a held prize, `claim_until` is in ms
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HeldPrizeView {
+    pub(crate) raffle_id: AccountId,
+    pub(crate) winner: AccountId,
+    pub(crate) amount: U128,
+    pub(crate) claim_until: U64,
+    pub(crate) swapping: bool,
+}
+
+impl RaffleDapp {
+    // Holds the prize of a winner while they elect the currency it is paid in
+    pub(crate) fn internal_hold_prize(
+        &mut self,
+        raffle_account_id: &AccountId,
+        winner_account_id: &AccountId,
+        amount: Balance,
+        claim_window: Timestamp,
+    ) {
+        self.held_prize_seq += 1;
+        let claim_until = env::block_timestamp() + claim_window;
+        self.held_prizes.insert(
+            &self.held_prize_seq,
+            &HeldPrize {
+                raffle_id: raffle_account_id.clone(),
+                winner: winner_account_id.clone(),
+                amount,
+                claim_until,
+                swapping: false,
+            },
+        );
+
+        log_event(
+            "prize_held",
+            json!({
+                "prize_id": self.held_prize_seq,
+                "raffle_id": raffle_account_id.to_string(),
+                "winner": winner_account_id.to_string(),
+                "amount": U128(amount),
+                "claim_until": U64(claim_until / TO_FROM_NANOSECONDS),
+            }),
+        );
+    }
+
+    // Checks that `min_amount_out` of the token is within the allowed slippage of the value of
+    // `amount` yoctoNEAR at the oracle's prices
+    fn assert_within_slippage(
+        &self,
+        config: &PrizeSwapConfig,
+        amount: Balance,
+        currency: &PrizeCurrency,
+    ) {
+        let expected_out = self
+            .price_oracle
+            .as_ref()
+            .and_then(|oracle| self.prices.get(&oracle.near_asset_id))
+            .zip(self.prices.get(&currency.token_id))
+            .and_then(|(near_price, token_price)| near_price.convert(amount, &token_price))
+            .unwrap_or_else(|| {
+                env::panic_str(
+                    "Prizes can only be swapped while the prices of the oracle are fresh",
+                )
+            });
+
+        let min_allowed = expected_out / 10_000 * (10_000 - config.max_slippage_bps) as Balance;
+        assert!(
+            currency.min_amount_out.0 >= min_allowed,
+            "The minimum amount out should be at least {}, the value of the prize less the maximum slippage of {} bps",
+            min_allowed,
+            config.max_slippage_bps
+        );
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Sets the DEX the winners can have their prize swapped on, passing `None` pays every prize
+    // in NEAR again. Once set, the prizes of the settled raffles are held for `claim_window` so
+    // that their winners can elect the currency they are paid in.
+    pub fn set_prize_swaps(&mut self, config: Option<PrizeSwapConfig>) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can set the prize swaps"
+        );
+        self.assert_not_decommissioned();
+
+        if let Some(config) = &config {
+            assert!(
+                config.max_slippage_bps <= MAX_SLIPPAGE_BPS,
+                "The maximum slippage cannot exceed {} bps",
+                MAX_SLIPPAGE_BPS
+            );
+            for pool in config.pools.iter() {
+                self.assert_accepted_token(&pool.token_id);
+            }
+        }
+
+        self.prize_swaps = config;
+        match &self.prize_swaps {
+            Some(config) => env::log_str(&format!(
+                "Winners can have their prize swapped for {} token(s) on {:?} within {} ms",
+                config.pools.len(),
+                config.dex_id.to_string(),
+                config.claim_window.0
+            )),
+            None => env::log_str("Prizes are paid in NEAR"),
+        }
+    }
+
+    // Pays a held prize to its winner in the given currency, in NEAR if `None`. The prize is
+    // wrapped and swapped on the DEX, the tokens out being sent to the winner, who has to be
+    // registered with the token's contract. Once the claim window is over, anyone can have the
+    // prize paid in NEAR.
+    pub fn claim_held_prize(&mut self, prize_id: u64, currency: Option<PrizeCurrency>) {
+        let mut prize = self
+            .held_prizes
+            .get(&prize_id)
+            .unwrap_or_else(|| env::panic_str("No held prize with this id"));
+        assert!(!prize.swapping, "The prize is already being swapped");
+
+        let claim_window_open = env::block_timestamp() < prize.claim_until;
+        assert!(
+            env::predecessor_account_id() == prize.winner || !claim_window_open,
+            "Only the winner can claim the prize before its claim window is over"
+        );
+
+        let currency = match currency {
+            Some(currency) => currency,
+            None => {
+                self.held_prizes.remove(&prize_id);
+                Promise::new(prize.winner.clone()).transfer(prize.amount);
+                log_event(
+                    "prize_claimed",
+                    json!({
+                        "prize_id": prize_id,
+                        "winner": prize.winner.to_string(),
+                        "amount": U128(prize.amount),
+                    }),
+                );
+                return;
+            }
+        };
+
+        assert!(
+            claim_window_open,
+            "The claim window of the prize is over, it can only be paid in NEAR"
+        );
+        let config = self
+            .prize_swaps
+            .clone()
+            .unwrap_or_else(|| env::panic_str("Prizes are only paid in NEAR"));
+        let pool = config
+            .pool(&currency.token_id)
+            .unwrap_or_else(|| env::panic_str("Prizes cannot be swapped for this token"));
+        self.assert_within_slippage(&config, prize.amount, &currency);
+
+        prize.swapping = true;
+        self.held_prizes.insert(&prize_id, &prize);
+
+        Promise::new(config.wrap_id.clone())
+            .function_call(
+                "near_deposit".to_string(),
+                Vec::new(),
+                prize.amount,
+                self.gas_config.near_wrap,
+            )
+            .then(
+                Promise::new(config.wrap_id.clone()).function_call(
+                    "ft_transfer_call".to_string(),
+                    json!({
+                        "receiver_id": config.dex_id.to_string(),
+                        "amount": U128(prize.amount),
+                        "msg": json!({
+                            "actions": [{
+                                "pool_id": pool.pool_id,
+                                "token_in": config.wrap_id.to_string(),
+                                "token_out": currency.token_id.to_string(),
+                                "amount_in": U128(prize.amount),
+                                "min_amount_out": currency.min_amount_out,
+                            }],
+                            "swap_out_recipient": prize.winner.to_string(),
+                        })
+                        .to_string(),
+                    })
+                    .to_string()
+                    .into_bytes(),
+                    1,
+                    self.gas_config.prize_swap,
+                ),
+            )
+            .then(
+                Promise::new(env::current_account_id()).function_call(
+                    "on_prize_swapped".to_string(),
+                    json!({ "prize_id": prize_id, "token_id": currency.token_id.to_string() })
+                        .to_string()
+                        .into_bytes(),
+                    0,
+                    self.gas_config.prize_swap_callback,
+                ),
+            );
+    }
+
+    // Releases a prize once its swap went through. Otherwise the wrapped NEAR refunded by the DEX,
+    // e.g. when the amount out fell below the minimum, is unwrapped and the prize can be claimed
+    // again once `on_prize_unwrapped` confirms the unwrapping.
+    #[private]
+    pub fn on_prize_swapped(&mut self, prize_id: u64, token_id: AccountId) -> bool {
+        let prize = match self.held_prizes.get(&prize_id) {
+            Some(prize) => prize,
+            None => return false,
+        };
+
+        // `ft_transfer_call` returns the amount the DEX used
+        let used = match env::promise_result(0) {
+            PromiseResult::Successful(result) => serde_json::from_slice::<U128>(&result)
+                .map(|used| used.0)
+                .unwrap_or(0),
+            _ => 0,
+        };
+
+        if used == prize.amount {
+            self.held_prizes.remove(&prize_id);
+            log_event(
+                "prize_swapped",
+                json!({
+                    "prize_id": prize_id,
+                    "winner": prize.winner.to_string(),
+                    "amount": U128(prize.amount),
+                    "token_id": token_id.to_string(),
+                }),
+            );
+            return true;
+        }
+
+        // The prize stays locked while its refund is wrapped, it couldn't be paid otherwise
+        let config = match self.prize_swaps.as_ref() {
+            Some(config) => config,
+            None => {
+                env::log_str(&format!(
+                    "The swap of the prize {} failed and its refund cannot be unwrapped while prizes are only paid in NEAR",
+                    prize_id
+                ));
+                return false;
+            }
+        };
+        Promise::new(config.wrap_id.clone())
+            .function_call(
+                "near_withdraw".to_string(),
+                json!({ "amount": U128(prize.amount - used) })
+                    .to_string()
+                    .into_bytes(),
+                1,
+                self.gas_config.near_wrap,
+            )
+            .then(
+                Promise::new(env::current_account_id()).function_call(
+                    "on_prize_unwrapped".to_string(),
+                    json!({ "prize_id": prize_id, "used": U128(used) })
+                        .to_string()
+                        .into_bytes(),
+                    0,
+                    self.gas_config.prize_unwrap_callback,
+                ),
+            );
+        false
+    }
+
+    // Makes a prize claimable again once the NEAR refunded by its failed swap was unwrapped, less
+    // the part of it the DEX used
+    #[private]
+    pub fn on_prize_unwrapped(&mut self, prize_id: u64, used: U128) -> bool {
+        let mut prize = match self.held_prizes.get(&prize_id) {
+            Some(prize) => prize,
+            None => return false,
+        };
+
+        if !matches!(env::promise_result(0), PromiseResult::Successful(_)) {
+            env::log_str(&format!(
+                "The refund of the swap of the prize {} could not be unwrapped",
+                prize_id
+            ));
+            return false;
+        }
+
+        prize.amount -= used.0;
+        prize.swapping = false;
+        self.held_prizes.insert(&prize_id, &prize);
+        env::log_str(&format!(
+            "The swap of the prize {} failed, it can be claimed again",
+            prize_id
+        ));
+        true
+    }
+
+    pub fn get_prize_swaps(&self) -> Option<PrizeSwapConfig> {
+        self.prize_swaps.clone()
+    }
+
+    pub fn get_held_prize(&self, prize_id: u64) -> Option<HeldPrizeView> {
+        self.held_prizes.get(&prize_id).map(|prize| HeldPrizeView {
+            raffle_id: prize.raffle_id,
+            winner: prize.winner,
+            amount: U128(prize.amount),
+            claim_until: U64(prize.claim_until / TO_FROM_NANOSECONDS),
+            swapping: prize.swapping,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prices::CachedPrice;
+    use crate::test_utils::*;
+    use crate::AcceptedToken;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn wrap_account_id() -> AccountId {
+        AccountId::new_unchecked("wrap.testnet".to_string())
+    }
+
+    // Settles a raffle of alice entered by bob and jacob while prizes can be swapped for mike's
+    // token, priced at 1 USD with 6 decimals while NEAR is priced at 3.5 USD. Returns the held
+    // prize and the amount of the token it is worth.
+    fn settle_swappable_raffle(
+        context: &mut VMContextBuilder,
+        contract: &mut RaffleDapp,
+    ) -> (HeldPrizeView, Balance) {
+        contract.set_accepted_token(
+            mike_account_id().to_string(),
+            Some(AcceptedToken {
+                decimals: 6,
+                min_amount: U128(1),
+            }),
+        );
+        contract.set_price_oracle(Some(PriceOracleConfig {
+            oracle: jack_account_id(),
+            near_asset_id: wrap_account_id(),
+        }));
+        contract.set_prize_swaps(Some(PrizeSwapConfig {
+            dex_id: jack_account_id(),
+            wrap_id: wrap_account_id(),
+            pools: vec![SwapPool {
+                token_id: mike_account_id(),
+                pool_id: 4,
+            }],
+            claim_window: U64(3_600_000),
+            max_slippage_bps: 100,
+        }));
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        for account_id in [bob_account_id(), jacob_account_id()] {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(2 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+        }
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        complete_draw(context, contract, alice_account_id());
+
+        for (asset_id, multiplier, decimals) in [
+            (wrap_account_id(), 35_000, 28),
+            (mike_account_id(), 10_000, 10),
+        ] {
+            contract.prices.insert(
+                &asset_id,
+                &CachedPrice {
+                    multiplier: U128(multiplier),
+                    decimals,
+                    updated_at: env::block_timestamp(),
+                },
+            );
+        }
+
+        let prize = contract.get_held_prize(1).unwrap();
+        let value = prize.amount.0 * 35 / 10u128.pow(19);
+        (prize, value)
+    }
+
+    #[test]
+    fn check_prize_swap() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        let (prize, value) = settle_swappable_raffle(&mut context, &mut contract);
+
+        context.predecessor_account_id(prize.winner.clone());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.claim_held_prize(
+            1,
+            Some(PrizeCurrency {
+                token_id: mike_account_id(),
+                min_amount_out: U128(value * 99 / 100),
+            }),
+        );
+        assert!(contract.get_held_prize(1).unwrap().swapping);
+
+        // the DEX used the whole wrapped prize
+        context.predecessor_account_id(raffle_dapp_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(
+                serde_json::to_vec(&prize.amount).unwrap()
+            )]
+        );
+
+        assert!(contract.on_prize_swapped(1, mike_account_id()));
+        assert!(contract.get_held_prize(1).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "The minimum amount out should be at least")]
+    fn check_prize_swap_slippage() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        let (prize, value) = settle_swappable_raffle(&mut context, &mut contract);
+
+        context.predecessor_account_id(prize.winner);
+        testing_env!(context.build());
+
+        contract.claim_held_prize(
+            1,
+            Some(PrizeCurrency {
+                token_id: mike_account_id(),
+                min_amount_out: U128(value / 2),
+            }),
+        );
+    }
+
+    #[test]
+    fn check_prize_swap_refund() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        let (prize, value) = settle_swappable_raffle(&mut context, &mut contract);
+
+        context.predecessor_account_id(prize.winner.clone());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.claim_held_prize(
+            1,
+            Some(PrizeCurrency {
+                token_id: mike_account_id(),
+                min_amount_out: U128(value * 99 / 100),
+            }),
+        );
+
+        // the DEX refunded the whole wrapped prize, which stays locked until it is unwrapped
+        context.predecessor_account_id(raffle_dapp_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(
+                serde_json::to_vec(&U128(0)).unwrap()
+            )]
+        );
+
+        assert!(!contract.on_prize_swapped(1, mike_account_id()));
+        assert!(contract.get_held_prize(1).unwrap().swapping);
+
+        context.storage_usage(env::storage_usage());
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+
+        assert!(!contract.on_prize_unwrapped(1, U128(0)));
+        assert!(contract.get_held_prize(1).unwrap().swapping);
+
+        context.storage_usage(env::storage_usage());
+        testing_env!(
+            context.build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(Vec::new())]
+        );
+
+        assert!(contract.on_prize_unwrapped(1, U128(0)));
+        let held_prize = contract.get_held_prize(1).unwrap();
+        assert!(!held_prize.swapping);
+        assert_eq!(held_prize.amount, prize.amount);
+    }
+}
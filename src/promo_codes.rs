@@ -0,0 +1,194 @@
+use crate::participation::InternalParticipation;
+use crate::raffle::RaffleStore;
+use crate::*;
+use near_sdk::json_types::Base58CryptoHash;
+use near_sdk::CryptoHash;
+
+// largest number of promo codes added by a single call of `add_promo_codes`
+const MAX_PROMO_CODES_PER_CALL: usize = 100;
+
+impl RaffleDapp {
+    // Enters the raffle with a single-use promo code instead of locking tokens, the storage of
+    // the entry being paid from the allowance funded by the organizer
+    pub(crate) fn internal_redeem_promo_code(
+        &mut self,
+        raffle_account_id: &AccountId,
+        promo_code: String,
+        proof: &[CryptoHash],
+    ) {
+        assert_eq!(
+            env::attached_deposit(),
+            0,
+            "Entries with a promo code don't lock any tokens"
+        );
+
+        assert!(
+            self.raffles.get(raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        let mut raffle_details = self.raffles.get(raffle_account_id).unwrap();
+        let code_hash: CryptoHash = env::sha256(promo_code.as_bytes()).try_into().unwrap();
+        assert!(
+            raffle_details.promo_codes.remove(&code_hash),
+            "The promo code is invalid or was already redeemed"
+        );
+        self.internal_save_raffle(raffle_account_id, &raffle_details);
+
+        self.internal_participate(raffle_account_id, &env::predecessor_account_id(), 0, proof);
+        self.internal_debit_sponsor_pool(raffle_account_id, initial_storage_usage);
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Adds single-use promo codes to the raffle, given as the sha256 of each code. Redeeming a
+    // code with `participate` enters the raffle without locking tokens, the entry being paid
+    // from the allowance of sponsored entries. The attached deposit pays for the storage of the
+    // codes, refunded once the raffle is settled, and the rest funds the allowance.
+    #[payable]
+    pub fn add_promo_codes(&mut self, raffle_id: String, code_hashes: Vec<Base58CryptoHash>) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can add promo codes"
+        );
+
+        assert!(
+            env::block_timestamp() < raffle_details.end,
+            "Promo codes can only be added before the raffle ends"
+        );
+
+        assert!(
+            !code_hashes.is_empty() && code_hashes.len() <= MAX_PROMO_CODES_PER_CALL,
+            "Between 1 and {} promo codes can be added at once",
+            MAX_PROMO_CODES_PER_CALL
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        for code_hash in &code_hashes {
+            raffle_details
+                .promo_codes
+                .insert(&CryptoHash::from(*code_hash));
+        }
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        // The storage cost doesn't change the size of the record and the update is already
+        // recorded in the change feed, so the measured cost stays exact
+        let storage_cost = storage_cost_since(initial_storage_usage);
+        assert!(
+            env::attached_deposit() >= storage_cost,
+            "The attached deposit should cover the storage cost of the promo codes"
+        );
+        raffle_details.storage_cost += storage_cost;
+        raffle_details.sponsor_pool += env::attached_deposit() - storage_cost;
+        self.internal_store_raffle(&raffle_account_id, &raffle_details);
+
+        env::log_str(&format!(
+            "{} promo codes added to the raffle of {:?}, which has {} left to redeem",
+            code_hashes.len(),
+            raffle_account_id.to_string(),
+            raffle_details.promo_codes.len()
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_promo_code() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        let code_hash: CryptoHash = env::sha256(b"GIVEAWAY").try_into().unwrap();
+        contract.add_promo_codes(alice_account_id().to_string(), vec![code_hash.into()]);
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(
+            alice_account_id().to_string(),
+            None,
+            Some("GIVEAWAY".to_string()),
+            None,
+        );
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(raffle_details.participants.get(&bob_account_id()), Some(0));
+        assert!(raffle_details.promo_codes.is_empty());
+        assert!(raffle_details.sponsor_pool < ONE_NEAR);
+        assert_eq!(contract.get_metrics().participate, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "The promo code is invalid or was already redeemed")]
+    fn check_promo_code_invalid() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.participate(
+            alice_account_id().to_string(),
+            None,
+            Some("GIVEAWAY".to_string()),
+            None,
+        );
+    }
+}
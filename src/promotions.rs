@@ -0,0 +1,289 @@
+use crate::events::log_event;
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use serde_json::json;
+
+// one basis point is a hundredth of a percent, 10000 doubling the prizes
+const BASIS_POINTS: u16 = 10_000;
+
+// Prizes of the raffles registered before `expires_at` matched by the contract out of a
+// dedicated budget, e.g. doubled up to 10 NEAR
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Promotion {
+    // share of the prize matched, in basis points
+    pub(crate) match_bps: u16,
+    // largest match of a single raffle
+    pub(crate) cap: Balance,
+    // what is left to match prizes with, held by the contract
+    pub(crate) budget: Balance,
+    pub(crate) matched: Balance,
+    pub(crate) expires_at: Timestamp,
+}
+
+impl Promotion {
+    fn is_active(&self) -> bool {
+        env::block_timestamp() < self.expires_at
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PromotionView {
+    pub(crate) match_bps: u16,
+    pub(crate) cap: U128,
+    pub(crate) budget: U128,
+    pub(crate) matched: U128,
+    // in ms
+    pub(crate) expires_at: Timestamp,
+    pub(crate) active: bool,
+}
+
+impl RaffleDapp {
+    // Takes the match of a newly registered prize out of the promotion's budget, ending the
+    // promotion once it expired
+    pub(crate) fn internal_match_prize(
+        &mut self,
+        raffle_account_id: &AccountId,
+        prize: Balance,
+    ) -> Balance {
+        self.internal_expire_promotion();
+
+        let promotion = match self.promotion.as_mut() {
+            Some(promotion) => promotion,
+            None => return 0,
+        };
+
+        let promo_match = (prize * promotion.match_bps as Balance / BASIS_POINTS as Balance)
+            .min(promotion.cap)
+            .min(promotion.budget);
+        if promo_match == 0 {
+            return 0;
+        }
+
+        promotion.budget -= promo_match;
+        promotion.matched += promo_match;
+        log_event(
+            "prize_matched",
+            json!({
+                "raffle_id": raffle_account_id.to_string(),
+                "amount": U128(promo_match),
+                "budget": U128(promotion.budget),
+            }),
+        );
+        promo_match
+    }
+
+    // Gives the match of a raffle that had no winner back to the promotion, or to the treasury
+    // once the promotion is over
+    pub(crate) fn internal_return_prize_match(&mut self, promo_match: Balance) {
+        if promo_match == 0 {
+            return;
+        }
+
+        match self
+            .promotion
+            .as_mut()
+            .filter(|promotion| promotion.is_active())
+        {
+            Some(promotion) => {
+                promotion.budget += promo_match;
+                promotion.matched -= promo_match;
+            }
+            None => {
+                Promise::new(self.treasury.clone()).transfer(promo_match);
+            }
+        }
+    }
+
+    // Ends the promotion once it expired, its remaining budget going back to the treasury
+    pub(crate) fn internal_expire_promotion(&mut self) {
+        if self
+            .promotion
+            .as_ref()
+            .is_some_and(|promotion| !promotion.is_active())
+        {
+            let promotion = self.promotion.take().unwrap();
+            if promotion.budget > 0 {
+                Promise::new(self.treasury.clone()).transfer(promotion.budget);
+            }
+            log_event(
+                "promotion_ended",
+                json!({
+                    "matched": U128(promotion.matched),
+                    "returned": U128(promotion.budget),
+                }),
+            );
+        }
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Matches `match_bps` basis points of the prizes of the raffles registered from now on, up
+    // to `cap` per raffle, until `duration_days` have passed or the budget runs out. The
+    // attached deposit is the budget, and the one left by a running promotion is carried over.
+    #[payable]
+    pub fn start_promotion(&mut self, match_bps: u16, cap: U128, duration_days: u64) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can run promotions"
+        );
+        self.assert_accepting_raffles();
+
+        assert!(
+            match_bps > 0 && match_bps <= BASIS_POINTS,
+            "The match should be between 1 and {} basis points",
+            BASIS_POINTS
+        );
+        assert!(
+            duration_days > 0,
+            "The promotion should last at least a day"
+        );
+
+        self.internal_expire_promotion();
+        let (budget, matched) = self
+            .promotion
+            .as_ref()
+            .map(|promotion| (promotion.budget, promotion.matched))
+            .unwrap_or_default();
+
+        let promotion = Promotion {
+            match_bps,
+            cap: cap.0,
+            budget: budget + env::attached_deposit(),
+            matched,
+            expires_at: env::block_timestamp() + duration_days * 24 * NANOSECONDS_PER_HOUR,
+        };
+        log_event(
+            "promotion_started",
+            json!({
+                "match_bps": match_bps,
+                "cap": cap,
+                "budget": U128(promotion.budget),
+                "expires_at": promotion.expires_at / TO_FROM_NANOSECONDS,
+            }),
+        );
+        self.promotion = Some(promotion);
+    }
+
+    // Ends the promotion early, its remaining budget going back to the treasury
+    pub fn end_promotion(&mut self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can run promotions"
+        );
+
+        assert!(self.promotion.is_some(), "No promotion is running");
+        self.promotion.as_mut().unwrap().expires_at = env::block_timestamp();
+        self.internal_expire_promotion();
+    }
+
+    pub fn get_promotion(&self) -> Option<PromotionView> {
+        self.promotion.as_ref().map(|promotion| PromotionView {
+            match_bps: promotion.match_bps,
+            cap: U128(promotion.cap),
+            budget: U128(promotion.budget),
+            matched: U128(promotion.matched),
+            expires_at: promotion.expires_at / TO_FROM_NANOSECONDS,
+            active: promotion.is_active(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fees::DEFAULT_CREATION_FEE;
+    use crate::raffle::ORGANIZER_BOND;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_promotion() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.block_timestamp(1644353705000 * TO_FROM_NANOSECONDS);
+        context.attached_deposit(15 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.start_promotion(10_000, U128(10 * ONE_NEAR), 30);
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        // the deposit less the creation fee, the bond and the storage is matched up to the cap
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert_eq!(raffle_details.promo_match, 10 * ONE_NEAR);
+        assert_eq!(
+            raffle_details.prize,
+            27 * ONE_NEAR - DEFAULT_CREATION_FEE - ORGANIZER_BOND - raffle_details.storage_cost
+        );
+        assert_eq!(contract.get_promotion().unwrap().budget, U128(5 * ONE_NEAR));
+
+        // nobody entered, so the match goes back to the budget
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        assert_eq!(
+            contract.get_promotion().unwrap().budget,
+            U128(15 * ONE_NEAR)
+        );
+    }
+
+    #[test]
+    fn check_promotion_expired() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.attached_deposit(15 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.start_promotion(10_000, U128(10 * ONE_NEAR), 1);
+
+        context.block_timestamp(env::block_timestamp() + 25 * NANOSECONDS_PER_HOUR);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(
+            contract
+                .raffles
+                .get(&alice_account_id())
+                .unwrap()
+                .promo_match,
+            0
+        );
+        assert!(contract.get_promotion().is_none());
+    }
+}
@@ -0,0 +1,308 @@
+use crate::raffle::{RaffleDetails, RaffleStore};
+use crate::*;
+use near_sdk::json_types::Base58CryptoHash;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::CryptoHash;
+
+// largest length of the question of a raffle, in bytes
+const MAX_QUESTION_LENGTH: usize = 200;
+
+// Question the entrants of a raffle have to answer, the skill element that many jurisdictions
+// require of promotional draws. Only the sha256 of the answer is stored, so it can't be read
+// from the contract's state.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Quiz {
+    pub(crate) question: String,
+    pub(crate) answer_hash: Base58CryptoHash,
+}
+
+// Rejects the entry points that can't take an answer to the raffle's question
+pub(crate) fn assert_no_quiz(raffle_details: &RaffleDetails) {
+    assert!(
+        raffle_details.quiz.is_none(),
+        "Entries to this raffle answer its question, it can only be entered with `participate`, `join_waitlist` or `buy_ticket`"
+    );
+}
+
+// Whether `answer` is the answer to the question of the quiz
+pub(crate) fn is_correct_answer(quiz: &Quiz, answer: &str) -> bool {
+    let answer_hash: CryptoHash = env::sha256(answer.as_bytes()).try_into().unwrap();
+    CryptoHash::from(quiz.answer_hash) == answer_hash
+}
+
+impl RaffleDapp {
+    // Checks the answer given to the question of the raffle, if it has one
+    pub(crate) fn internal_check_answer(
+        &self,
+        raffle_account_id: &AccountId,
+        answer: Option<String>,
+    ) {
+        let quiz = match self
+            .raffles
+            .get(raffle_account_id)
+            .and_then(|raffle_details| raffle_details.quiz)
+        {
+            Some(quiz) => quiz,
+            None => return,
+        };
+
+        let answer = answer.expect("This raffle requires an answer to its question");
+        assert!(
+            is_correct_answer(&quiz, &answer),
+            "The answer to the question of the raffle is incorrect"
+        );
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Sets the question the entrants have to answer, given with the sha256 of its answer, passing
+    // `None` removes it. It can only be changed before anyone entered. The attached deposit pays
+    // for the storage of the question, which is refunded along with the storage of the raffle.
+    #[payable]
+    pub fn set_quiz(&mut self, raffle_id: String, quiz: Option<Quiz>) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can set its question"
+        );
+        assert!(
+            raffle_details.participants.is_empty(),
+            "The question can only be changed before anyone entered the raffle"
+        );
+        if let Some(quiz) = &quiz {
+            assert!(
+                !quiz.question.is_empty() && quiz.question.len() <= MAX_QUESTION_LENGTH,
+                "The question should be between 1 and {} bytes long",
+                MAX_QUESTION_LENGTH
+            );
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        let is_set = quiz.is_some();
+        raffle_details.quiz = quiz;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        // The storage released by a shorter question is refunded right away
+        let storage_cost = storage_cost_since(initial_storage_usage);
+        let released = (initial_storage_usage.saturating_sub(env::storage_usage())) as Balance
+            * env::storage_byte_cost();
+        assert!(
+            env::attached_deposit() >= storage_cost,
+            "The attached deposit should cover the storage of the question, {} yoctoNEAR",
+            storage_cost
+        );
+        raffle_details.storage_cost = raffle_details.storage_cost + storage_cost - released;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        let refund = env::attached_deposit() - storage_cost + released;
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+
+        env::log_str(&format!(
+            "The question of the raffle {:?} was {}",
+            raffle_account_id.to_string(),
+            if is_set { "set" } else { "removed" }
+        ));
+    }
+
+    // Returns the question the entrants of the raffle have to answer, if any
+    pub fn get_quiz_question(&self, raffle_id: String) -> Option<String> {
+        self.find_raffle(raffle_id)
+            .and_then(|(_, raffle_details)| raffle_details.quiz)
+            .map(|quiz| quiz.question)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn register_quiz_raffle(context: &mut VMContextBuilder, contract: &mut RaffleDapp) {
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(ONE_NEAR / 10);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        let answer_hash: CryptoHash = env::sha256(b"4").try_into().unwrap();
+        contract.set_quiz(
+            alice_account_id().to_string(),
+            Some(Quiz {
+                question: "What is 2 + 2?".to_string(),
+                answer_hash: answer_hash.into(),
+            }),
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+    }
+
+    #[test]
+    fn check_quiz() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_quiz_raffle(&mut context, &mut contract);
+
+        assert_eq!(
+            contract.get_quiz_question(alice_account_id().to_string()),
+            Some("What is 2 + 2?".to_string())
+        );
+
+        contract.participate(
+            alice_account_id().to_string(),
+            None,
+            None,
+            Some("4".to_string()),
+        );
+        assert!(contract
+            .raffles
+            .get(&alice_account_id())
+            .unwrap()
+            .participants
+            .get(&bob_account_id())
+            .is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "The answer to the question of the raffle is incorrect")]
+    fn check_quiz_incorrect_answer() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_quiz_raffle(&mut context, &mut contract);
+
+        contract.participate(
+            alice_account_id().to_string(),
+            None,
+            None,
+            Some("5".to_string()),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "This raffle requires an answer to its question")]
+    fn check_quiz_waitlist_without_answer() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_quiz_raffle(&mut context, &mut contract);
+
+        contract.participate(
+            alice_account_id().to_string(),
+            None,
+            None,
+            Some("4".to_string()),
+        );
+        let mut raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        raffle_details.max_participants = 1;
+        contract.internal_save_raffle(&alice_account_id(), &raffle_details);
+
+        context.predecessor_account_id(jacob_account_id());
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.join_waitlist(alice_account_id().to_string(), None, None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Entries to this raffle answer its question, it can only be entered with `participate`, `join_waitlist` or `buy_ticket`"
+    )]
+    fn check_quiz_transfer_ticket() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_quiz_raffle(&mut context, &mut contract);
+
+        contract.participate(
+            alice_account_id().to_string(),
+            None,
+            None,
+            Some("4".to_string()),
+        );
+
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.transfer_ticket(
+            alice_account_id().to_string(),
+            jacob_account_id().to_string(),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The answer to the question of the raffle is incorrect")]
+    fn check_quiz_buy_ticket_incorrect_answer() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        register_quiz_raffle(&mut context, &mut contract);
+
+        contract.participate(
+            alice_account_id().to_string(),
+            None,
+            None,
+            Some("4".to_string()),
+        );
+
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.list_ticket(alice_account_id().to_string(), U128(3 * ONE_NEAR));
+
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(3 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.buy_ticket(
+            alice_account_id().to_string(),
+            bob_account_id().to_string(),
+            None,
+            Some("5".to_string()),
+        );
+    }
+}
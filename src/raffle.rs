@@ -0,0 +1,1226 @@
+use crate::events::log_event;
+use crate::gates::EntryGate;
+use crate::mystery::MysteryPrize;
+use crate::sealed::SealedPrize;
+use crate::*;
+use near_sdk::collections::{LookupMap, TreeMap, UnorderedMap, UnorderedSet};
+use near_sdk::json_types::{Base58CryptoHash, U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{CryptoHash, PublicKey};
+use serde_json::json;
+
+// upper bound for the number of participants of a single raffle, the winner is drawn
+// from the random seed's bytes so the participants must stay indexable by a u8
+pub(crate) const MAX_PARTICIPANTS: u64 = 256;
+
+// storage the prize of a raffle has to cover for each participant it allows, in bytes: the
+// payout receipt, the pending rating and the audience record kept for every participant once it
+// is settled
+pub(crate) const ENTRY_STORAGE_RESERVE: StorageUsage = 300;
+
+// range of the start and end dates of a raffle, in ms (from September 2001 to year 5138). Dates
+// in seconds fall below it, and dates in microseconds or nanoseconds above it.
+const MIN_TIMESTAMP_MS: Timestamp = 1_000_000_000_000;
+const MAX_TIMESTAMP_MS: Timestamp = 100_000_000_000_000;
+
+// bond taken out of the deposit of the organizers when they register a raffle, returned once
+// it is finalized unless a keeper had to force the finalization
+#[cfg(not(feature = "testnet"))]
+pub(crate) const ORGANIZER_BOND: Balance = ONE_NEAR / 10;
+#[cfg(feature = "testnet")]
+pub(crate) const ORGANIZER_BOND: Balance = ONE_NEAR / 1000;
+
+// largest number of raffles registered by a single call of `register_raffles`
+const MAX_RAFFLES_PER_BATCH: usize = 20;
+
+// Optional behaviors of a raffle, stored as bits of `RaffleDetails::flags`.
+// New modes only need a new bit here instead of a new field on the raffle.
+pub(crate) const FLAG_TRANSFERABLE_TICKETS: u32 = 1 << 0;
+pub(crate) const FLAG_TICKET_RESALE: u32 = 1 << 1;
+pub(crate) const FLAG_WAITLIST: u32 = 1 << 2;
+pub(crate) const FLAG_WITHDRAWALS: u32 = 1 << 3;
+pub(crate) const FLAG_PRIZE_CLAIM_KEYS: u32 = 1 << 4;
+pub(crate) const FLAG_PARTICIPATION_BADGES: u32 = 1 << 5;
+// the odds of each participant are proportional to their locked tokens instead of equal
+pub(crate) const FLAG_WEIGHTED_ODDS: u32 = 1 << 6;
+// accounts can commit their entry before the raffle starts
+pub(crate) const FLAG_PRE_REGISTRATION: u32 = 1 << 7;
+// entries don't lock any tokens, their storage being paid from the sponsorship allowance
+pub(crate) const FLAG_FREE_ENTRY: u32 = 1 << 8;
+
+// names of the flags as exposed to clients
+pub(crate) const RAFFLE_FLAGS: [(u32, &str); 9] = [
+    (FLAG_TRANSFERABLE_TICKETS, "transferable_tickets"),
+    (FLAG_TICKET_RESALE, "ticket_resale"),
+    (FLAG_WAITLIST, "waitlist"),
+    (FLAG_WITHDRAWALS, "withdrawals"),
+    (FLAG_PRIZE_CLAIM_KEYS, "prize_claim_keys"),
+    (FLAG_PARTICIPATION_BADGES, "participation_badges"),
+    (FLAG_WEIGHTED_ODDS, "weighted_odds"),
+    (FLAG_PRE_REGISTRATION, "pre_registration"),
+    (FLAG_FREE_ENTRY, "free_entry"),
+];
+
+// flags enabled when the organizer doesn't specify any
+pub(crate) const DEFAULT_RAFFLE_FLAGS: u32 =
+    FLAG_TRANSFERABLE_TICKETS | FLAG_TICKET_RESALE | FLAG_WAITLIST | FLAG_WITHDRAWALS;
+
+// Settings of a raffle registered with `register_raffles`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RaffleConfig {
+    pub(crate) raffle_id: String,
+    // in ms
+    pub(crate) start: U64,
+    pub(crate) end: U64,
+    pub(crate) max_participants: Option<u64>,
+    pub(crate) flags: Option<u32>,
+    pub(crate) allowlist_root: Option<Base58CryptoHash>,
+    pub(crate) prize_commitment: Option<Base58CryptoHash>,
+    // share of the attached deposit funding the raffle
+    pub(crate) deposit: U128,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct RaffleDetails {
+    pub(crate) owner: AccountId,
+    // account proposed by the owner to take over the raffle, until it accepts
+    pub(crate) pending_owner: Option<AccountId>,
+    pub(crate) prize: Balance,
+    // cost of the storage used by the raffle's record, refunded to the owner once it is settled
+    pub(crate) storage_cost: Balance,
+    pub(crate) start: Timestamp,
+    pub(crate) end: Timestamp,
+    pub(crate) participants: UnorderedMap<AccountId, Balance>,
+    // participants in join order keyed by their position, with the time of their entry, and
+    // the position of each participant
+    pub(crate) entries: TreeMap<u64, (AccountId, Timestamp)>,
+    pub(crate) entry_positions: LookupMap<AccountId, u64>,
+    pub(crate) next_entry_position: u64,
+    // bytes of storage taken by the entries of the participants
+    pub(crate) entries_storage: StorageUsage,
+    pub(crate) max_participants: u64,
+    // accounts waiting for a free slot once the raffle is full, keyed by their position
+    // in the queue together with their escrowed deposit
+    pub(crate) waitlist: TreeMap<u64, (AccountId, Balance)>,
+    pub(crate) waitlist_positions: LookupMap<AccountId, u64>,
+    pub(crate) next_waitlist_position: u64,
+    // tickets put on sale by participants along with their asking price
+    pub(crate) ticket_listings: TreeMap<AccountId, Balance>,
+    // percentage of every ticket resale paid to the owner
+    pub(crate) resale_royalty: u8,
+    // percentage of the locked tokens kept when a participant leaves, added to the prize
+    pub(crate) withdrawal_penalty: u8,
+    pub(crate) attempts: u8,
+    // account allowed to finalize the raffle on the owner's behalf
+    pub(crate) operator: Option<AccountId>,
+    // optional behaviors enabled for the raffle, see `RAFFLE_FLAGS`
+    pub(crate) flags: u32,
+    // whether the raffle shows up in the listing views, hidden raffles stay joinable by id
+    pub(crate) visible: bool,
+    // number of entries per hour, keyed by the hours elapsed since the unix epoch
+    pub(crate) participation_timeline: TreeMap<u64, u64>,
+    // entries committed before the start, which all the participants are until then
+    pub(crate) pre_registered: u64,
+    // set once a participant reclaims their deposit of a raffle that was never finalized
+    pub(crate) abandoned: bool,
+    // root of the Merkle tree of the accounts allowed to enter, anyone can enter if unset
+    pub(crate) allowlist_root: Option<CryptoHash>,
+    // the allowlist only applies for this long after the start, the raffle opening to everyone
+    // afterwards, or for the whole raffle if unset
+    pub(crate) priority_window: Timestamp,
+    // keys registered by participants to claim the prize if they win, instead of receiving it
+    pub(crate) claim_keys: UnorderedMap<AccountId, PublicKey>,
+    // allowance deposited by the owner to pay for the storage of sponsored entries
+    pub(crate) sponsor_pool: Balance,
+    // entries close this long before the end of the raffle
+    pub(crate) entry_cutoff: Timestamp,
+    // the winner is drawn this long after the end of the raffle
+    pub(crate) draw_delay: Timestamp,
+    // block whose random seed draws the winner, set when the raffle is finalized
+    pub(crate) draw_block: Option<BlockHeight>,
+    // account co-signing the payout of prizes of at least `approval_threshold`
+    pub(crate) approver: Option<AccountId>,
+    pub(crate) approval_threshold: Balance,
+    // winner drawn while waiting for the approver
+    pub(crate) pending_winner: Option<AccountId>,
+    pub(crate) bond: Balance,
+    // account that forced the finalization after `FINALIZE_WINDOW`, paid out of the bond
+    pub(crate) keeper: Option<AccountId>,
+    // token the entrants must hold
+    pub(crate) entry_gate: Option<EntryGate>,
+    // question the entrants must answer
+    pub(crate) quiz: Option<Quiz>,
+    // payload encrypted by the organizer for the winner
+    pub(crate) sealed_prize: Option<SealedPrize>,
+    // commitment to the prize, which stays hidden until it is revealed
+    pub(crate) mystery_prize: Option<MysteryPrize>,
+    // description of the off-chain prize, the prize money being kept as a bond until delivered
+    pub(crate) physical_prize: Option<String>,
+    // name of the organizer's series the raffle is an edition of
+    pub(crate) series: Option<String>,
+    // part of the prize matched by the promotion running when the raffle was registered
+    pub(crate) promo_match: Balance,
+    // sha256 of the promo codes entering the raffle without locking tokens, until redeemed
+    pub(crate) promo_codes: UnorderedSet<CryptoHash>,
+    // contract of the NFTs every entrant pools, all of them going to the winner
+    pub(crate) nft_pool: Option<AccountId>,
+    pub(crate) pooled_nfts: UnorderedMap<AccountId, String>,
+    // set when the pooled NFTs are only staked as tickets, every entrant getting theirs back
+    pub(crate) nft_staked: bool,
+    // largest number the participants of a number pick raffle choose from, and their picks
+    pub(crate) pick_range: Option<u8>,
+    pub(crate) picks: UnorderedMap<AccountId, u8>,
+    // prizes won instantly by the entries, and the pool escrowed by the owner to pay them
+    pub(crate) instant_wins: Vec<InstantWinTier>,
+    pub(crate) instant_pool: Balance,
+    // accounts whose entry already rolled for an instant win, so leaving and entering again
+    // doesn't roll again
+    pub(crate) instant_rolls: UnorderedSet<AccountId>,
+    // draws held while the raffle runs, before its final draw
+    pub(crate) draw_schedule: Option<DrawSchedule>,
+    // addresses on foreign chains participants elected to receive the prize on
+    pub(crate) foreign_payouts: UnorderedMap<AccountId, ForeignAddress>,
+}
+
+impl RaffleDetails {
+    // Blank raffle of `owner`, its collections being stored under a prefix derived from its id
+    pub(crate) fn new(
+        raffle_account_id: &AccountId,
+        owner: &AccountId,
+        start: Timestamp,
+        end: Timestamp,
+        max_participants: u64,
+        flags: u32,
+    ) -> Self {
+        let storage_prefix = env::sha256(raffle_account_id.as_bytes());
+        RaffleDetails {
+            owner: owner.clone(),
+            pending_owner: None,
+            prize: 0,
+            storage_cost: 0,
+            start,
+            end,
+            participants: UnorderedMap::new(storage_prefix.clone()),
+            entries: TreeMap::new([storage_prefix.as_slice(), b"e"].concat()),
+            entry_positions: LookupMap::new([storage_prefix.as_slice(), b"j"].concat()),
+            next_entry_position: 0,
+            entries_storage: 0,
+            max_participants,
+            waitlist: TreeMap::new([storage_prefix.as_slice(), b"w"].concat()),
+            waitlist_positions: LookupMap::new([storage_prefix.as_slice(), b"p"].concat()),
+            next_waitlist_position: 0,
+            ticket_listings: TreeMap::new([storage_prefix.as_slice(), b"l"].concat()),
+            resale_royalty: 0,
+            withdrawal_penalty: 0,
+            attempts: 0,
+            operator: None,
+            flags,
+            visible: true,
+            participation_timeline: TreeMap::new([storage_prefix.as_slice(), b"t"].concat()),
+            pre_registered: 0,
+            abandoned: false,
+            allowlist_root: None,
+            priority_window: 0,
+            claim_keys: UnorderedMap::new([storage_prefix.as_slice(), b"k"].concat()),
+            sponsor_pool: 0,
+            entry_cutoff: 0,
+            draw_delay: 0,
+            draw_block: None,
+            approver: None,
+            approval_threshold: 0,
+            pending_winner: None,
+            bond: ORGANIZER_BOND,
+            keeper: None,
+            entry_gate: None,
+            quiz: None,
+            sealed_prize: None,
+            mystery_prize: None,
+            physical_prize: None,
+            series: None,
+            promo_match: 0,
+            promo_codes: UnorderedSet::new([storage_prefix.as_slice(), b"o"].concat()),
+            nft_pool: None,
+            pooled_nfts: UnorderedMap::new([storage_prefix.as_slice(), b"n"].concat()),
+            nft_staked: false,
+            pick_range: None,
+            picks: UnorderedMap::new([storage_prefix.as_slice(), b"m"].concat()),
+            instant_wins: Vec::new(),
+            instant_pool: 0,
+            instant_rolls: UnorderedSet::new([storage_prefix.as_slice(), b"i"].concat()),
+            draw_schedule: None,
+            foreign_payouts: UnorderedMap::new([storage_prefix.as_slice(), b"f"].concat()),
+        }
+    }
+
+    pub(crate) fn has_flag(&self, flag: u32) -> bool {
+        self.flags & flag != 0
+    }
+
+    pub(crate) fn requires_approval(&self) -> bool {
+        self.approver.is_some() && self.prize >= self.approval_threshold
+    }
+
+    // Time from which the raffle doesn't accept new entries
+    pub(crate) fn entries_close(&self) -> Timestamp {
+        self.end - self.entry_cutoff
+    }
+
+    // Time from which the raffle is open to the accounts that aren't on its allowlist, if it has
+    // a priority window
+    pub(crate) fn public_from(&self) -> Option<Timestamp> {
+        (self.priority_window > 0).then_some(self.start + self.priority_window)
+    }
+
+    // Time after which the winner of the raffle can be drawn
+    pub(crate) fn draws_after(&self) -> Timestamp {
+        self.end + self.draw_delay
+    }
+
+    // Adds a participant with `locked_tokens` locked, entered at the current block
+    pub(crate) fn add_entry(&mut self, account_id: &AccountId, locked_tokens: Balance) {
+        let initial_storage_usage = env::storage_usage();
+        self.participants.insert(account_id, &locked_tokens);
+        self.log_entry(account_id, env::block_timestamp());
+        self.track_entries_storage(initial_storage_usage);
+    }
+
+    // Accounts the storage taken or released by the entries since `initial_storage_usage`
+    fn track_entries_storage(&mut self, initial_storage_usage: StorageUsage) {
+        self.entries_storage =
+            (self.entries_storage + env::storage_usage()).saturating_sub(initial_storage_usage);
+    }
+
+    // Appends the entry of a participant made at `entered_at` to the join order
+    pub(crate) fn log_entry(&mut self, account_id: &AccountId, entered_at: Timestamp) {
+        self.entries
+            .insert(&self.next_entry_position, &(account_id.clone(), entered_at));
+        self.entry_positions
+            .insert(account_id, &self.next_entry_position);
+        self.next_entry_position += 1;
+    }
+
+    // Removes a participant along with their pick, claim key and foreign payout, returning their
+    // locked tokens. Those were paid by the participant rather than counted in the storage of
+    // the entries.
+    pub(crate) fn remove_entry(&mut self, account_id: &AccountId) -> Option<Balance> {
+        self.picks.remove(account_id);
+        self.claim_keys.remove(account_id);
+        self.foreign_payouts.remove(account_id);
+        let initial_storage_usage = env::storage_usage();
+        if let Some(position) = self.entry_positions.remove(account_id) {
+            self.entries.remove(&position);
+        }
+        let locked_tokens = self.participants.remove(account_id);
+        self.track_entries_storage(initial_storage_usage);
+        locked_tokens
+    }
+
+    // Hands the entry of `sender_id` over to `receiver_id`, who takes its place in the join
+    // order and its pick. The claim key and foreign payout of `sender_id` are dropped, the
+    // receiver electing its own.
+    // Returns whether `sender_id` had an entry.
+    pub(crate) fn move_entry(&mut self, sender_id: &AccountId, receiver_id: &AccountId) -> bool {
+        let initial_storage_usage = env::storage_usage();
+        let locked_tokens = match self.participants.remove(sender_id) {
+            Some(locked_tokens) => locked_tokens,
+            None => return false,
+        };
+        self.participants.insert(receiver_id, &locked_tokens);
+        if let Some(position) = self.entry_positions.remove(sender_id) {
+            let (_, entered_at) = self.entries.get(&position).unwrap();
+            self.entries
+                .insert(&position, &(receiver_id.clone(), entered_at));
+            self.entry_positions.insert(receiver_id, &position);
+        }
+        self.track_entries_storage(initial_storage_usage);
+        if let Some(number) = self.picks.remove(sender_id) {
+            self.picks.insert(receiver_id, &number);
+        }
+        self.claim_keys.remove(sender_id);
+        self.foreign_payouts.remove(sender_id);
+        true
+    }
+
+    // Counts an entry in the hourly bucket of the current block, and among the pre-registrations
+    // before the start
+    pub(crate) fn record_entry(&mut self) {
+        let hour = env::block_timestamp() / NANOSECONDS_PER_HOUR;
+        let entries = self.participation_timeline.get(&hour).unwrap_or(0);
+        self.participation_timeline.insert(&hour, &(entries + 1));
+        if env::block_timestamp() <= self.start {
+            self.pre_registered += 1;
+        }
+    }
+
+    // Uncounts the pre-registration of a participant leaving before the start
+    pub(crate) fn record_exit(&mut self) {
+        if env::block_timestamp() <= self.start {
+            self.pre_registered -= 1;
+        }
+    }
+
+    // Moves the earliest waitlisted account into the participants if the raffle has a free slot,
+    // returning the promoted account
+    pub(crate) fn promote_from_waitlist(
+        &mut self,
+        raffle_account_id: &AccountId,
+    ) -> Option<AccountId> {
+        if self.participants.len() >= self.max_participants {
+            return None;
+        }
+
+        if let Some(position) = self.waitlist.min() {
+            let (account_id, locked_tokens) = self.waitlist.remove(&position).unwrap();
+            self.waitlist_positions.remove(&account_id);
+            self.add_entry(&account_id, locked_tokens);
+
+            log_event(
+                "waitlist_promoted",
+                json!({
+                    "raffle_id": raffle_account_id.to_string(),
+                    "account_id": account_id.to_string(),
+                }),
+            );
+            return Some(account_id);
+        }
+        None
+    }
+
+    // Empties the waitlist, returning the accounts it held along with their escrowed deposits
+    pub(crate) fn take_waitlist(&mut self) -> Vec<(AccountId, Balance)> {
+        let waitlist: Vec<(AccountId, Balance)> = self
+            .waitlist
+            .iter()
+            .map(|(_, waitlisted)| waitlisted)
+            .collect();
+        for (account_id, _) in waitlist.iter() {
+            self.waitlist_positions.remove(account_id);
+        }
+        self.waitlist.clear();
+        waitlist
+    }
+
+    // Returns the escrowed deposits of every account still on the waitlist
+    pub(crate) fn refund_waitlist(&mut self) {
+        for (account_id, locked_tokens) in self.take_waitlist() {
+            Promise::new(account_id).transfer(locked_tokens);
+        }
+    }
+
+    // Total amount held by the contract for the raffle: prize, storage cost and every deposit
+    pub(crate) fn held_balance(&self) -> Balance {
+        self.prize
+            + self.storage_cost
+            + self.sponsor_pool
+            + self.instant_pool
+            + self
+                .draw_schedule
+                .as_ref()
+                .map_or(0, DrawSchedule::escrowed_prizes)
+            + self.bond
+            + self.sealed_prize_deposit()
+            + self.participants.values().sum::<Balance>()
+            + self
+                .waitlist
+                .iter()
+                .map(|(_, (_, locked_tokens))| locked_tokens)
+                .sum::<Balance>()
+    }
+
+    pub(crate) fn prize_hidden(&self) -> bool {
+        self.mystery_prize
+            .as_ref()
+            .is_some_and(|mystery_prize| !mystery_prize.revealed)
+    }
+
+    // Prize shown by the views, hidden ones being listed as 0
+    pub(crate) fn listed_prize(&self) -> Balance {
+        if self.prize_hidden() {
+            0
+        } else {
+            self.prize
+        }
+    }
+
+    pub(crate) fn sealed_prize_deposit(&self) -> Balance {
+        self.sealed_prize
+            .as_ref()
+            .map_or(0, |sealed_prize| sealed_prize.deposit)
+    }
+
+    // Releases the storage held by the raffle's collections, refunding the waitlisted accounts
+    pub(crate) fn clear_storage(&mut self) {
+        self.refund_waitlist();
+        self.ticket_listings.clear();
+        self.participation_timeline.clear();
+        self.claim_keys.clear();
+        self.promo_codes.clear();
+        self.instant_rolls.clear();
+        self.pooled_nfts.clear();
+        self.picks.clear();
+        self.foreign_payouts.clear();
+        for (_, (account_id, _)) in self.entries.iter() {
+            self.entry_positions.remove(&account_id);
+        }
+        self.entries.clear();
+        self.participants.clear();
+    }
+}
+
+// Storage of the raffles, keeping the secondary indexes used by the listing views and the
+// change feed in sync
+pub(crate) trait RaffleStore {
+    fn internal_store_raffle(
+        &mut self,
+        raffle_account_id: &AccountId,
+        raffle_details: &RaffleDetails,
+    ) -> bool;
+    fn internal_save_raffle(
+        &mut self,
+        raffle_account_id: &AccountId,
+        raffle_details: &RaffleDetails,
+    );
+    fn internal_remove_raffle(&mut self, raffle_account_id: &AccountId);
+}
+
+impl RaffleStore for RaffleDapp {
+    // Stores the raffle and keeps the secondary indexes in sync with it, returning whether it
+    // was already stored. No change is recorded, the caller having recorded it already.
+    fn internal_store_raffle(
+        &mut self,
+        raffle_account_id: &AccountId,
+        raffle_details: &RaffleDetails,
+    ) -> bool {
+        let previous = self.raffles.insert(raffle_account_id, raffle_details);
+        if let Some(previous) = previous.as_ref() {
+            self.unindex_raffle(raffle_account_id, previous);
+        }
+        self.index_raffle(raffle_account_id, raffle_details);
+        previous.is_some()
+    }
+
+    // Stores the raffle and records the change in the change feed
+    fn internal_save_raffle(
+        &mut self,
+        raffle_account_id: &AccountId,
+        raffle_details: &RaffleDetails,
+    ) {
+        let kind = if self.internal_store_raffle(raffle_account_id, raffle_details) {
+            RaffleChangeKind::Updated
+        } else {
+            RaffleChangeKind::Created
+        };
+        self.record_change(raffle_account_id, kind);
+    }
+
+    fn internal_remove_raffle(&mut self, raffle_account_id: &AccountId) {
+        if let Some(raffle_details) = self.raffles.remove(raffle_account_id) {
+            self.unindex_raffle(raffle_account_id, &raffle_details);
+            self.featured_raffles.remove(raffle_account_id);
+            self.raffle_metadata.remove(raffle_account_id);
+            self.record_change(raffle_account_id, RaffleChangeKind::Removed);
+        }
+    }
+}
+
+impl RaffleDapp {
+    fn index_raffle(&mut self, raffle_account_id: &AccountId, raffle_details: &RaffleDetails) {
+        self.raffles_by_prize.insert(
+            &(raffle_details.listed_prize(), raffle_account_id.clone()),
+            &(),
+        );
+        self.raffles_by_end
+            .insert(&(raffle_details.end, raffle_account_id.clone()), &());
+        self.raffles_by_participants.insert(
+            &(raffle_details.participants.len(), raffle_account_id.clone()),
+            &(),
+        );
+        self.prize_liabilities += raffle_details.prize;
+    }
+
+    fn unindex_raffle(&mut self, raffle_account_id: &AccountId, raffle_details: &RaffleDetails) {
+        self.raffles_by_prize
+            .remove(&(raffle_details.listed_prize(), raffle_account_id.clone()));
+        self.raffles_by_end
+            .remove(&(raffle_details.end, raffle_account_id.clone()));
+        self.raffles_by_participants
+            .remove(&(raffle_details.participants.len(), raffle_account_id.clone()));
+        self.prize_liabilities -= raffle_details.prize;
+    }
+
+    // Prizes of the registered raffles plus the pools of the house raffles and of the jackpot,
+    // which the contract's balance must always cover
+    pub(crate) fn liabilities(&self) -> Balance {
+        self.prize_liabilities + self.house_pool + self.jackpot
+    }
+
+    // Balance of the contract that isn't staked for its storage
+    pub(crate) fn available_balance(&self) -> Balance {
+        env::account_balance()
+            .saturating_sub(env::storage_usage() as Balance * env::storage_byte_cost())
+    }
+
+    // Checks that the contract's available balance still covers its liabilities, once a new
+    // prize is recorded
+    pub(crate) fn assert_solvent(&self) {
+        assert!(
+            self.available_balance() >= self.liabilities(),
+            "The contract's balance doesn't cover its prize obligations, no new prize can be accepted"
+        );
+    }
+}
+
+impl RaffleConfig {
+    // Checks the settings, returning the maximum participants and the flags with their defaults
+    pub(crate) fn validate(&self) -> (u64, u32) {
+        assert_ms_timestamp(self.start.0, "start");
+        assert_ms_timestamp(self.end.0, "end");
+        assert!(
+            self.end.0 > self.start.0,
+            "The raffle's end date should be greater than its start date"
+        );
+
+        let max_participants = self.max_participants.unwrap_or(MAX_PARTICIPANTS);
+        assert!(
+            max_participants > 0 && max_participants <= MAX_PARTICIPANTS,
+            "The raffle's maximum participants should be between 1 and {}",
+            MAX_PARTICIPANTS
+        );
+
+        let flags = self.flags.unwrap_or(DEFAULT_RAFFLE_FLAGS);
+        assert_eq!(
+            flags & !RAFFLE_FLAGS.iter().fold(0, |all, (flag, _)| all | flag),
+            0,
+            "Unknown raffle flags"
+        );
+        assert!(
+            flags & FLAG_FREE_ENTRY == 0 || flags & FLAG_WEIGHTED_ODDS == 0,
+            "The odds of a free-entry raffle cannot be weighted by the locked tokens"
+        );
+
+        (max_participants, flags)
+    }
+}
+
+// Checks that a timestamp is in milliseconds, the ones in seconds, microseconds or nanoseconds
+// being far out of the range of dates a raffle can be scheduled at
+fn assert_ms_timestamp(timestamp: Timestamp, name: &str) {
+    assert!(
+        (MIN_TIMESTAMP_MS..MAX_TIMESTAMP_MS).contains(&timestamp),
+        "The raffle's {} date should be in milliseconds, {} is out of range",
+        name,
+        timestamp
+    );
+}
+
+// Parses the id of a new raffle of the caller, which is either the caller's account or one of
+// its sub-accounts
+pub(crate) fn owned_raffle_id(raffle_id: String) -> AccountId {
+    let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+    assert!(
+        raffle_account_id == env::predecessor_account_id()
+            || raffle_account_id
+                .as_str()
+                .ends_with(&format!(".{}", env::predecessor_account_id())),
+        "Raffles can only be registered under your account or its sub-accounts"
+    );
+    raffle_account_id
+}
+
+impl RaffleDapp {
+    // Registers the raffle `raffle_account_id` of `owner`, funded with `deposit`. The settings
+    // of `source` that the config doesn't hold are copied over, before the storage is measured.
+    pub(crate) fn internal_register_raffle(
+        &mut self,
+        raffle_account_id: &AccountId,
+        owner: &AccountId,
+        config: RaffleConfig,
+        deposit: Balance,
+        source: Option<RaffleDetails>,
+    ) {
+        self.assert_accepting_raffles();
+
+        // Check if the deposit is greater than the creation fee, which goes to the treasury.
+        // The exact storage cost of the raffle and the organizer's bond are deducted from it and refunded to the owner once the raffle is settled
+        // Thus, Prize = depost (in NEAR) - creation fee - organizer bond - storage cost
+        let creation_fee = self.fee_config.creation_flat;
+        assert!(
+            deposit > creation_fee + ORGANIZER_BOND,
+            "Prize money should be greater than the creation fee and the organizer bond"
+        );
+
+        // Allowing to register only one raffle per account,
+        // they can register a new raffle after their current raffle ends
+        assert!(
+            self.raffles.get(raffle_account_id).is_none(),
+            "You have already registered a raffle"
+        );
+
+        let (max_participants, flags) = config.validate();
+        let RaffleConfig {
+            start,
+            end,
+            allowlist_root,
+            prize_commitment,
+            ..
+        } = config;
+
+        let initial_storage_usage = env::storage_usage();
+        let mut raffle_details = RaffleDetails::new(
+            raffle_account_id,
+            owner,
+            start.0 * TO_FROM_NANOSECONDS,
+            end.0 * TO_FROM_NANOSECONDS,
+            max_participants,
+            flags,
+        );
+        raffle_details.allowlist_root = allowlist_root.map(CryptoHash::from);
+        raffle_details.mystery_prize = prize_commitment.map(|commitment| MysteryPrize {
+            commitment: commitment.into(),
+            deposit,
+            revealed: false,
+        });
+        raffle_details.series = self.recurring_series.get(owner);
+        if let Some(source) = source {
+            raffle_details.resale_royalty = source.resale_royalty;
+            raffle_details.withdrawal_penalty = source.withdrawal_penalty;
+            raffle_details.operator = source.operator;
+            raffle_details.visible = source.visible;
+            if source.entry_cutoff < raffle_details.end - raffle_details.start {
+                raffle_details.entry_cutoff = source.entry_cutoff;
+            }
+            raffle_details.draw_delay = source.draw_delay;
+            if raffle_details.allowlist_root.is_some()
+                && source.priority_window < raffle_details.end - raffle_details.start
+            {
+                raffle_details.priority_window = source.priority_window;
+            }
+            raffle_details.approver = source.approver;
+            raffle_details.approval_threshold = source.approval_threshold;
+            raffle_details.entry_gate = source.entry_gate;
+            raffle_details.quiz = source.quiz;
+            if raffle_details.mystery_prize.is_none() {
+                raffle_details.physical_prize = source.physical_prize;
+            }
+            raffle_details.series = raffle_details.series.or(source.series);
+        }
+
+        self.internal_save_raffle(raffle_account_id, &raffle_details);
+
+        // The prize doesn't change the size of the record and the creation is already recorded in
+        // the change feed, so the measured cost stays exact
+        let storage_cost = storage_cost_since(initial_storage_usage);
+        assert!(
+            deposit > creation_fee + ORGANIZER_BOND + storage_cost,
+            "Prize money should be greater than the creation fee, the organizer bond and the storage cost"
+        );
+        // The prize stays escrowed until the raffle is settled, so it must be large enough to
+        // back the storage its entries can take up to the participants limit
+        let entries_storage_cost = raffle_details.max_participants as Balance
+            * ENTRY_STORAGE_RESERVE as Balance
+            * env::storage_byte_cost();
+        assert!(
+            deposit > creation_fee + ORGANIZER_BOND + storage_cost + entries_storage_cost,
+            "Prize money should cover the storage of {} participants, lower the participants limit or raise the prize",
+            raffle_details.max_participants
+        );
+        raffle_details.prize = deposit - creation_fee - ORGANIZER_BOND - storage_cost;
+        // Hidden prizes aren't matched, the match would give them away
+        if !raffle_details.prize_hidden() {
+            raffle_details.promo_match =
+                self.internal_match_prize(raffle_account_id, raffle_details.prize);
+            raffle_details.prize += raffle_details.promo_match;
+        }
+        // The prize of the organizer's number pick raffles nobody won rolls over to this one
+        if let Some(rollover) = self.rollovers.remove(owner) {
+            raffle_details.prize += rollover;
+        }
+        raffle_details.storage_cost = storage_cost;
+        self.internal_store_raffle(raffle_account_id, &raffle_details);
+
+        self.internal_collect_fee(creation_fee);
+        self.assert_solvent();
+        self.metrics.register_raffle += 1;
+
+        let prize_money = if raffle_details.prize_hidden() {
+            "a mystery prize".to_string()
+        } else {
+            format!("prize money {:?} NEAR", raffle_details.prize / ONE_NEAR)
+        };
+        env::log_str(&format!(
+            "Raffle registered succesfully for {:?} with {} starting from {:?} ms till {:?} ms",
+            raffle_account_id.to_string(),
+            prize_money,
+            raffle_details.start / TO_FROM_NANOSECONDS,
+            raffle_details.end / TO_FROM_NANOSECONDS
+        ));
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Registers the caller's raffle, starting at the current block unless `start` is given
+    #[payable]
+    pub fn register_raffle(
+        &mut self,
+        start: Option<U64>,
+        end: U64,
+        max_participants: Option<u64>,
+        flags: Option<u32>,
+        allowlist_root: Option<Base58CryptoHash>,
+        prize_commitment: Option<Base58CryptoHash>,
+    ) {
+        self.internal_register_raffle(
+            &env::predecessor_account_id(),
+            &env::predecessor_account_id(),
+            RaffleConfig {
+                raffle_id: env::predecessor_account_id().to_string(),
+                start: start.unwrap_or(U64(env::block_timestamp() / TO_FROM_NANOSECONDS)),
+                end,
+                max_participants,
+                flags,
+                allowlist_root,
+                prize_commitment,
+                deposit: U128(env::attached_deposit()),
+            },
+            env::attached_deposit(),
+            None,
+        );
+    }
+
+    // Registers several raffles of the caller at once, the attached deposit being split across
+    // them as set by their `deposit`. Besides the caller's own account, the raffles are
+    // registered under sub-accounts of the caller, e.g. `booth1.<caller>`.
+    #[payable]
+    pub fn register_raffles(&mut self, configs: Vec<RaffleConfig>) {
+        assert!(
+            !configs.is_empty() && configs.len() <= MAX_RAFFLES_PER_BATCH,
+            "Between 1 and {} raffles can be registered at once",
+            MAX_RAFFLES_PER_BATCH
+        );
+
+        let total_deposit = configs
+            .iter()
+            .map(|config| config.deposit.0)
+            .fold(0, Balance::saturating_add);
+        assert_eq!(
+            env::attached_deposit(),
+            total_deposit,
+            "The attached deposit should be the sum of the deposits of the raffles"
+        );
+
+        for config in configs {
+            let raffle_account_id = owned_raffle_id(config.raffle_id.clone());
+            let deposit = config.deposit.0;
+            self.internal_register_raffle(
+                &raffle_account_id,
+                &env::predecessor_account_id(),
+                config,
+                deposit,
+                None,
+            );
+        }
+    }
+
+    // Registers a new raffle with the settings of the caller's raffle `source_raffle_id`: entry
+    // rules, allowlist, flags, entry gate, operator, approver and series. The new raffle is
+    // funded by the attached deposit and registered as `new_raffle_id`, the caller's account by
+    // default, so the source has to be registered under a sub-account or the new one does.
+    #[payable]
+    pub fn duplicate_raffle(
+        &mut self,
+        source_raffle_id: String,
+        new_start: U64,
+        new_end: U64,
+        new_raffle_id: Option<String>,
+    ) {
+        let source_account_id: AccountId = AccountId::try_from(source_raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&source_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let source = self.raffles.get(&source_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            source.owner,
+            "Only the raffle's owner can duplicate it"
+        );
+
+        let raffle_account_id = owned_raffle_id(
+            new_raffle_id.unwrap_or_else(|| env::predecessor_account_id().to_string()),
+        );
+        self.internal_register_raffle(
+            &raffle_account_id,
+            &env::predecessor_account_id(),
+            RaffleConfig {
+                raffle_id: raffle_account_id.to_string(),
+                start: new_start,
+                end: new_end,
+                max_participants: Some(source.max_participants),
+                flags: Some(source.flags),
+                allowlist_root: source.allowlist_root.map(Base58CryptoHash::from),
+                prize_commitment: None,
+                deposit: U128(env::attached_deposit()),
+            },
+            env::attached_deposit(),
+            Some(source),
+        );
+
+        env::log_str(&format!(
+            "The raffle of {:?} was duplicated from the raffle of {:?}",
+            raffle_account_id.to_string(),
+            source_account_id.to_string()
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fees::DEFAULT_CREATION_FEE;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+
+    #[test]
+    #[should_panic(expected = "Prize money should be greater than the creation fee")]
+    fn check_register_raffles_less_than_two_near() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(DEFAULT_CREATION_FEE);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705130),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The contract's balance doesn't cover its prize obligations")]
+    fn check_register_raffle_insolvent() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        context.account_balance(3 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        let solvency = contract.get_solvency();
+        assert_eq!(
+            solvency.liabilities.0,
+            contract.raffles.get(&alice_account_id()).unwrap().prize
+        );
+        assert!(solvency.balance.0 - solvency.storage_staked.0 >= solvency.liabilities.0);
+
+        // the balance misses the funds of the first raffle, as if they were lost
+        context.predecessor_account_id(bob_account_id());
+        context.account_balance(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Prize money should cover the storage of 256 participants")]
+    fn check_register_raffle_entries_storage() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(DEFAULT_CREATION_FEE + ONE_NEAR / 2);
+        testing_env!(context.build());
+
+        // covers the storage of 10 participants
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705130),
+            Some(10),
+            None,
+            None,
+            None,
+        );
+
+        context.predecessor_account_id(bob_account_id());
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705130),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn check_register_raffles() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(3 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705130),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "You have already registered a raffle")]
+    fn check_register_raffles_already_registered() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(3 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705130),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.register_raffle(
+            Some(U64(1644353705128)),
+            U64(1644353705140),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn check_register_raffles_storage_cost() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(3 * ONE_NEAR);
+        testing_env!(context.build());
+
+        let initial_storage_usage = env::storage_usage();
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705130),
+            None,
+            None,
+            None,
+            None,
+        );
+        let storage_cost =
+            (env::storage_usage() - initial_storage_usage) as Balance * env::storage_byte_cost();
+
+        let raffle_details = contract.raffles.get(&alice_account_id()).unwrap();
+        assert!(storage_cost > 0);
+        assert_eq!(raffle_details.storage_cost, storage_cost);
+        // the registration is a single change of the change feed
+        assert_eq!(contract.get_change_seq(), 1);
+        // the default creation fee goes to the treasury
+        assert_eq!(
+            raffle_details.prize,
+            3 * ONE_NEAR - DEFAULT_CREATION_FEE - ORGANIZER_BOND - storage_cost
+        );
+    }
+
+    #[test]
+    fn check_register_raffles_in_bulk() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(20 * ONE_NEAR);
+        testing_env!(context.build());
+
+        let booth: AccountId = format!("booth1.{}", alice_account_id()).parse().unwrap();
+        contract.register_raffles(
+            [(alice_account_id(), 5), (booth.clone(), 15)]
+                .into_iter()
+                .map(|(raffle_account_id, deposit)| RaffleConfig {
+                    raffle_id: raffle_account_id.to_string(),
+                    start: U64(1644353705121),
+                    end: U64(1644353705130),
+                    max_participants: None,
+                    flags: None,
+                    allowlist_root: None,
+                    prize_commitment: None,
+                    deposit: U128(deposit * ONE_NEAR),
+                })
+                .collect(),
+        );
+
+        let booth_raffle = contract.raffles.get(&booth).unwrap();
+        assert_eq!(booth_raffle.owner, alice_account_id());
+        assert_eq!(
+            booth_raffle.prize,
+            15 * ONE_NEAR - DEFAULT_CREATION_FEE - ORGANIZER_BOND - booth_raffle.storage_cost
+        );
+        assert!(contract.raffles.get(&alice_account_id()).is_some());
+    }
+
+    #[test]
+    fn check_duplicate_raffle() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            Some(10),
+            Some(FLAG_WAITLIST),
+            None,
+            None,
+        );
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.set_entry_cutoff(alice_account_id().to_string(), 0);
+        contract.set_nft_gate(
+            alice_account_id().to_string(),
+            Some(mike_account_id().to_string()),
+        );
+
+        context.attached_deposit(5 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        let booth: AccountId = format!("booth1.{}", alice_account_id()).parse().unwrap();
+        contract.duplicate_raffle(
+            alice_account_id().to_string(),
+            U64(1644353706121),
+            U64(1644353706521),
+            Some(booth.to_string()),
+        );
+
+        let copy = contract.raffles.get(&booth).unwrap();
+        assert_eq!(copy.owner, alice_account_id());
+        assert_eq!(copy.max_participants, 10);
+        assert_eq!(copy.flags, FLAG_WAITLIST);
+        assert!(copy.entry_gate.is_some());
+        assert_eq!(copy.start, 1644353706121 * TO_FROM_NANOSECONDS);
+        assert_eq!(
+            copy.prize,
+            5 * ONE_NEAR - DEFAULT_CREATION_FEE - ORGANIZER_BOND - copy.storage_cost
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown raffle flags")]
+    fn check_register_raffles_unknown_flags() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(3 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705130),
+            None,
+            Some(1 << 31),
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The raffle's start date should be in milliseconds")]
+    fn check_register_raffle_seconds() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn check_register_raffle_starting_now() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+        let mut contract = RaffleDapp::new();
+
+        context.block_timestamp(1644353705121 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(None, U64(1644353705521), None, None, None, None);
+        assert_eq!(
+            contract.raffles.get(&alice_account_id()).unwrap().start,
+            1644353705121 * TO_FROM_NANOSECONDS
+        );
+    }
+}
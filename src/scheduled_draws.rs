@@ -0,0 +1,422 @@
+use crate::events::log_event;
+use crate::finalize::{draw_index, draw_weighted, DRAW_DELAY_BLOCKS};
+use crate::raffle::{RaffleStore, FLAG_WEIGHTED_ODDS};
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use serde_json::json;
+
+// largest number of draws a raffle can schedule before its final one
+const MAX_SCHEDULED_DRAWS: usize = 30;
+
+// Draw held while the raffle runs, paying `prize` to a participant drawn from the ones entered
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScheduledDraw {
+    pub(crate) time: Timestamp,
+    pub(crate) prize: Balance,
+    pub(crate) winner: Option<AccountId>,
+}
+
+// Draws held over the life of a raffle, e.g. a winner every day for a week, before the final
+// draw of its prize. Their prizes are escrowed by the owner when they are scheduled.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DrawSchedule {
+    pub(crate) draws: Vec<ScheduledDraw>,
+    // index of the next draw to hold
+    pub(crate) next_draw: usize,
+    // the winners of a draw can't win the later ones, the final one included
+    pub(crate) exclude_winners: bool,
+    // block whose seed draws the winner of the next draw, once it is due
+    pub(crate) draw_block: Option<BlockHeight>,
+}
+
+// JSON representation of a scheduled draw, `time` is in ms
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScheduledDrawView {
+    pub(crate) time: U64,
+    pub(crate) prize: U128,
+    pub(crate) winner: Option<AccountId>,
+}
+
+impl DrawSchedule {
+    // Prizes of the draws not held yet
+    pub(crate) fn escrowed_prizes(&self) -> Balance {
+        self.draws[self.next_draw..]
+            .iter()
+            .map(|draw| draw.prize)
+            .sum()
+    }
+
+    // Whether `account_id` is excluded from the later draws for having won one
+    pub(crate) fn excludes(&self, account_id: &AccountId) -> bool {
+        self.exclude_winners
+            && self
+                .draws
+                .iter()
+                .any(|draw| draw.winner.as_ref() == Some(account_id))
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Schedules draws held while the raffle runs, given as their time in ms and their prize.
+    // The attached deposit escrows their prizes and pays for the storage of the schedule, the
+    // rest being refunded. It can only be changed while the raffle has no participants. Prizes
+    // of the draws not held by the end of the raffle go to the winner of its final draw.
+    #[payable]
+    pub fn set_draw_schedule(
+        &mut self,
+        raffle_id: String,
+        draws: Vec<(U64, U128)>,
+        exclude_winners: bool,
+    ) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can schedule its draws"
+        );
+
+        assert!(
+            raffle_details.participants.is_empty() && raffle_details.waitlist.is_empty(),
+            "The draws cannot be scheduled once the raffle has participants"
+        );
+        assert!(
+            raffle_details.pick_range.is_none(),
+            "Number pick raffles cannot schedule draws"
+        );
+
+        assert!(
+            draws.len() <= MAX_SCHEDULED_DRAWS,
+            "A raffle cannot schedule more than {} draws",
+            MAX_SCHEDULED_DRAWS
+        );
+        let draws: Vec<ScheduledDraw> = draws
+            .into_iter()
+            .map(|(time, prize)| ScheduledDraw {
+                time: time.0 * TO_FROM_NANOSECONDS,
+                prize: prize.0,
+                winner: None,
+            })
+            .collect();
+        let mut previous_time = raffle_details.start;
+        for draw in &draws {
+            assert!(
+                draw.time > previous_time && draw.time < raffle_details.end,
+                "The draws should be scheduled in order, between the start and the end of the raffle"
+            );
+            assert!(draw.prize > 0, "Every scheduled draw should have a prize");
+            previous_time = draw.time;
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        let previous_prizes = raffle_details
+            .draw_schedule
+            .as_ref()
+            .map(DrawSchedule::escrowed_prizes)
+            .unwrap_or(0);
+        raffle_details.draw_schedule = (!draws.is_empty()).then_some(DrawSchedule {
+            draws,
+            next_draw: 0,
+            exclude_winners,
+            draw_block: None,
+        });
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        // Storage released by a smaller schedule is left in the storage cost, returned at the end
+        let storage_cost = storage_cost_since(initial_storage_usage);
+        let prizes = raffle_details
+            .draw_schedule
+            .as_ref()
+            .map(DrawSchedule::escrowed_prizes)
+            .unwrap_or(0);
+        assert!(
+            env::attached_deposit() + previous_prizes >= storage_cost + prizes,
+            "The attached deposit should cover the prizes of the draws and the storage of the schedule, {} yoctoNEAR",
+            storage_cost + prizes - previous_prizes
+        );
+        raffle_details.storage_cost += storage_cost;
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        let refund = env::attached_deposit() + previous_prizes - storage_cost - prizes;
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+
+        env::log_str(&format!(
+            "The raffle {:?} has {} scheduled draws with {} yoctoNEAR of prizes",
+            raffle_account_id.to_string(),
+            raffle_details
+                .draw_schedule
+                .as_ref()
+                .map_or(0, |schedule| schedule.draws.len()),
+            prizes
+        ));
+    }
+
+    // Holds the next scheduled draw of the raffle once it is due. Like the final draw, its winner
+    // is drawn from the seed of a block at least `DRAW_DELAY_BLOCKS` after the one it became due
+    // in, so the first call commits to that block and a call in a later block draws the winner.
+    // Anyone can call it.
+    pub fn run_scheduled_draw(&mut self, raffle_id: String) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "Sorry, no raffle is being conducted by {:?}",
+            raffle_account_id.to_string()
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        let mut schedule = raffle_details
+            .draw_schedule
+            .clone()
+            .expect("The raffle has no scheduled draws");
+        assert!(
+            schedule.next_draw < schedule.draws.len(),
+            "Every scheduled draw of the raffle was held"
+        );
+        assert!(
+            env::block_timestamp() >= schedule.draws[schedule.next_draw].time,
+            "The next draw of the raffle is not due yet"
+        );
+
+        let draw_block = match schedule.draw_block {
+            Some(draw_block) => draw_block,
+            None => {
+                let draw_block = env::block_height() + DRAW_DELAY_BLOCKS;
+                schedule.draw_block = Some(draw_block);
+                raffle_details.draw_schedule = Some(schedule);
+                self.internal_save_raffle(&raffle_account_id, &raffle_details);
+                env::log_str(&format!(
+                    "The winner of the draw will be drawn from the random seed of the block {:?} or a later one",
+                    draw_block
+                ));
+                return;
+            }
+        };
+        assert!(
+            env::block_height() >= draw_block,
+            "The winner of the draw can be drawn from the block {:?} on",
+            draw_block
+        );
+
+        let candidates: Vec<(AccountId, Balance)> = raffle_details
+            .participants
+            .iter()
+            .filter(|(account_id, _)| !schedule.excludes(account_id))
+            .collect();
+        assert!(
+            !candidates.is_empty(),
+            "No participant can win the draw yet"
+        );
+
+        let random_seed = env::random_seed();
+        let draw = if raffle_details.has_flag(FLAG_WEIGHTED_ODDS) {
+            let weights: Vec<Balance> = candidates
+                .iter()
+                .map(|(_, locked_tokens)| *locked_tokens)
+                .collect();
+            draw_weighted(&random_seed, &weights)
+        } else {
+            draw_index(&random_seed, candidates.len())
+        };
+        let random_index = match draw {
+            Some(random_index) => random_index,
+            None => {
+                env::log_str(
+                    "Failed to discover Random index in this block, the draw can be retried in a later block",
+                );
+                return;
+            }
+        };
+
+        let winner_account_id = candidates[random_index].0.clone();
+        let draw_number = schedule.next_draw;
+        let prize = schedule.draws[draw_number].prize;
+        schedule.draws[draw_number].winner = Some(winner_account_id.clone());
+        schedule.next_draw += 1;
+        schedule.draw_block = None;
+        raffle_details.draw_schedule = Some(schedule);
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+        Promise::new(winner_account_id.clone()).transfer(prize);
+
+        log_event(
+            "scheduled_draw",
+            json!({
+                "raffle_id": raffle_account_id.to_string(),
+                "draw": draw_number,
+                "winner": winner_account_id.to_string(),
+                "prize": U128(prize),
+            }),
+        );
+    }
+
+    pub fn get_draw_schedule(&self, raffle_id: String) -> Vec<ScheduledDrawView> {
+        self.find_raffle(raffle_id)
+            .and_then(|(_, raffle_details)| raffle_details.draw_schedule)
+            .map(|schedule| {
+                schedule
+                    .draws
+                    .into_iter()
+                    .map(|draw| ScheduledDrawView {
+                        time: U64(draw.time / TO_FROM_NANOSECONDS),
+                        prize: U128(draw.prize),
+                        winner: draw.winner,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_scheduled_draws() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(3 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.set_draw_schedule(
+            alice_account_id().to_string(),
+            vec![
+                (U64(1644353705200), U128(ONE_NEAR)),
+                (U64(1644353705300), U128(ONE_NEAR)),
+            ],
+            true,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        // the first call commits to a later block, whose seed draws the winner
+        context.block_timestamp(1644353705200 * TO_FROM_NANOSECONDS);
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.run_scheduled_draw(alice_account_id().to_string());
+        assert!(
+            contract.get_draw_schedule(alice_account_id().to_string())[0]
+                .winner
+                .is_none()
+        );
+
+        context.block_index(env::block_height() + DRAW_DELAY_BLOCKS);
+        context.random_seed(vec![0; 32]);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.run_scheduled_draw(alice_account_id().to_string());
+        let draws = contract.get_draw_schedule(alice_account_id().to_string());
+        assert_eq!(draws[0].winner, Some(bob_account_id()));
+        assert_eq!(
+            contract
+                .raffles
+                .get(&alice_account_id())
+                .unwrap()
+                .draw_schedule
+                .unwrap()
+                .escrowed_prizes(),
+            ONE_NEAR
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "No participant can win the draw yet")]
+    fn check_scheduled_draws_exclude_winners() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(3 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.set_draw_schedule(
+            alice_account_id().to_string(),
+            vec![
+                (U64(1644353705200), U128(ONE_NEAR)),
+                (U64(1644353705300), U128(ONE_NEAR)),
+            ],
+            true,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        // bob wins the first draw, and is the only participant left for the second one
+        for time in [1644353705200, 1644353705300] {
+            context.block_timestamp(time * TO_FROM_NANOSECONDS);
+            context.attached_deposit(0);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+            contract.run_scheduled_draw(alice_account_id().to_string());
+
+            context.block_index(env::block_height() + DRAW_DELAY_BLOCKS);
+            context.random_seed(vec![0; 32]);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+            contract.run_scheduled_draw(alice_account_id().to_string());
+        }
+    }
+}
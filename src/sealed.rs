@@ -0,0 +1,187 @@
+use crate::raffle::RaffleStore;
+use crate::*;
+
+// largest payload an organizer can seal for the winner, in bytes
+const MAX_SEALED_PRIZE_LENGTH: usize = 1_024;
+
+// Payload encrypted for the winner (e.g. a gift card code), along with the deposit paying for
+// its storage
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SealedPrize {
+    pub(crate) payload: String,
+    pub(crate) deposit: Balance,
+}
+
+impl RaffleDapp {
+    // Hands the sealed prize of a settled raffle over to its winner, the deposit of the payload
+    // paying for its storage from then on
+    pub(crate) fn internal_unseal_prize(
+        &mut self,
+        raffle_account_id: &AccountId,
+        sealed_prize: SealedPrize,
+        winner_account_id: &AccountId,
+    ) {
+        self.sealed_prizes.insert(
+            &(raffle_account_id.clone(), winner_account_id.clone()),
+            &sealed_prize.payload,
+        );
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Attaches a payload encrypted by the organizer to the raffle, which only its winner can
+    // retrieve once it is settled. The attached deposit pays for the payload's storage, the
+    // excess being refunded, and a previous payload is replaced.
+    #[payable]
+    pub fn set_sealed_prize(&mut self, raffle_id: String, payload: String) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can seal a prize"
+        );
+
+        assert!(
+            raffle_details.draw_block.is_none(),
+            "The sealed prize cannot be changed once the raffle is being finalized"
+        );
+
+        assert!(
+            payload.len() <= MAX_SEALED_PRIZE_LENGTH,
+            "The sealed prize cannot exceed {} bytes",
+            MAX_SEALED_PRIZE_LENGTH
+        );
+
+        // The previous payload is dropped first, so only the new one is charged
+        let mut refund = env::attached_deposit();
+        if let Some(sealed_prize) = raffle_details.sealed_prize.take() {
+            refund += sealed_prize.deposit;
+            self.internal_save_raffle(&raffle_account_id, &raffle_details);
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        raffle_details.sealed_prize = Some(SealedPrize {
+            payload,
+            deposit: 0,
+        });
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        // The deposit doesn't change the size of the record and the update is already recorded in
+        // the change feed, so the measured cost stays exact
+        let storage_cost = storage_cost_since(initial_storage_usage);
+        assert!(
+            refund >= storage_cost,
+            "The attached deposit should cover the storage cost of the sealed prize"
+        );
+        raffle_details.sealed_prize.as_mut().unwrap().deposit = storage_cost;
+        self.internal_store_raffle(&raffle_account_id, &raffle_details);
+
+        if refund > storage_cost {
+            Promise::new(env::predecessor_account_id()).transfer(refund - storage_cost);
+        }
+
+        env::log_str(&format!(
+            "A sealed prize was attached to the raffle of {:?}",
+            raffle_account_id.to_string()
+        ));
+    }
+
+    // Returns the payload sealed for the caller as the winner of the raffle. View calls don't
+    // know their caller, so this is a call checking that the predecessor is the winner.
+    pub fn reveal_sealed_prize(&mut self, raffle_id: String) -> String {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        let payload = self
+            .sealed_prizes
+            .get(&(raffle_account_id, env::predecessor_account_id()));
+        assert!(
+            payload.is_some(),
+            "Only the winner of the raffle can reveal its sealed prize"
+        );
+        payload.unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_sealed_prize() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.set_sealed_prize(alice_account_id().to_string(), "c2VhbGVk".to_string());
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.finalize_raffle(alice_account_id().to_string());
+        complete_draw(&mut context, &mut contract, alice_account_id());
+
+        context.predecessor_account_id(bob_account_id());
+        testing_env!(context.build());
+
+        assert_eq!(
+            contract.reveal_sealed_prize(alice_account_id().to_string()),
+            "c2VhbGVk"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the winner of the raffle can reveal its sealed prize")]
+    fn check_reveal_sealed_prize_not_winner() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(jacob_account_id());
+        testing_env!(context.build());
+
+        contract.reveal_sealed_prize(alice_account_id().to_string());
+    }
+}
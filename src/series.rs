@@ -0,0 +1,364 @@
+use crate::raffle::{RaffleDetails, RaffleStore};
+use crate::views::{decode_cursor, Page};
+use crate::*;
+use near_sdk::json_types::{Base64VecU8, U128};
+use near_sdk::serde::Serialize;
+
+// longest name of a series, in bytes
+const MAX_SERIES_NAME_LENGTH: usize = 64;
+
+// largest number of accounts returned by a single call of `get_series_leaderboard`
+const MAX_LEADERBOARD_LIMIT: u64 = 100;
+
+// Place of a standing in the leaderboard of a series: its wins, entries and prizes won, then the
+// account id
+type SeriesRank = (u64, u64, Balance, AccountId);
+
+// Recurring raffles of an organizer grouped under a name, each settled raffle being an edition
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Series {
+    pub(crate) editions: u64,
+    pub(crate) total_prizes: Balance,
+    // whether the next raffles of the organizer automatically join the series
+    pub(crate) recurring: bool,
+    pub(crate) standings: UnorderedMap<AccountId, SeriesStanding>,
+    pub(crate) ranking: TreeMap<SeriesRank, ()>,
+}
+
+impl Series {
+    // Applies `update` to the standing of `account_id`, moving it to its new place in the ranking
+    pub(crate) fn update_standing(
+        &mut self,
+        account_id: &AccountId,
+        update: impl FnOnce(&mut SeriesStanding),
+    ) {
+        let mut standing = self.standings.get(account_id).unwrap_or_default();
+        self.ranking.remove(&standing.rank(account_id));
+        update(&mut standing);
+        self.ranking.insert(&standing.rank(account_id), &());
+        self.standings.insert(account_id, &standing);
+    }
+}
+
+// Results of an account across the editions of a series
+#[derive(BorshDeserialize, BorshSerialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeriesStanding {
+    pub(crate) entries: u64,
+    pub(crate) wins: u64,
+    pub(crate) prizes_won: U128,
+}
+
+impl SeriesStanding {
+    fn rank(&self, account_id: &AccountId) -> SeriesRank {
+        (
+            self.wins,
+            self.entries,
+            self.prizes_won.0,
+            account_id.clone(),
+        )
+    }
+}
+
+impl Default for SeriesStanding {
+    fn default() -> Self {
+        Self {
+            entries: 0,
+            wins: 0,
+            prizes_won: U128(0),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeriesView {
+    pub(crate) owner: AccountId,
+    pub(crate) name: String,
+    pub(crate) editions: u64,
+    pub(crate) total_prizes: U128,
+    pub(crate) recurring: bool,
+    // edition open right now, the organizer's raffle if it is part of the series
+    pub(crate) current_raffle: Option<AccountId>,
+}
+
+impl RaffleDapp {
+    // Records the settled raffle as an edition of its series, if it is part of one
+    pub(crate) fn record_series_edition(
+        &mut self,
+        raffle_detail: &RaffleDetails,
+        winner: Option<(&AccountId, Balance)>,
+    ) {
+        let key = match raffle_detail.series.as_ref() {
+            Some(name) => (raffle_detail.owner.clone(), name.clone()),
+            None => return,
+        };
+        let mut series = match self.series.get(&key) {
+            Some(series) => series,
+            None => return,
+        };
+
+        series.editions += 1;
+        for account_id in raffle_detail.participants.keys() {
+            series.update_standing(&account_id, |standing| standing.entries += 1);
+        }
+        if let Some((winner_account_id, prize)) = winner {
+            series.total_prizes += prize;
+            series.update_standing(winner_account_id, |standing| {
+                standing.wins += 1;
+                standing.prizes_won = U128(standing.prizes_won.0 + prize);
+            });
+        }
+        self.series.insert(&key, &series);
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Adds the caller's raffle to their series `name`, creating it if needed. A recurring series
+    // also takes in the next raffles the caller registers, each of them being the next edition.
+    pub fn add_to_series(&mut self, raffle_id: String, name: String, recurring: bool) {
+        let raffle_account_id: AccountId = AccountId::try_from(raffle_id).unwrap();
+
+        assert!(
+            self.raffles.get(&raffle_account_id).is_some(),
+            "No raffle registered from this account"
+        );
+
+        let mut raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+        assert_eq!(
+            env::predecessor_account_id(),
+            raffle_details.owner,
+            "Only the raffle's owner can add it to a series"
+        );
+
+        assert!(
+            !name.is_empty() && name.len() <= MAX_SERIES_NAME_LENGTH,
+            "The name of the series should be between 1 and {} bytes",
+            MAX_SERIES_NAME_LENGTH
+        );
+
+        let key = (raffle_details.owner.clone(), name.clone());
+        let mut series = self.series.get(&key).unwrap_or_else(|| {
+            let storage_prefix = env::sha256(format!("{}:{}", key.0, key.1).as_bytes());
+            Series {
+                editions: 0,
+                total_prizes: 0,
+                recurring,
+                standings: UnorderedMap::new([storage_prefix.as_slice(), b"n"].concat()),
+                ranking: TreeMap::new([storage_prefix.as_slice(), b"r"].concat()),
+            }
+        });
+        series.recurring = recurring;
+        self.series.insert(&key, &series);
+
+        // Only one series of the organizer takes in their next raffles
+        if recurring {
+            self.recurring_series.insert(&raffle_details.owner, &name);
+        } else if self.recurring_series.get(&raffle_details.owner) == Some(name.clone()) {
+            self.recurring_series.remove(&raffle_details.owner);
+        }
+
+        raffle_details.series = Some(name.clone());
+        self.internal_save_raffle(&raffle_account_id, &raffle_details);
+
+        env::log_str(&format!(
+            "The raffle of {:?} is now part of the series {:?}",
+            raffle_account_id.to_string(),
+            name
+        ));
+    }
+
+    pub fn get_series(&self, owner: String, name: String) -> Option<SeriesView> {
+        let owner: AccountId = AccountId::try_from(owner).ok()?;
+
+        self.series
+            .get(&(owner.clone(), name.clone()))
+            .map(|series| SeriesView {
+                current_raffle: self
+                    .raffles
+                    .get(&owner)
+                    .filter(|raffle_details| raffle_details.series.as_ref() == Some(&name))
+                    .map(|_| owner.clone()),
+                owner,
+                name,
+                editions: series.editions,
+                total_prizes: U128(series.total_prizes),
+                recurring: series.recurring,
+            })
+    }
+
+    // Returns the accounts that entered the series, ranked by wins, then by entries and prizes
+    // won, the ties being broken by account id in descending order
+    pub fn get_series_leaderboard(
+        &self,
+        owner: String,
+        name: String,
+        cursor: Option<Base64VecU8>,
+        limit: Option<u64>,
+    ) -> Page<(AccountId, SeriesStanding)> {
+        let owner: AccountId = match AccountId::try_from(owner) {
+            Ok(owner) => owner,
+            Err(_) => return Page::empty(),
+        };
+        let series = match self.series.get(&(owner, name)) {
+            Some(series) => series,
+            None => return Page::empty(),
+        };
+        let limit = limit
+            .unwrap_or(MAX_LEADERBOARD_LIMIT)
+            .min(MAX_LEADERBOARD_LIMIT);
+
+        let ranking: Box<dyn Iterator<Item = (SeriesRank, ())>> = match decode_cursor(cursor) {
+            Ok(Some(after)) => Box::new(series.ranking.iter_rev_from(after)),
+            Ok(None) => Box::new(series.ranking.iter_rev()),
+            Err(_) => return Page::empty(),
+        };
+        Page::collect(
+            ranking.map(|(rank, _)| {
+                let standing = series.standings.get(&rank.3).unwrap();
+                (rank.clone(), (rank.3, standing))
+            }),
+            limit,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_series() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        // two editions, the second one joining the recurring series on its own
+        for (start, end) in [
+            (1644353705121, 1644353705521),
+            (1644353706121, 1644353706521),
+        ] {
+            context.block_timestamp((start - 100) * TO_FROM_NANOSECONDS);
+            context.predecessor_account_id(alice_account_id());
+            context.attached_deposit(17 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.register_raffle(Some(U64(start)), U64(end), None, None, None, None);
+            if contract
+                .get_series(alice_account_id().to_string(), "weekly".to_string())
+                .is_none()
+            {
+                contract.add_to_series(alice_account_id().to_string(), "weekly".to_string(), true);
+            }
+            assert_eq!(
+                contract
+                    .get_series(alice_account_id().to_string(), "weekly".to_string())
+                    .unwrap()
+                    .current_raffle,
+                Some(alice_account_id())
+            );
+
+            context.block_timestamp((start + 4) * TO_FROM_NANOSECONDS);
+            context.predecessor_account_id(bob_account_id());
+            context.attached_deposit(2 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+
+            context.block_timestamp((end + 79) * TO_FROM_NANOSECONDS);
+            context.predecessor_account_id(alice_account_id());
+            context.attached_deposit(0);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.finalize_raffle(alice_account_id().to_string());
+            complete_draw(&mut context, &mut contract, alice_account_id());
+        }
+
+        let series = contract
+            .get_series(alice_account_id().to_string(), "weekly".to_string())
+            .unwrap();
+        assert_eq!(series.editions, 2);
+        assert!(series.current_raffle.is_none());
+
+        let leaderboard = contract
+            .get_series_leaderboard(
+                alice_account_id().to_string(),
+                "weekly".to_string(),
+                None,
+                None,
+            )
+            .items;
+        assert_eq!(leaderboard.len(), 1);
+        assert_eq!(leaderboard[0].0, bob_account_id());
+        assert_eq!(leaderboard[0].1.wins, 2);
+        assert_eq!(leaderboard[0].1.entries, 2);
+        assert_eq!(series.total_prizes, leaderboard[0].1.prizes_won);
+    }
+
+    #[test]
+    fn check_series_leaderboard_pages() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.add_to_series(alice_account_id().to_string(), "weekly".to_string(), false);
+
+        // bob and jacob are tied ahead of mike, who entered once less
+        let key = (alice_account_id(), "weekly".to_string());
+        let mut series = contract.series.get(&key).unwrap();
+        for (account_id, entries) in [
+            (mike_account_id(), 1),
+            (bob_account_id(), 2),
+            (jacob_account_id(), 2),
+        ] {
+            series.update_standing(&account_id, |standing| standing.entries = entries);
+        }
+        contract.series.insert(&key, &series);
+
+        let page = contract.get_series_leaderboard(
+            alice_account_id().to_string(),
+            "weekly".to_string(),
+            None,
+            Some(2),
+        );
+        let ranked: Vec<AccountId> = page
+            .items
+            .into_iter()
+            .map(|(account_id, _)| account_id)
+            .collect();
+        assert_eq!(ranked, vec![jacob_account_id(), bob_account_id()]);
+
+        let page = contract.get_series_leaderboard(
+            alice_account_id().to_string(),
+            "weekly".to_string(),
+            page.next_cursor,
+            Some(2),
+        );
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].0, mike_account_id());
+        assert!(page.next_cursor.is_none());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::*;
+use near_sdk::serde::Serialize;
+
+// Standards implemented by the contract, listed in its source metadata
+const STANDARDS: [(&str, &str); 2] = [("nep330", "1.2.0"), ("nep297", "1.0.0")];
+
+// NEP-330 description of the source the deployed wasm was built from. The fields are set at build
+// time from the `NEP330_*` environment variables exported by `build.sh`, falling back to the
+// package's metadata.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractSourceMetadata {
+    pub(crate) version: Option<String>,
+    pub(crate) link: Option<String>,
+    pub(crate) standards: Vec<Standard>,
+    pub(crate) build_info: Option<BuildInfo>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Standard {
+    pub(crate) standard: String,
+    pub(crate) version: String,
+}
+
+// How the wasm was built, so that it can be rebuilt from the same source and compared
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BuildInfo {
+    pub(crate) build_environment: String,
+    pub(crate) build_command: Vec<String>,
+    pub(crate) contract_path: String,
+    // repository and commit of the source, e.g. `git+https://github.com/org/repo?rev=<commit>`
+    pub(crate) source_code_snapshot: String,
+    pub(crate) output_wasm_path: Option<String>,
+}
+
+// Value of a build-time variable, `None` when it wasn't set or is empty
+fn build_var(value: Option<&str>) -> Option<String> {
+    value.filter(|value| !value.is_empty()).map(str::to_string)
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    pub fn contract_source_metadata(&self) -> ContractSourceMetadata {
+        // The build info is only known for the builds recording the commit they were made from
+        let build_info = build_var(option_env!("NEP330_BUILD_INFO_SOURCE_CODE_SNAPSHOT")).map(
+            |source_code_snapshot| BuildInfo {
+                build_environment: build_var(option_env!("NEP330_BUILD_INFO_BUILD_ENVIRONMENT"))
+                    .unwrap_or_default(),
+                build_command: build_var(option_env!("NEP330_BUILD_INFO_BUILD_COMMAND"))
+                    .map(|command| command.split(' ').map(str::to_string).collect())
+                    .unwrap_or_default(),
+                contract_path: build_var(option_env!("NEP330_BUILD_INFO_CONTRACT_PATH"))
+                    .unwrap_or_default(),
+                source_code_snapshot,
+                output_wasm_path: build_var(option_env!("NEP330_BUILD_INFO_OUTPUT_WASM_PATH")),
+            },
+        );
+
+        ContractSourceMetadata {
+            version: build_var(option_env!("NEP330_VERSION"))
+                .or_else(|| build_var(Some(env!("CARGO_PKG_VERSION")))),
+            link: build_var(option_env!("NEP330_LINK"))
+                .or_else(|| build_var(Some(env!("CARGO_PKG_REPOSITORY")))),
+            standards: STANDARDS
+                .iter()
+                .map(|(standard, version)| Standard {
+                    standard: standard.to_string(),
+                    version: version.to_string(),
+                })
+                .collect(),
+            build_info,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_contract_source_metadata() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let contract = RaffleDapp::new();
+
+        let metadata = contract.contract_source_metadata();
+        assert_eq!(
+            metadata.version,
+            Some(env!("CARGO_PKG_VERSION").to_string())
+        );
+        assert_eq!(
+            metadata.link,
+            Some("https://github.com/arquam-ejaz/raffle-dapp".to_string())
+        );
+        assert!(metadata
+            .standards
+            .iter()
+            .any(|standard| standard.standard == "nep330"));
+    }
+}
@@ -0,0 +1,253 @@
+use crate::events::log_event;
+use crate::finalize::FINALIZE_WINDOW;
+use crate::raffle::RaffleStore;
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use serde_json::json;
+
+// number of raffles wound down by a single call unless a limit is given
+const DEFAULT_SUNSET_LIMIT: u64 = 10;
+
+// Phases of the retirement of the deployment, started by the contract account
+#[derive(BorshDeserialize, BorshSerialize, Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SunsetPhase {
+    // No new raffles can be registered, the active ones are being wound down
+    Sunsetting,
+    // Every raffle was wound down and the balances were reconciled, the state is final
+    Decommissioned,
+}
+
+impl RaffleDapp {
+    pub(crate) fn assert_accepting_raffles(&self) {
+        assert!(
+            self.sunset.is_none(),
+            "The contract is being retired, no new raffles can be registered"
+        );
+        self.assert_not_exporting();
+    }
+
+    pub(crate) fn assert_not_decommissioned(&self) {
+        assert_ne!(
+            self.sunset,
+            Some(SunsetPhase::Decommissioned),
+            "The contract is decommissioned"
+        );
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Starts retiring the deployment: new raffles can no longer be registered, the running
+    // promotion ends and the active raffles are wound down with `sunset_raffles`
+    pub fn start_sunset(&mut self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can retire the contract"
+        );
+        assert!(
+            self.sunset.is_none(),
+            "The contract is already being retired"
+        );
+
+        self.sunset = Some(SunsetPhase::Sunsetting);
+        if let Some(promotion) = self.promotion.as_mut() {
+            promotion.expires_at = env::block_timestamp();
+            self.internal_expire_promotion();
+        }
+        self.house_config = None;
+        self.jackpot_config = None;
+
+        log_event("sunset_started", json!({ "raffles": self.raffles.len() }));
+    }
+
+    // Winds down up to `limit` raffles of a contract being retired. Raffles still running are
+    // cancelled and their participants refunded, ended ones are finalized, a later call
+    // completing their draw. Raffles waiting for the approval of their draw or for the reveal of
+    // their mystery prize are left to their approver and organizer. Anyone can call it.
+    pub fn sunset_raffles(&mut self, limit: Option<u64>) {
+        assert_eq!(
+            self.sunset,
+            Some(SunsetPhase::Sunsetting),
+            "The contract isn't being retired"
+        );
+
+        let now = env::block_timestamp();
+        let raffle_account_ids: Vec<AccountId> = self
+            .raffles_by_end
+            .iter()
+            .map(|((_, raffle_account_id), _)| raffle_account_id)
+            .filter(|raffle_account_id| {
+                let raffle_detail = self.raffles.get(raffle_account_id).unwrap();
+                let awaiting_reveal = raffle_detail.prize_hidden()
+                    && now > raffle_detail.end
+                    && now <= raffle_detail.end + FINALIZE_WINDOW;
+                raffle_detail.pending_winner.is_none() && !awaiting_reveal
+            })
+            .take(limit.unwrap_or(DEFAULT_SUNSET_LIMIT) as usize)
+            .collect();
+
+        let mut processed = 0;
+        for raffle_account_id in raffle_account_ids {
+            if env::prepaid_gas() - env::used_gas() < self.gas_config.min_finalize_call {
+                break;
+            }
+            let mut raffle_detail = self.raffles.get(&raffle_account_id).unwrap();
+            if now > raffle_detail.end {
+                self.internal_finalize_raffle(&raffle_account_id, false);
+            } else {
+                raffle_detail.abandoned = true;
+                self.internal_save_raffle(&raffle_account_id, &raffle_detail);
+                self.internal_close_raffle(&raffle_account_id);
+            }
+            processed += 1;
+        }
+
+        env::log_str(&format!(
+            "{} raffle(s) processed, {} left",
+            processed,
+            self.raffles.len()
+        ));
+    }
+
+    // Completes the retirement once every raffle was wound down: the pools of the house raffles
+    // and of the jackpot go to the treasury and the contract no longer accepts changes. Prizes
+    // escrowed for claim keys and prizes rolled over stay withdrawable by their owners.
+    pub fn decommission(&mut self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can retire the contract"
+        );
+        assert_eq!(
+            self.sunset,
+            Some(SunsetPhase::Sunsetting),
+            "The contract isn't being retired"
+        );
+        assert!(
+            self.raffles.is_empty(),
+            "Every raffle has to be wound down before the contract is decommissioned"
+        );
+
+        let reconciled = self.house_pool + self.jackpot;
+        self.house_pool = 0;
+        self.jackpot = 0;
+        if reconciled > 0 {
+            Promise::new(self.treasury.clone()).transfer(reconciled);
+        }
+        self.sunset = Some(SunsetPhase::Decommissioned);
+
+        log_event(
+            "contract_decommissioned",
+            json!({
+                "treasury": self.treasury.to_string(),
+                "reconciled": U128(reconciled),
+            }),
+        );
+    }
+
+    // Sends the prize rolled over by the number pick raffles of the caller, who can no longer
+    // register the raffle it was meant for once the contract is being retired or migrated
+    pub fn withdraw_rollover(&mut self) {
+        assert!(
+            self.sunset.is_some() || self.export_target.is_some(),
+            "Rolled over prizes go to the next raffle until the contract is retired"
+        );
+
+        let account_id = env::predecessor_account_id();
+        let amount = self
+            .rollovers
+            .remove(&account_id)
+            .unwrap_or_else(|| env::panic_str("No prize rolled over for this account"));
+        Promise::new(account_id.clone()).transfer(amount);
+
+        env::log_str(&format!(
+            "{} yoctoNEAR rolled over were withdrawn by {:?}",
+            amount,
+            account_id.to_string()
+        ));
+    }
+
+    pub fn get_sunset_phase(&self) -> Option<SunsetPhase> {
+        self.sunset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_sunset() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        context.predecessor_account_id(raffle_dapp_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.start_sunset();
+        contract.sunset_raffles(None);
+        assert!(contract.raffles.is_empty());
+
+        contract.decommission();
+        assert_eq!(
+            contract.get_sunset_phase(),
+            Some(SunsetPhase::Decommissioned)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The contract is being retired, no new raffles can be registered")]
+    fn check_sunset_registration() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+        contract.start_sunset();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+}
@@ -0,0 +1,51 @@
+// Helpers shared by the unit tests of every module
+use crate::finalize::DRAW_DELAY_BLOCKS;
+use crate::RaffleDapp;
+use near_sdk::test_utils::VMContextBuilder;
+use near_sdk::{env, testing_env, AccountId};
+
+// Setting up a mock context with current account as 'raffle-dapp.testnet'
+pub(crate) fn get_context() -> VMContextBuilder {
+    let mut builder = VMContextBuilder::new();
+    builder.current_account_id(raffle_dapp_account_id());
+    builder
+}
+
+pub(crate) fn raffle_dapp_account_id() -> AccountId {
+    AccountId::new_unchecked("raffle-dapp.testnet".to_string())
+}
+
+pub(crate) fn alice_account_id() -> AccountId {
+    AccountId::new_unchecked("alice.testnet".to_string())
+}
+
+pub(crate) fn bob_account_id() -> AccountId {
+    AccountId::new_unchecked("bob.testnet".to_string())
+}
+
+pub(crate) fn jacob_account_id() -> AccountId {
+    AccountId::new_unchecked("jacob.testnet".to_string())
+}
+
+pub(crate) fn mike_account_id() -> AccountId {
+    AccountId::new_unchecked("mike.testnet".to_string())
+}
+
+pub(crate) fn jack_account_id() -> AccountId {
+    AccountId::new_unchecked("jack.testnet".to_string())
+}
+
+// Runs the call to `finalize_raffle` the contract makes to itself once the block whose seed
+// draws the winner is reached
+pub(crate) fn complete_draw(
+    context: &mut VMContextBuilder,
+    contract: &mut RaffleDapp,
+    raffle_account_id: AccountId,
+) {
+    context.block_index(env::block_height() + DRAW_DELAY_BLOCKS);
+    context.predecessor_account_id(raffle_dapp_account_id());
+    context.storage_usage(env::storage_usage());
+    testing_env!(context.build());
+
+    contract.finalize_raffle(raffle_account_id.to_string());
+}
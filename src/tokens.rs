@@ -0,0 +1,90 @@
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+
+// NEP-141 token accepted by the contract, with the number of decimals of its amounts and the
+// least amount of it a raffle can require
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AcceptedToken {
+    pub(crate) decimals: u8,
+    pub(crate) min_amount: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AcceptedTokenView {
+    pub(crate) contract_id: AccountId,
+    pub(crate) decimals: u8,
+    pub(crate) min_amount: U128,
+}
+
+impl RaffleDapp {
+    // Checks that the token is accepted, returning its settings
+    pub(crate) fn assert_accepted_token(&self, contract_id: &AccountId) -> AcceptedToken {
+        let token = self.accepted_tokens.get(contract_id);
+        assert!(
+            token.is_some(),
+            "The token {:?} is not accepted",
+            contract_id.to_string()
+        );
+        token.unwrap()
+    }
+
+    // Checks that the token is accepted and that `amount` of it is at least its minimum
+    pub(crate) fn assert_accepted_amount(&self, contract_id: &AccountId, amount: Balance) {
+        let token = self.assert_accepted_token(contract_id);
+        assert!(
+            amount >= token.min_amount.0,
+            "The amount of {:?} should be at least {} ({} decimals)",
+            contract_id.to_string(),
+            token.min_amount.0,
+            token.decimals
+        );
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Adds the NEP-141 token `contract_id` to the accepted tokens, or updates it. Passing `None`
+    // removes it, raffles already restricted to its holders can no longer be entered.
+    pub fn set_accepted_token(&mut self, contract_id: String, token: Option<AcceptedToken>) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can change the accepted tokens"
+        );
+        self.assert_not_decommissioned();
+
+        let contract_id: AccountId = AccountId::try_from(contract_id).unwrap();
+        match token {
+            Some(token) => {
+                env::log_str(&format!(
+                    "The token {:?} is accepted from {} ({} decimals)",
+                    contract_id.to_string(),
+                    token.min_amount.0,
+                    token.decimals
+                ));
+                self.accepted_tokens.insert(&contract_id, &token);
+            }
+            None => {
+                self.accepted_tokens.remove(&contract_id);
+                env::log_str(&format!(
+                    "The token {:?} is no longer accepted",
+                    contract_id.to_string()
+                ));
+            }
+        }
+    }
+
+    pub fn get_accepted_tokens(&self) -> Vec<AcceptedTokenView> {
+        self.accepted_tokens
+            .iter()
+            .map(|(contract_id, token)| AcceptedTokenView {
+                contract_id,
+                decimals: token.decimals,
+                min_amount: token.min_amount,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,64 @@
+use crate::*;
+use near_sdk::json_types::Base58CryptoHash;
+use near_sdk::CryptoHash;
+
+#[near_bindgen]
+impl RaffleDapp {
+    // Deploys `code` (passed as borsh since it is raw wasm) on the contract account and calls
+    // `migrate` on the new version in the same batch, so the upgrade is rolled back if the
+    // state can't be migrated. Only the contract account (e.g. a DAO it is controlled by) can
+    // upgrade the contract.
+    pub fn upgrade(&mut self, #[serializer(borsh)] code: Vec<u8>) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Only the contract account can upgrade the contract"
+        );
+        self.assert_not_decommissioned();
+
+        let code_hash: CryptoHash = env::sha256(&code).try_into().unwrap();
+        env::log_str(&format!(
+            "Upgrading the contract to the code with hash {}",
+            String::from(&Base58CryptoHash::from(code_hash))
+        ));
+
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call(
+                "migrate".to_string(),
+                Vec::new(),
+                0,
+                self.gas_config.migrate,
+            );
+    }
+
+    // Reads the state left by the previous version. Fields added to the contract later on are
+    // initialized here, after reading the state with the layout of the previous version.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        env::state_read().unwrap_or_else(|| env::panic_str("The contract isn't initialized"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+
+    #[test]
+    #[should_panic(expected = "Only the contract account can upgrade the contract")]
+    fn check_upgrade_not_owner() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        testing_env!(context.build());
+
+        contract.upgrade(vec![0, 97, 115, 109]);
+    }
+}
@@ -0,0 +1,1516 @@
+use crate::admin::{MAX_RESALE_ROYALTY, MAX_WITHDRAWAL_PENALTY};
+use crate::claims::{CLAIM_KEY_ALLOWANCE, PRIZE_CLAIM_EXPIRY};
+use crate::finalize::{FINALIZE_WINDOW, SWEEP_EXPIRY};
+use crate::participation::{InternalParticipation, MIN_LOCKED_AMOUNT, RECLAIM_GRACE_PERIOD};
+use crate::raffle::{
+    RaffleDetails, ENTRY_STORAGE_RESERVE, FLAG_WEIGHTED_ODDS, MAX_PARTICIPANTS, ORGANIZER_BOND,
+    RAFFLE_FLAGS,
+};
+use crate::*;
+use near_sdk::collections::TreeMap;
+use near_sdk::json_types::{Base64VecU8, U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use std::ops::Bound;
+
+// default and maximum page size of the listing views
+pub(crate) const DEFAULT_PAGE_LIMIT: u64 = 50;
+pub(crate) const MAX_PAGE_LIMIT: u64 = 100;
+
+// Page of a listing view. `next_cursor` is set when the page is full, and passing it back
+// returns the items following the last one of the page. A cursor that can't be decoded returns
+// an empty page.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Page<T> {
+    pub(crate) items: Vec<T>,
+    pub(crate) next_cursor: Option<Base64VecU8>,
+}
+
+impl<T> Page<T> {
+    pub(crate) fn empty() -> Self {
+        Page {
+            items: Vec::new(),
+            next_cursor: None,
+        }
+    }
+
+    // Collects up to `limit` items keyed by their position in the listing's ordering. The cursor
+    // is the encoding of the key of the last item, so the next page resumes right after it even
+    // when items were added or removed in between, without skipping or repeating any.
+    pub(crate) fn collect<K: BorshSerialize>(
+        items: impl Iterator<Item = (K, T)>,
+        limit: u64,
+    ) -> Self {
+        let mut page = Page {
+            items: Vec::new(),
+            next_cursor: None,
+        };
+        let mut last_key = None;
+        for (key, item) in items.take(limit as usize) {
+            page.items.push(item);
+            last_key = Some(key);
+        }
+        if page.items.len() as u64 == limit {
+            page.next_cursor = last_key.map(|key| Base64VecU8(key.try_to_vec().unwrap()));
+        }
+        page
+    }
+}
+
+// Key of the last item of the previous page, decoded from its cursor, an error if it is invalid
+pub(crate) fn decode_cursor<K: BorshDeserialize>(
+    cursor: Option<Base64VecU8>,
+) -> Result<Option<K>, std::io::Error> {
+    cursor
+        .map(|cursor| K::try_from_slice(&cursor.0))
+        .transpose()
+}
+
+// Keys of an index of the raffles, in ascending or descending order, from the one following
+// `after`
+fn index_after<'a, K>(
+    index: &'a TreeMap<(K, AccountId), ()>,
+    descending: bool,
+    after: Option<(K, AccountId)>,
+) -> Box<dyn Iterator<Item = (K, AccountId)> + 'a>
+where
+    K: Ord + Clone + BorshSerialize + BorshDeserialize,
+{
+    match (after, descending) {
+        (None, false) => Box::new(index.iter().map(|(key, _)| key)),
+        (None, true) => Box::new(index.iter_rev().map(|(key, _)| key)),
+        (Some(after), false) => Box::new(index.iter_from(after).map(|(key, _)| key)),
+        (Some(after), true) => Box::new(index.iter_rev_from(after).map(|(key, _)| key)),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum RaffleSortKey {
+    Prize,
+    End,
+    Participants,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum RaffleStatus {
+    // the raffle has not started yet
+    Upcoming,
+    // the raffle is accepting participants
+    Active,
+    // the raffle has ended and is waiting to be finalized
+    Ended,
+    // the raffle was never finalized and its participants started reclaiming their deposits
+    Abandoned,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum TimelineInterval {
+    Hour,
+    Day,
+}
+
+// number of entries made during the interval starting at `start`, in ms
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TimelineBucket {
+    start: Timestamp,
+    entries: u64,
+}
+
+// JSON representation of a raffle returned by the view methods, timestamps are in ms
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RaffleView {
+    pub(crate) raffle_id: AccountId,
+    owner: AccountId,
+    // `None` for mystery prizes until they are revealed
+    pub(crate) prize: Option<U128>,
+    start: U64,
+    end: U64,
+    // no entries are accepted from this time on
+    entries_close: U64,
+    // the winner is drawn after this time
+    draws_after: U64,
+    // accounts that aren't on the allowlist can enter from this time on
+    public_from: Option<U64>,
+    participants: u64,
+    // entries committed before the start
+    pre_registered: u64,
+    max_participants: u64,
+    status: RaffleStatus,
+    // token the entrants must hold
+    entry_gate: Option<EntryGate>,
+    // question the entrants must answer
+    question: Option<String>,
+    // off-chain prize the winner gets instead of the prize money
+    physical_prize: Option<String>,
+    // the odds of each participant are proportional to their locked tokens instead of equal
+    weighted_odds: bool,
+}
+
+// fees collected by the contract and the share donated to the public-goods account
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TreasuryView {
+    pub(crate) treasury: AccountId,
+    pub(crate) fees_collected: U128,
+    pub(crate) fees_donated: U128,
+    pub(crate) donation_account: Option<AccountId>,
+    pub(crate) donation_bps: u16,
+}
+
+// entry of a participant, `entered_at` is in ms
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EntryView {
+    pub(crate) account_id: AccountId,
+    pub(crate) locked_tokens: U128,
+    pub(crate) entered_at: U64,
+}
+
+// storage taken by a raffle in bytes, and its cost
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageUsageView {
+    // the raffle's record and metadata, paid by the organizer
+    pub(crate) record: StorageUsage,
+    // the entries of the participants, paid by each of them
+    pub(crate) entries: StorageUsage,
+    pub(crate) total: StorageUsage,
+    pub(crate) cost: U128,
+}
+
+// balance of the contract next to the prizes and pools it owes
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SolvencyView {
+    pub(crate) balance: U128,
+    // part of the balance staked for the contract's storage
+    pub(crate) storage_staked: U128,
+    pub(crate) liabilities: U128,
+}
+
+// draw of a raffle waiting for the approval of its approver
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingDrawView {
+    pub(crate) winner: AccountId,
+    pub(crate) prize: U128,
+    pub(crate) approver: AccountId,
+}
+
+// expired raffle waiting to be swept to the treasury, `end` is in ms
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingSweep {
+    pub(crate) raffle_id: AccountId,
+    pub(crate) end: U64,
+    pub(crate) amount: U128,
+}
+
+// amounts charged by the contract, in yoctoNEAR
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeeConfigView {
+    // taken from the deposit of every registration
+    pub(crate) creation_flat: U128,
+    // share of the prize taken when a winner is drawn, in basis points
+    pub(crate) prize_bps: u16,
+    // taken from the prize and paid to whoever finalizes the raffle
+    pub(crate) finalize_bounty: U128,
+    // fee schedule proposed to replace this one
+    pub(crate) pending: Option<PendingFeeConfigView>,
+    pub(crate) min_locked_amount: U128,
+    // deducted from the prize for the raffle's storage and charged for each entry's storage
+    pub(crate) storage_byte_cost: U128,
+    // deducted from the prize when it is escrowed for a claim key
+    pub(crate) claim_key_allowance: U128,
+    // taken from the deposit of every registration and returned when the raffle is finalized
+    pub(crate) organizer_bond: U128,
+}
+
+// `effective_at` is the time (in ms) from which the fee schedule can be applied
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingFeeConfigView {
+    pub(crate) creation_flat: U128,
+    pub(crate) prize_bps: u16,
+    pub(crate) finalize_bounty: U128,
+    pub(crate) effective_at: Timestamp,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EntryRateLimitView {
+    pub(crate) max_entries: u32,
+    pub(crate) window: Timestamp,
+}
+
+// caps and delays enforced by the contract, durations are in ms
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LimitsView {
+    pub(crate) max_participants: u64,
+    pub(crate) max_resale_royalty: u8,
+    pub(crate) max_withdrawal_penalty: u8,
+    pub(crate) entry_rate_limit: Option<EntryRateLimitView>,
+    pub(crate) free_entry_limit: Option<EntryRateLimitView>,
+    pub(crate) reclaim_grace_period: Timestamp,
+    pub(crate) sweep_expiry: Timestamp,
+    pub(crate) prize_claim_expiry: Timestamp,
+    pub(crate) finalize_window: Timestamp,
+    pub(crate) max_page_limit: u64,
+    // storage in bytes the prize has to cover for each participant a raffle allows
+    pub(crate) entry_storage_reserve: StorageUsage,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BundleView {
+    pub(crate) raffle_ids: Vec<AccountId>,
+    pub(crate) per_raffle_amount: U128,
+}
+
+// chance of an account to win a raffle: `tickets` in `total_tickets`
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OddsView {
+    pub(crate) tickets: u64,
+    pub(crate) total_tickets: u64,
+}
+
+impl RaffleDapp {
+    // Raffle registered under `raffle_id`, `None` if it isn't a valid account id or no raffle is
+    // registered under it, so that the views answer `null` rather than failing
+    pub(crate) fn find_raffle(&self, raffle_id: String) -> Option<(AccountId, RaffleDetails)> {
+        let raffle_account_id = AccountId::try_from(raffle_id).ok()?;
+        let raffle_details = self.raffles.get(&raffle_account_id)?;
+        Some((raffle_account_id, raffle_details))
+    }
+
+    fn raffle_status(raffle_details: &RaffleDetails) -> RaffleStatus {
+        let current_timestamp = env::block_timestamp();
+        if raffle_details.abandoned {
+            RaffleStatus::Abandoned
+        } else if current_timestamp <= raffle_details.start {
+            RaffleStatus::Upcoming
+        } else if current_timestamp < raffle_details.end {
+            RaffleStatus::Active
+        } else {
+            RaffleStatus::Ended
+        }
+    }
+
+    pub(crate) fn raffle_view(
+        raffle_account_id: AccountId,
+        raffle_details: &RaffleDetails,
+    ) -> RaffleView {
+        RaffleView {
+            raffle_id: raffle_account_id,
+            owner: raffle_details.owner.clone(),
+            prize: (!raffle_details.prize_hidden()).then_some(U128(raffle_details.prize)),
+            start: U64(raffle_details.start / TO_FROM_NANOSECONDS),
+            end: U64(raffle_details.end / TO_FROM_NANOSECONDS),
+            entries_close: U64(raffle_details.entries_close() / TO_FROM_NANOSECONDS),
+            draws_after: U64(raffle_details.draws_after() / TO_FROM_NANOSECONDS),
+            public_from: raffle_details
+                .public_from()
+                .map(|public_from| U64(public_from / TO_FROM_NANOSECONDS)),
+            participants: raffle_details.participants.len(),
+            pre_registered: raffle_details.pre_registered,
+            max_participants: raffle_details.max_participants,
+            status: Self::raffle_status(raffle_details),
+            entry_gate: raffle_details.entry_gate.clone(),
+            question: raffle_details
+                .quiz
+                .as_ref()
+                .map(|quiz| quiz.question.clone()),
+            physical_prize: raffle_details.physical_prize.clone(),
+            weighted_odds: raffle_details.has_flag(FLAG_WEIGHTED_ODDS),
+        }
+    }
+
+    // View of the raffle of the given index key, `None` if it is hidden
+    fn visible_raffle_view<K>(&self, key: (K, AccountId)) -> Option<((K, AccountId), RaffleView)> {
+        let raffle_details = self.raffles.get(&key.1).unwrap();
+        if !raffle_details.visible {
+            return None;
+        }
+        let view = Self::raffle_view(key.1.clone(), &raffle_details);
+        Some((key, view))
+    }
+
+    // Page of the visible raffles of an index, from the key following the cursor
+    fn sorted_raffles<K>(
+        &self,
+        index: &TreeMap<(K, AccountId), ()>,
+        descending: bool,
+        cursor: Option<Base64VecU8>,
+        limit: u64,
+    ) -> Page<RaffleView>
+    where
+        K: Ord + Clone + BorshSerialize + BorshDeserialize,
+    {
+        let after = match decode_cursor(cursor) {
+            Ok(after) => after,
+            Err(_) => return Page::empty(),
+        };
+        Page::collect(
+            index_after(index, descending, after).filter_map(|key| self.visible_raffle_view(key)),
+            limit,
+        )
+    }
+}
+
+#[near_bindgen]
+impl RaffleDapp {
+    pub fn get_fee_config(&self) -> FeeConfigView {
+        FeeConfigView {
+            creation_flat: U128(self.fee_config.creation_flat),
+            prize_bps: self.fee_config.prize_bps,
+            finalize_bounty: U128(self.fee_config.finalize_bounty),
+            pending: self
+                .pending_fee_config
+                .as_ref()
+                .map(|pending| PendingFeeConfigView {
+                    creation_flat: U128(pending.fee_config.creation_flat),
+                    prize_bps: pending.fee_config.prize_bps,
+                    finalize_bounty: U128(pending.fee_config.finalize_bounty),
+                    effective_at: pending.effective_at / TO_FROM_NANOSECONDS,
+                }),
+            min_locked_amount: U128(MIN_LOCKED_AMOUNT),
+            storage_byte_cost: U128(env::storage_byte_cost()),
+            claim_key_allowance: U128(CLAIM_KEY_ALLOWANCE),
+            organizer_bond: U128(ORGANIZER_BOND),
+        }
+    }
+
+    pub fn get_treasury(&self) -> TreasuryView {
+        TreasuryView {
+            treasury: self.treasury.clone(),
+            fees_collected: U128(self.fees_collected),
+            fees_donated: U128(self.fees_donated),
+            donation_account: self
+                .donation
+                .as_ref()
+                .map(|donation| donation.account.clone()),
+            donation_bps: self.donation.as_ref().map_or(0, |donation| donation.bps),
+        }
+    }
+
+    pub fn get_solvency(&self) -> SolvencyView {
+        SolvencyView {
+            balance: U128(env::account_balance()),
+            storage_staked: U128(env::account_balance() - self.available_balance()),
+            liabilities: U128(self.liabilities()),
+        }
+    }
+
+    pub fn get_limits(&self) -> LimitsView {
+        LimitsView {
+            max_participants: MAX_PARTICIPANTS,
+            max_resale_royalty: MAX_RESALE_ROYALTY,
+            max_withdrawal_penalty: MAX_WITHDRAWAL_PENALTY,
+            entry_rate_limit: self
+                .entry_rate_limit
+                .as_ref()
+                .map(|rate_limit| EntryRateLimitView {
+                    max_entries: rate_limit.max_entries,
+                    window: rate_limit.window / TO_FROM_NANOSECONDS,
+                }),
+            free_entry_limit: self
+                .free_entry_limit
+                .as_ref()
+                .map(|rate_limit| EntryRateLimitView {
+                    max_entries: rate_limit.max_entries,
+                    window: rate_limit.window / TO_FROM_NANOSECONDS,
+                }),
+            reclaim_grace_period: RECLAIM_GRACE_PERIOD / TO_FROM_NANOSECONDS,
+            sweep_expiry: SWEEP_EXPIRY / TO_FROM_NANOSECONDS,
+            prize_claim_expiry: PRIZE_CLAIM_EXPIRY / TO_FROM_NANOSECONDS,
+            finalize_window: FINALIZE_WINDOW / TO_FROM_NANOSECONDS,
+            max_page_limit: MAX_PAGE_LIMIT,
+            entry_storage_reserve: ENTRY_STORAGE_RESERVE,
+        }
+    }
+
+    // Returns the bundles held by `account_id`, limited to the raffles it is still entered in
+    pub fn get_bundles(&self, account_id: String) -> Vec<BundleView> {
+        let account_id: AccountId = match AccountId::try_from(account_id) {
+            Ok(account_id) => account_id,
+            Err(_) => return Vec::new(),
+        };
+
+        self.bundles
+            .get(&account_id)
+            .unwrap_or_default()
+            .iter()
+            .map(|bundle| BundleView {
+                raffle_ids: self.active_bundle_raffles(&account_id, bundle),
+                per_raffle_amount: U128(bundle.per_raffle_amount),
+            })
+            .filter(|bundle| !bundle.raffle_ids.is_empty())
+            .collect()
+    }
+
+    // Lists the participants of the raffle in the order they entered it, with the time of their
+    // entry. Tickets that changed hands keep their place, and accounts promoted from the
+    // waitlist enter when they are promoted.
+    pub fn get_entries(
+        &self,
+        raffle_id: String,
+        cursor: Option<Base64VecU8>,
+        limit: Option<u64>,
+    ) -> Option<Page<EntryView>> {
+        let (_, raffle_details) = self.find_raffle(raffle_id)?;
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+        let entries: Box<dyn Iterator<Item = (u64, (AccountId, Timestamp))>> =
+            match decode_cursor(cursor) {
+                Ok(Some(position)) => Box::new(raffle_details.entries.iter_from(position)),
+                Ok(None) => Box::new(raffle_details.entries.iter()),
+                Err(_) => return Some(Page::empty()),
+            };
+        Some(Page::collect(
+            entries.map(|(position, (account_id, entered_at))| {
+                (
+                    position,
+                    EntryView {
+                        locked_tokens: U128(raffle_details.participants.get(&account_id).unwrap()),
+                        account_id,
+                        entered_at: U64(entered_at / TO_FROM_NANOSECONDS),
+                    },
+                )
+            }),
+            limit,
+        ))
+    }
+
+    // Returns the storage taken by the raffle, so organizers see what the storage part of their
+    // deposit pays for and the contract account can spot storage-heavy raffles
+    pub fn get_storage_usage(&self, raffle_id: String) -> Option<StorageUsageView> {
+        let (_, raffle_details) = self.find_raffle(raffle_id)?;
+        let record = (raffle_details.storage_cost / env::storage_byte_cost()) as StorageUsage;
+        let total = record + raffle_details.entries_storage;
+        Some(StorageUsageView {
+            record,
+            entries: raffle_details.entries_storage,
+            total,
+            cost: U128(total as Balance * env::storage_byte_cost()),
+        })
+    }
+
+    // Lists the tickets of the raffle on sale with their price, in order of their seller's id
+    pub fn get_ticket_listings(
+        &self,
+        raffle_id: String,
+        cursor: Option<Base64VecU8>,
+        limit: Option<u64>,
+    ) -> Option<Page<(AccountId, U128)>> {
+        let (_, raffle_details) = self.find_raffle(raffle_id)?;
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+        let listings: Box<dyn Iterator<Item = (AccountId, Balance)>> = match decode_cursor(cursor) {
+            Ok(Some(after)) => Box::new(raffle_details.ticket_listings.iter_from(after)),
+            Ok(None) => Box::new(raffle_details.ticket_listings.iter()),
+            Err(_) => return Some(Page::empty()),
+        };
+        Some(Page::collect(
+            listings.map(|(account_id, price)| (account_id.clone(), (account_id, U128(price)))),
+            limit,
+        ))
+    }
+
+    pub fn get_pending_draw(&self, raffle_id: String) -> Option<PendingDrawView> {
+        let (_, raffle_details) = self.find_raffle(raffle_id)?;
+        let winner = raffle_details.pending_winner?;
+        Some(PendingDrawView {
+            winner,
+            prize: U128(raffle_details.prize),
+            approver: raffle_details.approver?,
+        })
+    }
+
+    // Returns the chance of `account_id` to win the raffle, every entry being a single ticket.
+    // In a number pick raffle, a pick wins with a chance of one in the range of the numbers,
+    // whatever the other picks. With weighted odds, the tickets are the locked tokens, scaled
+    // down alike when their total doesn't fit in a u64.
+    pub fn get_odds(&self, raffle_id: String, account_id: String) -> Option<OddsView> {
+        let (_, raffle_details) = self.find_raffle(raffle_id)?;
+        let account_id: AccountId = AccountId::try_from(account_id).ok()?;
+
+        let entered = raffle_details.participants.get(&account_id).is_some();
+        let odds = match raffle_details.pick_range {
+            Some(max_number) => OddsView {
+                tickets: (entered && raffle_details.picks.get(&account_id).is_some()) as u64,
+                total_tickets: max_number as u64,
+            },
+            None if raffle_details.has_flag(FLAG_WEIGHTED_ODDS) => {
+                let total: Balance = raffle_details.participants.values().sum();
+                let scale = total / u64::MAX as Balance + 1;
+                OddsView {
+                    tickets: (raffle_details.participants.get(&account_id).unwrap_or(0) / scale)
+                        as u64,
+                    total_tickets: (total / scale) as u64,
+                }
+            }
+            None => OddsView {
+                tickets: entered as u64,
+                total_tickets: raffle_details.participants.len(),
+            },
+        };
+        Some(odds)
+    }
+
+    // Returns the names of the optional behaviors enabled for the raffle
+    pub fn get_raffle_flags(&self, raffle_id: String) -> Option<Vec<String>> {
+        let (_, raffle_details) = self.find_raffle(raffle_id)?;
+        Some(
+            RAFFLE_FLAGS
+                .iter()
+                .filter(|(flag, _)| raffle_details.has_flag(*flag))
+                .map(|(_, name)| name.to_string())
+                .collect(),
+        )
+    }
+
+    // Returns the number of entries of the raffle per hour, or per day, in chronological order.
+    // Intervals without any entry are left out.
+    pub fn get_participation_timeline(
+        &self,
+        raffle_id: String,
+        interval: Option<TimelineInterval>,
+    ) -> Option<Vec<TimelineBucket>> {
+        let (_, raffle_details) = self.find_raffle(raffle_id)?;
+        let hours_per_bucket = match interval.unwrap_or(TimelineInterval::Hour) {
+            TimelineInterval::Hour => 1,
+            TimelineInterval::Day => 24,
+        };
+
+        let mut timeline: Vec<TimelineBucket> = Vec::new();
+        for (hour, entries) in raffle_details.participation_timeline.iter() {
+            let start = hour / hours_per_bucket * hours_per_bucket * NANOSECONDS_PER_HOUR
+                / TO_FROM_NANOSECONDS;
+            match timeline.last_mut() {
+                Some(bucket) if bucket.start == start => bucket.entries += entries,
+                _ => timeline.push(TimelineBucket { start, entries }),
+            }
+        }
+        Some(timeline)
+    }
+
+    // Lists the expired raffles whose funds can be swept to the treasury, in order of their end
+    pub fn get_pending_sweeps(
+        &self,
+        cursor: Option<Base64VecU8>,
+        limit: Option<u64>,
+    ) -> Page<PendingSweep> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+        let after = match decode_cursor(cursor) {
+            Ok(after) => after,
+            Err(_) => return Page::empty(),
+        };
+
+        Page::collect(
+            self.expired_raffles(after).map(|(end, raffle_account_id)| {
+                let raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+                (
+                    (end, raffle_account_id.clone()),
+                    PendingSweep {
+                        raffle_id: raffle_account_id,
+                        end: U64(end / TO_FROM_NANOSECONDS),
+                        amount: U128(raffle_details.held_balance()),
+                    },
+                )
+            }),
+            limit,
+        )
+    }
+
+    // Returns the raffles with the given ids in the same order, `None` for ids with no raffle
+    // registered and for the ids past the first `MAX_PAGE_LIMIT`. Hidden raffles are returned too
+    // since their id is already known.
+    pub fn get_raffles_by_ids(&self, raffle_ids: Vec<String>) -> Vec<Option<RaffleView>> {
+        raffle_ids
+            .into_iter()
+            .enumerate()
+            .map(|(index, raffle_id)| {
+                if index as u64 >= MAX_PAGE_LIMIT {
+                    return None;
+                }
+                self.find_raffle(raffle_id)
+                    .map(|(raffle_account_id, raffle_details)| {
+                        Self::raffle_view(raffle_account_id, &raffle_details)
+                    })
+            })
+            .collect()
+    }
+
+    // Lists the visible raffles still running that end within `window` ms, the ones ending first
+    // coming first, using the index of the raffles by end
+    pub fn get_raffles_ending_soon(
+        &self,
+        window: U64,
+        cursor: Option<Base64VecU8>,
+        limit: Option<u64>,
+    ) -> Page<RaffleView> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+        let now = env::block_timestamp();
+        let ends_by = now.saturating_add(window.0.saturating_mul(TO_FROM_NANOSECONDS));
+        // the lowest valid account id, bounding the index's keys of a given end
+        let first_key = (now + 1, AccountId::new_unchecked("0-0".to_string()));
+
+        // The cursor of a page fetched earlier can point to a raffle that has ended since
+        let start = match decode_cursor(cursor) {
+            Ok(Some(after)) if after >= first_key => Bound::Excluded(after),
+            Ok(_) => Bound::Included(first_key),
+            Err(_) => return Page::empty(),
+        };
+        Page::collect(
+            self.raffles_by_end
+                .range((start, Bound::Unbounded))
+                .take_while(|((end, _), _)| *end <= ends_by)
+                .filter_map(|(key, _)| self.visible_raffle_view(key)),
+            limit,
+        )
+    }
+
+    // Lists the raffles that ended and can be finalized, hidden ones included, in order of their
+    // end so that keepers know which ones to settle. Raffles waiting for the approval of their
+    // draw are left out.
+    pub fn get_finalizable_raffles(
+        &self,
+        cursor: Option<Base64VecU8>,
+        limit: Option<u64>,
+    ) -> Page<RaffleView> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+        let after = match decode_cursor(cursor) {
+            Ok(after) => after,
+            Err(_) => return Page::empty(),
+        };
+
+        Page::collect(
+            self.finalizable_raffles(after)
+                .map(|(end, raffle_account_id)| {
+                    let raffle_details = self.raffles.get(&raffle_account_id).unwrap();
+                    (
+                        (end, raffle_account_id.clone()),
+                        Self::raffle_view(raffle_account_id, &raffle_details),
+                    )
+                }),
+            limit,
+        )
+    }
+
+    // Lists the visible raffles whose prize is at least `min_prize` and which are in the given status,
+    // in order of their end. `cursor` and `limit` paginate over the matching raffles.
+    pub fn get_raffles_filtered(
+        &self,
+        min_prize: Option<U128>,
+        status: Option<RaffleStatus>,
+        cursor: Option<Base64VecU8>,
+        limit: Option<u64>,
+    ) -> Page<RaffleView> {
+        let min_prize = min_prize.map(|min_prize| min_prize.0).unwrap_or(0);
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+        let after = match decode_cursor(cursor) {
+            Ok(after) => after,
+            Err(_) => return Page::empty(),
+        };
+
+        Page::collect(
+            self.raffles_by_end_after(after)
+                .map(|key| {
+                    let raffle_details = self.raffles.get(&key.1).unwrap();
+                    (key, raffle_details)
+                })
+                .filter(|(_, raffle_details)| {
+                    raffle_details.visible
+                        && raffle_details.listed_prize() >= min_prize
+                        && status
+                            .as_ref()
+                            .map(|status| *status == Self::raffle_status(raffle_details))
+                            .unwrap_or(true)
+                })
+                .map(|(key, raffle_details)| {
+                    let view = Self::raffle_view(key.1.clone(), &raffle_details);
+                    (key, view)
+                }),
+            limit,
+        )
+    }
+
+    // Lists the visible raffles ordered by prize, end time or number of participants, in ascending
+    // order unless `descending` is set. `cursor` and `limit` paginate over the ordering, the
+    // cursor of a page only being valid for the same `sort_by` and `descending`.
+    pub fn get_raffles_sorted(
+        &self,
+        sort_by: RaffleSortKey,
+        descending: Option<bool>,
+        cursor: Option<Base64VecU8>,
+        limit: Option<u64>,
+    ) -> Page<RaffleView> {
+        let descending = descending.unwrap_or(false);
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+
+        match sort_by {
+            RaffleSortKey::Prize => {
+                self.sorted_raffles(&self.raffles_by_prize, descending, cursor, limit)
+            }
+            RaffleSortKey::End => {
+                self.sorted_raffles(&self.raffles_by_end, descending, cursor, limit)
+            }
+            RaffleSortKey::Participants => {
+                self.sorted_raffles(&self.raffles_by_participants, descending, cursor, limit)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fees::DEFAULT_CREATION_FEE;
+    use crate::raffle::{FLAG_WAITLIST, FLAG_WITHDRAWALS};
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn check_get_raffles_filtered() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(5 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(27 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705200)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        testing_env!(context.build());
+
+        let raffles = contract
+            .get_raffles_filtered(Some(U128(10 * ONE_NEAR)), None, None, None)
+            .items;
+        assert_eq!(raffles.len(), 2);
+
+        let raffles = contract
+            .get_raffles_filtered(
+                Some(U128(10 * ONE_NEAR)),
+                Some(RaffleStatus::Active),
+                None,
+                None,
+            )
+            .items;
+        assert_eq!(raffles.len(), 1);
+        assert_eq!(raffles[0].raffle_id, alice_account_id());
+        let storage_cost = contract
+            .raffles
+            .get(&alice_account_id())
+            .unwrap()
+            .storage_cost;
+        assert_eq!(
+            raffles[0].prize,
+            Some(U128(
+                17 * ONE_NEAR - DEFAULT_CREATION_FEE - ORGANIZER_BOND - storage_cost
+            ))
+        );
+
+        // the raffles come in order of their end, then of their id
+        let page = contract.get_raffles_filtered(None, None, None, Some(1));
+        assert_eq!(page.items[0].raffle_id, alice_account_id());
+        let page = contract.get_raffles_filtered(None, None, page.next_cursor, Some(1));
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].raffle_id, bob_account_id());
+    }
+
+    #[test]
+    fn check_get_raffles_sorted() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(5 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705321),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(27 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705421),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(mike_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(bob_account_id().to_string(), None, None, None);
+
+        let raffle_ids = |raffles: Vec<RaffleView>| -> Vec<AccountId> {
+            raffles.into_iter().map(|raffle| raffle.raffle_id).collect()
+        };
+
+        assert_eq!(
+            raffle_ids(
+                contract
+                    .get_raffles_sorted(RaffleSortKey::Prize, Some(true), None, None)
+                    .items
+            ),
+            vec![jacob_account_id(), alice_account_id(), bob_account_id()]
+        );
+        assert_eq!(
+            raffle_ids(
+                contract
+                    .get_raffles_sorted(RaffleSortKey::End, None, None, None)
+                    .items
+            ),
+            vec![bob_account_id(), jacob_account_id(), alice_account_id()]
+        );
+        assert_eq!(
+            raffle_ids(
+                contract
+                    .get_raffles_sorted(RaffleSortKey::Participants, Some(true), None, Some(1))
+                    .items
+            ),
+            vec![bob_account_id()]
+        );
+    }
+
+    #[test]
+    fn check_get_raffle_flags() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            Some(FLAG_WAITLIST | FLAG_WITHDRAWALS),
+            None,
+            None,
+        );
+
+        assert_eq!(
+            contract.get_raffle_flags(alice_account_id().to_string()),
+            Some(vec!["waitlist".to_string(), "withdrawals".to_string()])
+        );
+    }
+
+    #[test]
+    fn check_hidden_raffle_not_listed() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(5 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(0);
+        testing_env!(context.build());
+
+        contract.set_raffle_visibility(bob_account_id().to_string(), false);
+
+        let raffles = contract.get_raffles_filtered(None, None, None, None).items;
+        assert_eq!(raffles.len(), 1);
+        assert_eq!(raffles[0].raffle_id, alice_account_id());
+
+        let raffles = contract
+            .get_raffles_sorted(RaffleSortKey::Prize, None, None, None)
+            .items;
+        assert_eq!(raffles.len(), 1);
+        assert_eq!(raffles[0].raffle_id, alice_account_id());
+
+        // the hidden raffle can still be joined with its id
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.participate(bob_account_id().to_string(), None, None, None);
+        assert!(contract
+            .raffles
+            .get(&bob_account_id())
+            .unwrap()
+            .participants
+            .get(&jacob_account_id())
+            .is_some());
+    }
+
+    #[test]
+    fn check_get_participation_timeline() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644526505121),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.attached_deposit(2 * ONE_NEAR);
+        for (account_id, timestamp) in [
+            (bob_account_id(), 1644353705125),
+            (jacob_account_id(), 1644353765125),
+            (mike_account_id(), 1644443705125),
+        ] {
+            context.block_timestamp(timestamp * TO_FROM_NANOSECONDS);
+            context.predecessor_account_id(account_id);
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+        }
+
+        let timeline = contract
+            .get_participation_timeline(alice_account_id().to_string(), None)
+            .unwrap();
+        assert_eq!(timeline.len(), 2);
+        assert_eq!(timeline[0].start, 1644350400000);
+        assert_eq!(timeline[0].entries, 2);
+        assert_eq!(timeline[1].start, 1644440400000);
+        assert_eq!(timeline[1].entries, 1);
+
+        let timeline = contract
+            .get_participation_timeline(alice_account_id().to_string(), Some(TimelineInterval::Day))
+            .unwrap();
+        assert_eq!(timeline.len(), 2);
+        assert_eq!(timeline[0].start, 1644278400000);
+        assert_eq!(timeline[1].start, 1644364800000);
+    }
+
+    #[test]
+    fn check_get_fee_config_and_limits() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        let fee_config = contract.get_fee_config();
+        assert_eq!(fee_config.creation_flat, U128(DEFAULT_CREATION_FEE));
+        assert_eq!(fee_config.min_locked_amount, U128(MIN_LOCKED_AMOUNT));
+
+        assert!(contract.get_limits().entry_rate_limit.is_none());
+
+        contract.set_entry_rate_limit(5, 60000);
+
+        let limits = contract.get_limits();
+        assert_eq!(limits.max_participants, 256);
+        assert_eq!(limits.entry_rate_limit.as_ref().unwrap().max_entries, 5);
+        assert_eq!(limits.entry_rate_limit.as_ref().unwrap().window, 60000);
+        assert_eq!(
+            limits.reclaim_grace_period,
+            RECLAIM_GRACE_PERIOD / TO_FROM_NANOSECONDS
+        );
+    }
+
+    #[test]
+    fn check_get_finalizable_raffles() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_raffle_visibility(alice_account_id().to_string(), false);
+
+        context.predecessor_account_id(jacob_account_id());
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705900),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705600 * TO_FROM_NANOSECONDS);
+        testing_env!(context.build());
+
+        let raffles = contract.get_finalizable_raffles(None, None).items;
+        assert_eq!(raffles.len(), 1);
+        assert_eq!(raffles[0].raffle_id, alice_account_id());
+
+        context.block_timestamp(1644353706000 * TO_FROM_NANOSECONDS);
+        testing_env!(context.build());
+
+        assert_eq!(contract.get_finalizable_raffles(None, None).items.len(), 2);
+        let page = contract.get_finalizable_raffles(None, Some(1));
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(
+            contract
+                .get_finalizable_raffles(page.next_cursor, Some(1))
+                .items
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn check_get_raffles_ending_soon() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        for (account_id, end) in [
+            (alice_account_id(), 1644353705521),
+            (bob_account_id(), 1644353705300),
+            (jacob_account_id(), 1644353705900),
+        ] {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(17 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.register_raffle(Some(U64(1644353705121)), U64(end), None, None, None, None);
+        }
+
+        context.block_timestamp(1644353705300 * TO_FROM_NANOSECONDS);
+        testing_env!(context.build());
+
+        // bob's raffle has just ended and jacob's one ends after the window
+        let raffles = contract.get_raffles_ending_soon(U64(300), None, None).items;
+        assert_eq!(raffles.len(), 1);
+        assert_eq!(raffles[0].raffle_id, alice_account_id());
+
+        assert_eq!(
+            contract
+                .get_raffles_ending_soon(U64(600), None, None)
+                .items
+                .len(),
+            2
+        );
+        assert_eq!(
+            contract
+                .get_raffles_ending_soon(U64(u64::MAX), None, None)
+                .items
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn check_get_raffles_by_ids() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let raffles = contract.get_raffles_by_ids(vec![
+            bob_account_id().to_string(),
+            alice_account_id().to_string(),
+        ]);
+        assert_eq!(raffles.len(), 2);
+        assert!(raffles[0].is_none());
+        assert_eq!(raffles[1].as_ref().unwrap().raffle_id, alice_account_id());
+    }
+
+    #[test]
+    fn check_views_of_missing_raffles() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let contract = RaffleDapp::new();
+
+        assert!(contract
+            .get_raffle_flags(bob_account_id().to_string())
+            .is_none());
+        assert!(contract
+            .get_storage_usage("Not an id!".to_string())
+            .is_none());
+        assert!(contract
+            .get_odds(bob_account_id().to_string(), "Not an id!".to_string())
+            .is_none());
+        assert!(contract
+            .get_entries(bob_account_id().to_string(), None, None)
+            .is_none());
+        assert!(contract.get_raffles_by_ids(vec!["Not an id!".to_string()])[0].is_none());
+        assert!(contract.get_bundles("Not an id!".to_string()).is_empty());
+        assert_eq!(
+            contract
+                .can_participate(
+                    "Not an id!".to_string(),
+                    bob_account_id().to_string(),
+                    U128(ONE_NEAR),
+                    None,
+                    None,
+                )
+                .reasons,
+            vec![IneligibilityReason::NoRaffle]
+        );
+
+        let page = contract.get_raffles_filtered(None, None, Some(Base64VecU8(vec![1])), None);
+        assert!(page.items.is_empty());
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn check_get_odds() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        for account_id in [bob_account_id(), jacob_account_id()] {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(2 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+        }
+
+        let odds = contract
+            .get_odds(alice_account_id().to_string(), bob_account_id().to_string())
+            .unwrap();
+        assert_eq!((odds.tickets, odds.total_tickets), (1, 2));
+
+        let odds = contract
+            .get_odds(
+                alice_account_id().to_string(),
+                mike_account_id().to_string(),
+            )
+            .unwrap();
+        assert_eq!((odds.tickets, odds.total_tickets), (0, 2));
+    }
+
+    #[test]
+    fn check_get_entries() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        for (account_id, entered_at) in [
+            (jacob_account_id(), 1644353705125),
+            (bob_account_id(), 1644353705130),
+            (mike_account_id(), 1644353705135),
+        ] {
+            context.block_timestamp(entered_at * TO_FROM_NANOSECONDS);
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(2 * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+        }
+
+        // bob leaves, and jacob hands his ticket over, keeping his place
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+        contract.leave_raffle(alice_account_id().to_string());
+
+        context.predecessor_account_id(jacob_account_id());
+        context.attached_deposit(1);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+        contract.transfer_ticket(
+            alice_account_id().to_string(),
+            bob_account_id().to_string(),
+            None,
+        );
+
+        let entries: Vec<(AccountId, u64)> = contract
+            .get_entries(alice_account_id().to_string(), None, None)
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|entry| (entry.account_id, entry.entered_at.0))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (bob_account_id(), 1644353705125),
+                (mike_account_id(), 1644353705135)
+            ]
+        );
+
+        // bob leaving between two pages doesn't make the next page skip mike
+        let page = contract
+            .get_entries(alice_account_id().to_string(), None, Some(1))
+            .unwrap();
+        assert_eq!(page.items[0].account_id, bob_account_id());
+
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+        contract.leave_raffle(alice_account_id().to_string());
+
+        let page = contract
+            .get_entries(alice_account_id().to_string(), page.next_cursor, Some(1))
+            .unwrap();
+        assert_eq!(page.items[0].account_id, mike_account_id());
+    }
+
+    #[test]
+    fn check_get_storage_usage() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let usage = contract
+            .get_storage_usage(alice_account_id().to_string())
+            .unwrap();
+        assert!(usage.record > 0);
+        assert_eq!(usage.entries, 0);
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        context.predecessor_account_id(bob_account_id());
+        context.attached_deposit(2 * ONE_NEAR);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.participate(alice_account_id().to_string(), None, None, None);
+
+        let usage = contract
+            .get_storage_usage(alice_account_id().to_string())
+            .unwrap();
+        assert!(usage.entries > 0);
+        assert_eq!(usage.total, usage.record + usage.entries);
+        assert_eq!(
+            usage.cost.0,
+            usage.total as Balance * env::storage_byte_cost()
+        );
+
+        // leaving releases the storage of the entry
+        context.attached_deposit(0);
+        context.storage_usage(env::storage_usage());
+        testing_env!(context.build());
+
+        contract.leave_raffle(alice_account_id().to_string());
+        assert_eq!(
+            contract
+                .get_storage_usage(alice_account_id().to_string())
+                .unwrap()
+                .entries,
+            0
+        );
+    }
+
+    #[test]
+    fn check_get_weighted_odds() {
+        let mut context = get_context();
+        context.predecessor_account_id(raffle_dapp_account_id());
+        testing_env!(context.build());
+
+        let mut contract = RaffleDapp::new();
+
+        context.predecessor_account_id(alice_account_id());
+        context.attached_deposit(17 * ONE_NEAR);
+        testing_env!(context.build());
+
+        contract.register_raffle(
+            Some(U64(1644353705121)),
+            U64(1644353705521),
+            None,
+            Some(FLAG_WEIGHTED_ODDS),
+            None,
+            None,
+        );
+
+        context.block_timestamp(1644353705125 * TO_FROM_NANOSECONDS);
+        for (account_id, locked_tokens) in [(bob_account_id(), 2), (jacob_account_id(), 6)] {
+            context.predecessor_account_id(account_id);
+            context.attached_deposit(locked_tokens * ONE_NEAR);
+            context.storage_usage(env::storage_usage());
+            testing_env!(context.build());
+
+            contract.participate(alice_account_id().to_string(), None, None, None);
+        }
+
+        // bob locked a quarter of the tokens
+        let odds = contract
+            .get_odds(alice_account_id().to_string(), bob_account_id().to_string())
+            .unwrap();
+        assert_eq!(odds.total_tickets / odds.tickets, 4);
+    }
+}